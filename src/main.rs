@@ -9,6 +9,7 @@ use std::thread;
 use tokio::sync::{mpsc, oneshot};
 
 mod models;
+mod settings;
 mod storage;
 mod ui;
 mod utils;
@@ -16,7 +17,8 @@ mod view_models;
 mod vk_provider;
 
 fn main() {
-    Builder::from_env(Env::default().default_filter_or("debug,reqwest=info"))
+    let settings = settings::Settings::load();
+    Builder::from_env(Env::default().default_filter_or(settings.log_filter.as_str()))
         .target(Target::Stdout)
         .format_timestamp(Some(TimestampPrecision::Seconds))
         .init();
@@ -52,6 +54,7 @@ fn main() {
                 rx_stop,
                 tx_msg,
                 rx_req,
+                settings,
                 tokio_stack_size,
                 tokio_thread_pool_size,
             );
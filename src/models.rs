@@ -1,8 +1,29 @@
 mod news_item;
-pub use news_item::{Link, NewsItemModel, Photo};
+pub use news_item::{format_count, Link, NewsIndex, NewsItemId, NewsItemModel, Photo};
 
 mod news_source;
-pub use news_source::NewsSourceModel;
+pub use news_source::{arrange_sources, NewsSourceModel, SourcesOrder};
 
 mod user;
 pub use user::UserModel;
+
+mod conversation;
+pub use conversation::ConversationModel;
+
+mod message;
+pub use message::MessageModel;
+
+mod chat_event;
+pub use chat_event::ChatEvent;
+
+mod friend;
+pub use friend::{arrange_friends, FriendModel, FriendsOrder};
+
+mod community;
+pub use community::{community_kind, format_members, CommunityModel};
+
+mod comment;
+pub use comment::CommentModel;
+
+mod post_draft;
+pub use post_draft::PostDraft;
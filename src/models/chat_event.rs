@@ -0,0 +1,13 @@
+use super::MessageModel;
+
+/// Real-time event of the user's chats
+pub enum ChatEvent {
+    /// Message has been sent or received
+    NewMessage(MessageModel),
+    /// Incoming messages of the conversation have been read by user
+    Read(i64),
+    /// A number of conversations having unread messages
+    Unread(i64),
+    /// Friend has become online or offline
+    Online { user_id: i64, online: bool },
+}
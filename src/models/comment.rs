@@ -0,0 +1,24 @@
+use super::Photo;
+use std::fmt;
+
+#[derive(Clone)]
+pub struct CommentModel {
+    pub id: i64,
+    pub from_id: i64, // positive for users, negative for groups
+    // the top-level comment the reply belongs to, zero for top-level comments
+    pub thread_id: i64,
+    pub thread_count: i64, // replies to the top-level comment
+    pub timestamp: i64,    // unix time of the comment
+    pub author: String,
+    pub avatar: String,
+    pub reply_to: String, // name of the author replied to
+    pub datetime: String,
+    pub text: String,
+    pub photos: Option<Vec<Photo>>,
+}
+
+impl fmt::Display for CommentModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "comment {} in thread {}", self.id, self.thread_id)
+    }
+}
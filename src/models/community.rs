@@ -0,0 +1,67 @@
+use std::fmt;
+
+#[derive(Clone)]
+pub struct CommunityModel {
+    pub id: i64, // positive, the community posts news as -id
+    pub name: String,
+    pub avatar: String,
+    pub uri: String,         // url to page https://vk.com/club1
+    pub kind: String,        // public page, event or open, closed, private group
+    pub members: String,     // formatted count of members, empty if unknown
+    pub description: String, // markup
+    pub is_member: bool,
+    pub muted: bool, // its posts are not shown in news
+}
+
+impl fmt::Display for CommunityModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "community {} {}", self.id, &self.name)
+    }
+}
+
+/// Returns readable kind of the community of type group, page or event with is_closed 0, 1 or 2
+pub fn community_kind(type_: &str, is_closed: u8) -> String {
+    match (type_, is_closed) {
+        ("page", _) => "public page",
+        ("event", _) => "event",
+        (_, 1) => "closed group",
+        (_, 2) => "private group",
+        _ => "open group",
+    }
+    .to_string()
+}
+
+/// Returns count of members with thousands separated, e.g. "1 266 277 members"
+pub fn format_members(count: i64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(' ');
+        }
+        grouped.push(c);
+    }
+    if count == 1 {
+        format!("{} member", grouped)
+    } else {
+        format!("{} members", grouped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kind_and_members() {
+        assert_eq!(community_kind("page", 0), "public page");
+        assert_eq!(community_kind("event", 1), "event");
+        assert_eq!(community_kind("group", 0), "open group");
+        assert_eq!(community_kind("group", 1), "closed group");
+        assert_eq!(community_kind("group", 2), "private group");
+        assert_eq!(format_members(1), "1 member");
+        assert_eq!(format_members(999), "999 members");
+        assert_eq!(format_members(1000), "1 000 members");
+        assert_eq!(format_members(1266277), "1 266 277 members");
+    }
+}
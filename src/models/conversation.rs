@@ -0,0 +1,18 @@
+use std::fmt;
+
+#[derive(Clone)]
+pub struct ConversationModel {
+    pub peer_id: i64,   // user id, -group id or 2000000000 + chat id
+    pub timestamp: i64, // unix time of the last message
+    pub title: String,
+    pub avatar: String,
+    pub last_message: String,
+    pub datetime: String,
+    pub unread: i64,
+}
+
+impl fmt::Display for ConversationModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conversation {} {}", self.peer_id, &self.title)
+    }
+}
@@ -0,0 +1,155 @@
+use crate::utils::local_from_timestamp;
+use std::fmt;
+
+#[derive(Clone)]
+pub struct FriendModel {
+    pub id: i64,
+    pub name: String,
+    pub avatar: String,
+    pub uri: String, // url to page https://vk.com/id1
+    pub online: bool,
+    pub last_seen: i64,     // unix time, 0 if unknown
+    pub lists: Vec<String>, // names of the friend lists the friend is in
+}
+
+impl FriendModel {
+    /// Online or the last time the friend has been seen
+    pub fn status(&self) -> String {
+        if self.online {
+            "online".to_string()
+        } else if self.last_seen > 0 {
+            format!(
+                "last seen {}",
+                local_from_timestamp(self.last_seen).format("%d.%m.%Y %H:%M")
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    /// The friend has gone online or offline at the moment
+    pub fn set_online(&mut self, online: bool, timestamp: i64) {
+        if self.online && !online {
+            self.last_seen = timestamp;
+        }
+        self.online = online;
+    }
+
+    fn matches(&self, search: &str) -> bool {
+        search.is_empty() || self.name.to_lowercase().contains(search)
+    }
+}
+
+impl fmt::Display for FriendModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "friend {} {}", self.id, &self.name)
+    }
+}
+
+/// Order of the friends page
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FriendsOrder {
+    // online first, then by name
+    Online,
+    // the most recently seen first, online ones are seen now
+    LastSeen,
+    Name,
+}
+
+impl FriendsOrder {
+    /// Parses id of the order as it is in UI, unknown ones are by name
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "online" => FriendsOrder::Online,
+            "last_seen" => FriendsOrder::LastSeen,
+            _ => FriendsOrder::Name,
+        }
+    }
+}
+
+/// Returns the friends whose names contain search (case insensitive) and who are in the list if it is set,
+/// ordered as requested
+pub fn arrange_friends(
+    friends: &[FriendModel],
+    search: &str,
+    list: Option<&str>,
+    order: FriendsOrder,
+) -> Vec<FriendModel> {
+    let search = search.trim().to_lowercase();
+    let mut selected: Vec<FriendModel> = friends
+        .iter()
+        .filter(|friend| friend.matches(&search))
+        .filter(|friend| list.map_or(true, |list| friend.lists.iter().any(|l| l == list)))
+        .cloned()
+        .collect();
+    let by_name = |a: &FriendModel, b: &FriendModel| a.name.cmp(&b.name);
+    match order {
+        FriendsOrder::Online => {
+            selected.sort_by(|a, b| b.online.cmp(&a.online).then_with(|| by_name(a, b)))
+        }
+        FriendsOrder::LastSeen => selected.sort_by(|a, b| {
+            let seen = |f: &FriendModel| if f.online { i64::MAX } else { f.last_seen };
+            seen(b).cmp(&seen(a)).then_with(|| by_name(a, b))
+        }),
+        FriendsOrder::Name => selected.sort_by(by_name),
+    }
+    selected
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn friend(id: i64, name: &str, online: bool, last_seen: i64, lists: &[&str]) -> FriendModel {
+        FriendModel {
+            id,
+            name: name.to_string(),
+            avatar: String::new(),
+            uri: String::new(),
+            online,
+            last_seen,
+            lists: lists.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn search_sort_and_group() {
+        let friends = vec![
+            friend(1, "Olga Ivanova", false, 100, &["Family"]),
+            friend(2, "Ivan Petrov", true, 50, &["Family", "Colleagues"]),
+            friend(3, "Boris Smirnov", false, 200, &["Colleagues"]),
+        ];
+        let ids = |selected: Vec<FriendModel>| selected.iter().map(|f| f.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(arrange_friends(&friends, "", None, FriendsOrder::Online)),
+            vec![2, 3, 1]
+        );
+        assert_eq!(
+            ids(arrange_friends(&friends, "", None, FriendsOrder::LastSeen)),
+            vec![2, 3, 1]
+        );
+        assert_eq!(
+            ids(arrange_friends(&friends, "", None, FriendsOrder::Name)),
+            vec![3, 2, 1]
+        );
+        // search is case insensitive
+        assert_eq!(
+            ids(arrange_friends(&friends, " IVAN", None, FriendsOrder::Name)),
+            vec![2, 1]
+        );
+        assert_eq!(
+            ids(arrange_friends(
+                &friends,
+                "",
+                Some("Family"),
+                FriendsOrder::Name
+            )),
+            vec![2, 1]
+        );
+        // the friend gone offline has been seen just now
+        let mut ivan = friends[1].clone();
+        ivan.set_online(false, 300);
+        assert_eq!(ivan.last_seen, 300);
+        assert!(ivan.status().starts_with("last seen"));
+    }
+}
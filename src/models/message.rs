@@ -0,0 +1,22 @@
+use super::Photo;
+use std::fmt;
+
+#[derive(Clone)]
+pub struct MessageModel {
+    pub id: i64,
+    pub peer_id: i64,   // conversation the message belongs to
+    pub from_id: i64,   // positive for users, negative for groups
+    pub timestamp: i64, // unix time of the message
+    pub out: bool,      // sent by the user
+    pub author: String,
+    pub avatar: String,
+    pub datetime: String,
+    pub text: String,
+    pub photos: Option<Vec<Photo>>,
+}
+
+impl fmt::Display for MessageModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "message {} in {}", self.id, self.peer_id)
+    }
+}
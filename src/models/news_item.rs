@@ -1,8 +1,14 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Photo {
-    pub uri: String,
+    pub uri: String, // local file
     pub text: String,
+    // address the file has been downloaded from, empty for the files of user
+    #[serde(default)]
+    pub source: String,
 }
 
 impl fmt::Display for Photo {
@@ -11,12 +17,17 @@ impl fmt::Display for Photo {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Link {
     pub uri: String,
     pub text: String,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct NewsItemModel {
+    pub source_id: i64, // positive for users, negative for groups
+    pub post_id: i64,   // zero if item is not a post
+    pub timestamp: i64, // unix time of the item
     pub author: String,
     pub avatar: String,
     pub itemtype: String,
@@ -24,4 +35,200 @@ pub struct NewsItemModel {
     pub content: String,
     pub photos: Option<Vec<Photo>>,
     pub links: Option<Vec<Link>>,
+    // engagement counters, they change every time the post is re-fetched
+    #[serde(default)]
+    pub likes: i64,
+    #[serde(default)]
+    pub comments: i64,
+    #[serde(default)]
+    pub reposts: i64,
+    #[serde(default)]
+    pub views: i64,
+    // user likes the post
+    #[serde(default)]
+    pub liked: bool,
+    // id of the first photo or friend the item is about if it is not a post, zero otherwise
+    #[serde(default)]
+    pub content_id: i64,
+}
+
+/// Identity of the news item, the same item received more than once has the same identity
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct NewsItemId {
+    pub source_id: i64,
+    pub post_id: i64, // time of the item if it is not a post
+    pub itemtype: String,
+    // tells apart the items which are not posts of the same source and time, zero for posts
+    pub content_id: i64,
+}
+
+impl NewsItemModel {
+    pub fn id(&self) -> NewsItemId {
+        let is_post = self.post_id != 0;
+        NewsItemId {
+            source_id: self.source_id,
+            post_id: if is_post {
+                self.post_id
+            } else {
+                self.timestamp
+            },
+            itemtype: self.itemtype.clone(),
+            content_id: if is_post { 0 } else { self.content_id },
+        }
+    }
+}
+
+/// Displayed news items by their identity, so the item received more than once,
+/// e.g. on overlapping pages or re-fetched with new counters, is updated instead of duplicating
+pub struct NewsIndex<T> {
+    items: HashMap<NewsItemId, T>,
+}
+
+impl<T: Clone> NewsIndex<T> {
+    pub fn new() -> Self {
+        NewsIndex {
+            items: HashMap::new(),
+        }
+    }
+
+    /// Returns the item made of the model if it has not been received yet, the known one is updated
+    pub fn add(
+        &mut self,
+        model: &NewsItemModel,
+        make: impl FnOnce(&NewsItemModel) -> T,
+        update: impl FnOnce(&T, &NewsItemModel),
+    ) -> Option<T> {
+        let id = model.id();
+        match self.items.get(&id) {
+            Some(known) => {
+                log::debug!("update news item {}", &id);
+                update(known, model);
+                None
+            }
+            None => {
+                let item = make(model);
+                self.items.insert(id, item.clone());
+                Some(item)
+            }
+        }
+    }
+
+    pub fn get(&self, id: &NewsItemId) -> Option<&T> {
+        self.items.get(id)
+    }
+
+    /// Forgets the items of the source, returns them
+    pub fn remove_source(&mut self, source_id: i64) -> Vec<T> {
+        let mut removed = Vec::new();
+        self.items.retain(|id, item| {
+            if id.source_id == source_id {
+                removed.push(item.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+impl fmt::Display for NewsItemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}_{}", self.itemtype, self.source_id, self.post_id)
+    }
+}
+
+/// Returns the counter shortened the way vk.com does, e.g. "987", "10.1K" or "1.2M"
+pub fn format_count(count: i64) -> String {
+    if count < 1_000 {
+        count.to_string()
+    } else if count < 1_000_000 {
+        format!("{:.1}K", (count / 100) as f64 / 10.0)
+    } else {
+        format!("{:.1}M", (count / 100_000) as f64 / 10.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(source_id: i64, post_id: i64, timestamp: i64, itemtype: &str) -> NewsItemModel {
+        NewsItemModel {
+            source_id,
+            post_id,
+            timestamp,
+            author: String::new(),
+            avatar: String::new(),
+            itemtype: itemtype.to_string(),
+            datetime: String::new(),
+            content: String::new(),
+            photos: None,
+            links: None,
+            likes: 0,
+            comments: 0,
+            reposts: 0,
+            views: 0,
+            liked: false,
+            content_id: 0,
+        }
+    }
+
+    // indexes the page, returns the new items, the known ones get the counters of the page
+    fn add_page(index: &mut NewsIndex<NewsItemModel>, page: &[NewsItemModel]) -> Vec<i64> {
+        page.iter()
+            .filter_map(|model| {
+                index.add(
+                    model,
+                    |model| model.clone(),
+                    |known, model| assert_eq!(known.id(), model.id()),
+                )
+            })
+            .map(|model| model.post_id)
+            .collect()
+    }
+
+    #[test]
+    fn index_overlapping_pages() {
+        let mut index = NewsIndex::new();
+        let first = vec![item(-1, 13, 300, "post"), item(-1, 12, 200, "post")];
+        assert_eq!(add_page(&mut index, &first), vec![13, 12]);
+        // the overlapping part is not added once more
+        let second = vec![item(-1, 12, 200, "post"), item(-1, 11, 100, "post")];
+        assert_eq!(add_page(&mut index, &second), vec![11]);
+        assert_eq!(index.remove_source(-1).len(), 3);
+        assert!(index.get(&first[0].id()).is_none());
+    }
+
+    #[test]
+    fn index_reposts_and_photos() {
+        let mut index = NewsIndex::new();
+        // reposts of the same post are the own posts of the sources
+        let reposts = vec![item(-1, 5, 100, "post"), item(-2, 5, 100, "post")];
+        assert_eq!(add_page(&mut index, &reposts).len(), 2);
+        // photos have no post_id, the ones of the same source and time differ in the photos
+        let mut photo = item(-1, 0, 100, "photo");
+        photo.content_id = 457239017;
+        let mut other_photo = item(-1, 0, 100, "photo");
+        other_photo.content_id = 457239018;
+        let page = vec![photo.clone(), other_photo];
+        assert_eq!(add_page(&mut index, &page).len(), 2);
+        // and differ from the post of the same source and time
+        assert_ne!(photo.id(), reposts[0].id());
+        // the same photo received once more is known
+        assert!(add_page(&mut index, &[photo]).is_empty());
+    }
+
+    #[test]
+    fn shorten_counters() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(987), "987");
+        assert_eq!(format_count(10089), "10.0K");
+        assert_eq!(format_count(10189), "10.1K");
+        assert_eq!(format_count(1_266_277), "1.2M");
+    }
 }
@@ -1,13 +1,33 @@
+use crate::utils::local_from_timestamp;
 use std::fmt;
 
 #[derive(Clone)]
 pub struct NewsSourceModel {
-    pub id: i64,
+    pub id: i64,         // source_id of its news, negative for groups
     pub name: String,    // RT
     pub avatar: String,  // url
     pub desc: String,    // Интернет СМИ
     pub uri: String,     // url to page https://vk.com/rt_russian
     pub comment: String, // 1 266 277 подписчиков
+    pub posts: u64,      // a number of the source posts received
+    pub last_post: i64,  // unix time of the most recent post, 0 if there are none
+    pub favourite: bool,
+    pub muted: bool,
+}
+
+impl NewsSourceModel {
+    /// A number of posts and the time of the most recent one
+    pub fn activity(&self) -> String {
+        if self.last_post > 0 {
+            format!(
+                "{} posts, last {}",
+                self.posts,
+                local_from_timestamp(self.last_post).format("%d.%m.%Y %H:%M")
+            )
+        } else {
+            String::new()
+        }
+    }
 }
 
 impl fmt::Display for NewsSourceModel {
@@ -15,3 +35,96 @@ impl fmt::Display for NewsSourceModel {
         write!(f, "source {}", &self.name)
     }
 }
+
+/// Order of the sources pane
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SourcesOrder {
+    // the most posts first
+    Activity,
+    // the most recently posted first
+    Recent,
+    Name,
+}
+
+impl SourcesOrder {
+    /// Parses id of the order as it is in UI, unknown ones are by activity
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "recent" => SourcesOrder::Recent,
+            "name" => SourcesOrder::Name,
+            _ => SourcesOrder::Activity,
+        }
+    }
+}
+
+/// Returns the sources ordered as requested, favourite ones go first and muted ones go last
+pub fn arrange_sources(sources: &[NewsSourceModel], order: SourcesOrder) -> Vec<NewsSourceModel> {
+    let mut arranged = sources.to_vec();
+    arranged.sort_by(|a, b| {
+        b.favourite
+            .cmp(&a.favourite)
+            .then_with(|| a.muted.cmp(&b.muted))
+            .then_with(|| match order {
+                SourcesOrder::Activity => b
+                    .posts
+                    .cmp(&a.posts)
+                    .then_with(|| b.last_post.cmp(&a.last_post)),
+                SourcesOrder::Recent => b.last_post.cmp(&a.last_post),
+                SourcesOrder::Name => std::cmp::Ordering::Equal,
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    arranged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source(id: i64, name: &str, posts: u64, last_post: i64) -> NewsSourceModel {
+        NewsSourceModel {
+            id,
+            name: name.to_string(),
+            avatar: String::new(),
+            desc: String::new(),
+            uri: String::new(),
+            comment: String::new(),
+            posts,
+            last_post,
+            favourite: false,
+            muted: false,
+        }
+    }
+
+    #[test]
+    fn favourites_first_muted_last() {
+        let mut sources = vec![
+            source(1, "Olga Ivanova", 3, 100),
+            source(-2, "RT", 10, 50),
+            source(-3, "Habr", 1, 200),
+            source(4, "Boris Smirnov", 10, 150),
+        ];
+        let ids =
+            |arranged: Vec<NewsSourceModel>| arranged.iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(arrange_sources(&sources, SourcesOrder::Activity)),
+            vec![4, -2, 1, -3]
+        );
+        assert_eq!(
+            ids(arrange_sources(&sources, SourcesOrder::Recent)),
+            vec![-3, 4, 1, -2]
+        );
+        assert_eq!(
+            ids(arrange_sources(&sources, SourcesOrder::Name)),
+            vec![4, -3, 1, -2]
+        );
+        sources[0].favourite = true;
+        sources[3].muted = true;
+        assert_eq!(
+            ids(arrange_sources(&sources, SourcesOrder::Activity)),
+            vec![1, -2, -3, 4]
+        );
+        assert_eq!(SourcesOrder::from_id("recent"), SourcesOrder::Recent);
+        assert_eq!(SourcesOrder::from_id(""), SourcesOrder::Activity);
+    }
+}
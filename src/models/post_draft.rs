@@ -0,0 +1,20 @@
+use std::fmt;
+
+#[derive(Clone)]
+pub struct PostDraft {
+    pub group_id: i64, // positive id of the community to post to, zero for the user's own wall
+    pub text: String,
+    pub photos: Vec<String>, // files to attach
+    // the post is signed by the community rather than by the user
+    pub from_group: bool,
+    pub publish_date: Option<i64>, // unix time to publish the postponed post at
+}
+
+impl fmt::Display for PostDraft {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.group_id {
+            0 => write!(f, "post to own wall"),
+            group_id => write!(f, "post to wall of community {}", group_id),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 
+#[derive(Clone)]
 pub struct UserModel {
     pub name: String,
     pub image: String,
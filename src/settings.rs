@@ -0,0 +1,287 @@
+//! User settings persisted in `$XDG_CONFIG_HOME/gvk/config.toml`.
+//!
+//! Every setting is optional in the file, missing ones take their default values.
+//! The file is watched by UI, so the changes are applied while the application is running,
+//! except for log_filter and cache_dir which take effect on the next start.
+use serde::{Deserialize, Serialize};
+use std::env::vars_os;
+use std::fmt;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// limits of the values
+const MIN_POLL_INTERVAL_SEC: u64 = 10;
+const MAX_POLL_INTERVAL_SEC: u64 = 86_400; // 24 hours
+const MIN_FRIENDS_POLL_INTERVAL_SEC: u64 = 60;
+const MIN_INITIAL_NEWS_SEC: u64 = 60;
+const MAX_INITIAL_NEWS_SEC: u64 = 604_800; // a week
+const MAX_NEWS_PAGE_SIZE: u32 = 100; // vk.com does not return more
+/// Size types of the photos as vk.com names them, <https://vk.com/dev/photo_sizes>
+pub const PHOTO_SIZE_TYPES: [&str; 10] = ["s", "m", "x", "o", "p", "q", "r", "y", "z", "w"];
+/// Access scopes of the user token, <https://vk.com/dev/permissions>
+pub const AUTH_SCOPES: [&str; 19] = [
+    "notify",
+    "friends",
+    "photos",
+    "audio",
+    "video",
+    "stories",
+    "pages",
+    "status",
+    "notes",
+    "messages",
+    "wall",
+    "ads",
+    "offline",
+    "docs",
+    "groups",
+    "notifications",
+    "stats",
+    "email",
+    "market",
+];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Interval between two subsequent queries of the news
+    pub poll_interval_sec: u64,
+    /// Interval between two subsequent queries of the friends
+    pub friends_poll_interval_sec: u64,
+    /// A time interval to get news for on the first start
+    pub initial_news_sec: u64,
+    /// A number of news items to request per page
+    pub news_page_size: u32,
+    /// Preferred size types of the photos, the most preferred first
+    pub photo_sizes: Vec<String>,
+    /// Filter of the log messages in env_logger format, e.g. "info,gvk=debug"
+    pub log_filter: String,
+    /// Directory to cache files in, if not set it is $XDG_CACHE_HOME/gvk
+    pub cache_dir: Option<String>,
+    /// Id of the vk.com application to authorize, forks might register their own one
+    pub client_id: String,
+    /// Access scopes to request on login, the ones required by features are requested on demand
+    pub scopes: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            poll_interval_sec: 60,
+            friends_poll_interval_sec: 300, // 5 minutes
+            initial_news_sec: 3_600,        // 60 minutes
+            news_page_size: 100,
+            photo_sizes: ["y", "x", "r", "q", "p", "o", "m", "s"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            log_filter: "debug,reqwest=info".to_string(),
+            cache_dir: None,
+            client_id: "7720259".to_string(),
+            scopes: [
+                "offline", "friends", "groups", "photos", "audio", "video", "stories", "status",
+                "notes", "wall",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        }
+    }
+}
+
+pub enum SettingsError {
+    // reading config file failed
+    Read(String),
+    // config file is not valid TOML or has wrong types
+    Parse(String),
+    // setting has value out of range
+    Invalid(String),
+    // writing config file failed
+    Write(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Read(err) => write!(f, "failed reading settings: {}", err),
+            SettingsError::Parse(err) => write!(f, "failed parsing settings: {}", err),
+            SettingsError::Invalid(err) => write!(f, "invalid setting: {}", err),
+            SettingsError::Write(err) => write!(f, "failed writing settings: {}", err),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from the config file, the defaults are used if it is absent or invalid
+    pub fn load() -> Self {
+        let pathname = config_path();
+        if !pathname.exists() {
+            return Settings::default();
+        }
+        match Settings::load_from(&pathname) {
+            Ok(settings) => settings,
+            Err(e) => {
+                // logger might not be initialized yet
+                eprintln!("{}, using defaults", e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn load_from(pathname: &Path) -> Result<Self, SettingsError> {
+        let content =
+            read_to_string(pathname).map_err(|e| SettingsError::Read(format!("{}", e)))?;
+        Settings::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, SettingsError> {
+        let settings = toml::from_str::<Settings>(content)
+            .map_err(|e| SettingsError::Parse(format!("{}", e)))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        self.save_to(&config_path())
+    }
+
+    pub fn save_to(&self, pathname: &Path) -> Result<(), SettingsError> {
+        self.validate()?;
+        let content = toml::to_string(self).map_err(|e| SettingsError::Write(format!("{}", e)))?;
+        if let Some(dir) = pathname.parent() {
+            create_dir_all(dir).map_err(|e| SettingsError::Write(format!("{}", e)))?;
+        }
+        write(pathname, content).map_err(|e| SettingsError::Write(format!("{}", e)))
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.poll_interval_sec < MIN_POLL_INTERVAL_SEC
+            || self.poll_interval_sec > MAX_POLL_INTERVAL_SEC
+        {
+            return Err(SettingsError::Invalid(format!(
+                "poll_interval_sec must be from {} to {}",
+                MIN_POLL_INTERVAL_SEC, MAX_POLL_INTERVAL_SEC
+            )));
+        }
+        if self.friends_poll_interval_sec < MIN_FRIENDS_POLL_INTERVAL_SEC
+            || self.friends_poll_interval_sec > MAX_POLL_INTERVAL_SEC
+        {
+            return Err(SettingsError::Invalid(format!(
+                "friends_poll_interval_sec must be from {} to {}",
+                MIN_FRIENDS_POLL_INTERVAL_SEC, MAX_POLL_INTERVAL_SEC
+            )));
+        }
+        if self.initial_news_sec < MIN_INITIAL_NEWS_SEC
+            || self.initial_news_sec > MAX_INITIAL_NEWS_SEC
+        {
+            return Err(SettingsError::Invalid(format!(
+                "initial_news_sec must be from {} to {}",
+                MIN_INITIAL_NEWS_SEC, MAX_INITIAL_NEWS_SEC
+            )));
+        }
+        if self.news_page_size == 0 || self.news_page_size > MAX_NEWS_PAGE_SIZE {
+            return Err(SettingsError::Invalid(format!(
+                "news_page_size must be from 1 to {}",
+                MAX_NEWS_PAGE_SIZE
+            )));
+        }
+        if self.photo_sizes.is_empty() {
+            return Err(SettingsError::Invalid(
+                "photo_sizes must contain at least one size".to_string(),
+            ));
+        }
+        if let Some(size) = self
+            .photo_sizes
+            .iter()
+            .find(|s| !PHOTO_SIZE_TYPES.contains(&s.as_str()))
+        {
+            return Err(SettingsError::Invalid(format!(
+                "unknown photo size \"{}\", must be one of {}",
+                size,
+                PHOTO_SIZE_TYPES.join(", ")
+            )));
+        }
+        if self.log_filter.trim().is_empty() {
+            return Err(SettingsError::Invalid(
+                "log_filter must not be empty".to_string(),
+            ));
+        }
+        if let Some(dir) = &self.cache_dir {
+            if !Path::new(dir).is_absolute() {
+                return Err(SettingsError::Invalid(
+                    "cache_dir must be an absolute path".to_string(),
+                ));
+            }
+        }
+        if self.client_id.is_empty() || !self.client_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(SettingsError::Invalid(
+                "client_id must be a number".to_string(),
+            ));
+        }
+        if let Some(scope) = self
+            .scopes
+            .iter()
+            .find(|s| !AUTH_SCOPES.contains(&s.as_str()))
+        {
+            return Err(SettingsError::Invalid(format!(
+                "unknown scope \"{}\", must be one of {}",
+                scope,
+                AUTH_SCOPES.join(", ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Returns pathname of the config file
+pub fn config_path() -> PathBuf {
+    // see https://specifications.freedesktop.org/basedir-spec/latest/ar01s03.html
+    let mut home_dir = ".".to_string();
+    let mut config_dir: Option<String> = None;
+    for (key, value) in vars_os() {
+        if let Some(key) = key.to_str() {
+            if let Some(value) = value.to_str() {
+                match key {
+                    "HOME" => home_dir = value.to_string(),
+                    "XDG_CONFIG_HOME" => config_dir = Some(value.to_string()),
+                    &_ => {}
+                }
+            }
+        }
+    }
+    let config_dir = config_dir.unwrap_or_else(|| home_dir + "/.config");
+    Path::new(&config_dir).join("gvk").join(CONFIG_FILE_NAME)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::TestDir;
+
+    #[test]
+    fn parse_validate_save() {
+        // missing settings take defaults
+        let settings = Settings::parse("poll_interval_sec = 120").ok().unwrap();
+        assert_eq!(settings.poll_interval_sec, 120);
+        assert_eq!(settings.news_page_size, Settings::default().news_page_size);
+        // wrong values are rejected
+        assert!(Settings::parse("poll_interval_sec = 1").is_err());
+        assert!(Settings::parse("friends_poll_interval_sec = 10").is_err());
+        assert!(Settings::parse("news_page_size = 1000").is_err());
+        assert!(Settings::parse("photo_sizes = [\"big\"]").is_err());
+        assert!(Settings::parse("cache_dir = \"relative\"").is_err());
+        assert!(Settings::parse("poll_interval_sec = \"often\"").is_err());
+        assert!(Settings::parse("client_id = \"app\"").is_err());
+        assert!(Settings::parse("scopes = [\"everything\"]").is_err());
+        // saved settings are loaded back
+        let dir = TestDir::new("settings");
+        let pathname = Path::new(dir.path()).join("config").join(CONFIG_FILE_NAME);
+        let mut settings = Settings::default();
+        settings.photo_sizes = vec!["x".to_string(), "m".to_string()];
+        settings.cache_dir = Some("/tmp/gvk".to_string());
+        assert!(settings.save_to(&pathname).is_ok());
+        assert_eq!(Settings::load_from(&pathname).ok(), Some(settings));
+    }
+}
@@ -1,8 +1,10 @@
+use crate::models::NewsItemModel;
+use crate::settings::Settings;
 use crate::vk_provider::AuthResponse;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env::vars_os;
 use std::fmt;
-use std::fs::{read_to_string, write};
+use std::fs::read_to_string;
 use std::path::Path;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -10,17 +12,39 @@ use std::sync::{
 };
 use tokio::fs::File as TokioFile;
 use tokio::io::{AsyncReadExt, AsyncWriteExt}; // for read_to_end() / write_all()
+use tokio::sync::OnceCell;
 
+mod accounts;
+pub use accounts::{AccountEntry, Accounts};
+pub mod credentials;
+use credentials::SharedCredentials;
 pub mod download;
+mod news_log;
+pub use news_log::HISTORY_SEC as NEWS_HISTORY_SEC;
+mod sources;
+pub use sources::SourceStats;
+#[cfg(test)]
+mod test_dir;
+#[cfg(test)]
+pub use test_dir::TestDir;
 
 pub type SharedStorage = Arc<Storage>;
 
 const AUTH_FILE_NAME: &str = "/auth.json";
+const ACCOUNTS_FILE_NAME: &str = "/accounts.json";
+const ACCOUNTS_DIR_NAME: &str = "/accounts/";
 const CACHE_FILES_NAME: &str = "/cache_files.json";
+const NEWS_LOG_NAME: &str = "/news.jsonl";
+const MUTED_SOURCES_NAME: &str = "/muted_sources.json";
+const SOURCES_NAME: &str = "/sources.json";
 
 pub struct Storage {
     // root path
     cache_home: String,
+    // root path of the registry of accounts, shared by the account storages
+    accounts_home: String,
+    // root path of the state user has set, e.g. the muted sources, out of the cache which might be cleaned up
+    data_home: String,
     // temp files
     temp_files: String,
     // file storage
@@ -29,6 +53,17 @@ pub struct Storage {
     files: RwLock<HashMap<String, String>>,
     // flag files has changed after last saving state
     is_files_dirty: AtomicBool,
+    // if set, files are not downloaded but resolved against this directory (offline replay mode)
+    fixture_files: Option<String>,
+    // preferred size types of the photos to download, the most preferred first
+    photo_sizes: RwLock<Vec<String>>,
+    // access tokens, the store is opened on the first use and is shared by the account storages
+    credentials: Arc<OnceCell<SharedCredentials>>,
+    // source_id of the news sources user has muted, their news are not shown
+    muted: RwLock<HashSet<i64>>,
+    // the directory of the test storage is removed along with it
+    #[cfg(test)]
+    test_dir: Option<TestDir>,
 }
 
 pub enum StorageError {
@@ -40,6 +75,7 @@ pub enum StorageError {
     ReadWriteFile(String),
     DownloadFile(String),
     FileCacheDictionary,
+    MutedSources,
 }
 
 impl fmt::Display for StorageError {
@@ -57,12 +93,17 @@ impl fmt::Display for StorageError {
             StorageError::FileCacheDictionary => {
                 write!(f, "failed to access file cache dictionary")
             }
+            StorageError::MutedSources => write!(f, "failed to access muted sources"),
         }
     }
 }
 
 impl Storage {
-    pub fn new() -> Self {
+    pub fn new(settings: &Settings) -> Self {
+        if let Some(cache_dir) = &settings.cache_dir {
+            return Storage::with_cache_dir(cache_dir.clone())
+                .with_data_dir(credentials::data_dir());
+        }
         // see https://specifications.freedesktop.org/basedir-spec/latest/ar01s03.html
         let mut home_dir = ".".to_string();
         let mut cache_dir = ".cache".to_string();
@@ -78,7 +119,13 @@ impl Storage {
             }
         }
         // root cache
-        let cache_home = home_dir + "/" + &cache_dir + "/gvk";
+        Storage::with_cache_dir(home_dir + "/" + &cache_dir + "/gvk")
+            .with_data_dir(credentials::data_dir())
+    }
+
+    /// Creates storage rooted in the cache_home directory, the state user has set is kept there too
+    /// unless it is moved by with_data_dir()
+    pub fn with_cache_dir(cache_home: String) -> Self {
         // files cache
         let mut cache_files = cache_home.clone() + "/files";
         if std::fs::create_dir_all(&Path::new(cache_files.as_str())).is_err() {
@@ -108,13 +155,101 @@ impl Storage {
         if files.len() > 0 {
             log::debug!("loaded {} previously cached files", files.len());
         }
+        let muted = Storage::load_muted(&cache_home);
         Storage {
+            accounts_home: cache_home.clone(),
+            data_home: cache_home.clone(),
             cache_home,
             temp_files,
             cache_files,
             files: RwLock::new(files),
             is_files_dirty: AtomicBool::new(false),
+            fixture_files: None,
+            photo_sizes: RwLock::new(Settings::default().photo_sizes),
+            credentials: Arc::new(OnceCell::new()),
+            muted: RwLock::new(muted),
+            #[cfg(test)]
+            test_dir: None,
+        }
+    }
+
+    /// Creates storage of the test in a directory of its own which is removed along with the storage,
+    /// files are not downloaded and access tokens are kept in the directory too
+    #[cfg(test)]
+    pub fn for_test(name: &str) -> Self {
+        let dir = TestDir::new(name);
+        let credentials = Arc::new(credentials::EncryptedFileStore::new(
+            dir.join("credentials"),
+        ));
+        let mut storage = Storage::with_cache_dir(dir.path().to_string())
+            .with_fixture_files(String::new())
+            .with_credentials(credentials);
+        storage.test_dir = Some(dir);
+        storage
+    }
+
+    /// Keeps the state user has set, e.g. the muted sources, in the data_home directory
+    pub fn with_data_dir(mut self, data_home: String) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&data_home) {
+            log::error!("failed creating data directory {}: {}", &data_home, e);
         }
+        self.muted = RwLock::new(Storage::load_muted(&data_home));
+        self.data_home = data_home;
+        self
+    }
+
+    // sync inner version, called from constructors
+    fn load_muted(dir: &str) -> HashSet<i64> {
+        read_to_string(dir.to_string() + MUTED_SOURCES_NAME)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Keeps access tokens in the store instead of the default one
+    pub fn with_credentials(mut self, credentials: SharedCredentials) -> Self {
+        self.credentials = Arc::new(OnceCell::new_with(Some(credentials)));
+        self
+    }
+
+    async fn get_credentials(&self) -> &SharedCredentials {
+        self.credentials.get_or_init(credentials::open).await
+    }
+
+    /// Creates storage of the account data rooted in its own subdirectory,
+    /// so the accounts do not share news history and cached files
+    pub fn for_account(&self, user_id: &str) -> Self {
+        let mut storage =
+            Storage::with_cache_dir(self.cache_home.clone() + ACCOUNTS_DIR_NAME + user_id)
+                .with_data_dir(self.data_home.clone() + ACCOUNTS_DIR_NAME + user_id);
+        storage.accounts_home = self.accounts_home.clone();
+        storage.fixture_files = self.fixture_files.clone();
+        storage.photo_sizes = RwLock::new(self.get_photo_sizes());
+        storage.credentials = self.credentials.clone();
+        storage
+    }
+
+    /// Applies changed settings
+    pub fn apply_settings(&self, settings: &Settings) {
+        if let Ok(mut photo_sizes) = self.photo_sizes.write() {
+            *photo_sizes = settings.photo_sizes.clone();
+        }
+    }
+
+    /// Returns preferred size types of the photos, the most preferred first
+    pub fn get_photo_sizes(&self) -> Vec<String> {
+        if let Ok(photo_sizes) = self.photo_sizes.read() {
+            photo_sizes.clone()
+        } else {
+            Settings::default().photo_sizes
+        }
+    }
+
+    /// Turns offline mode on: all files are resolved against the local directory instead of downloading
+    pub fn with_fixture_files(mut self, dir: String) -> Self {
+        log::info!("files are resolved against local directory {}", &dir);
+        self.fixture_files = Some(dir);
+        self
     }
 
     pub async fn save_state_async(&self) -> Result<(), StorageError> {
@@ -192,21 +327,185 @@ impl Storage {
         self.get_cache_dir().to_string() + AUTH_FILE_NAME
     }
 
+    fn get_accounts_file_name(&self) -> String {
+        self.accounts_home.clone() + ACCOUNTS_FILE_NAME
+    }
+
     fn get_cache_files_name(&self) -> String {
         self.get_cache_dir().to_string() + CACHE_FILES_NAME
     }
 
-    pub async fn save_auth_async(&self, auth: &AuthResponse) -> Result<(), StorageError> {
-        let v = serde_json::to_string(auth).map_err(|_| StorageError::JsonSerialize)?;
+    fn get_news_log_name(&self) -> String {
+        self.get_cache_dir().to_string() + NEWS_LOG_NAME
+    }
 
-        let auth_file = self.get_auth_file_name();
-        let mut file = TokioFile::create(&auth_file)
+    fn get_muted_sources_name(&self) -> String {
+        self.data_home.clone() + MUTED_SOURCES_NAME
+    }
+
+    fn get_sources_name(&self) -> String {
+        self.data_home.clone() + SOURCES_NAME
+    }
+
+    /// Returns true if user has muted news of the source
+    pub fn is_muted(&self, source_id: i64) -> bool {
+        self.muted
+            .read()
+            .map_or(false, |muted| muted.contains(&source_id))
+    }
+
+    /// Mutes or unmutes news of the source, the muted sources are kept across restarts
+    pub async fn set_muted_async(&self, source_id: i64, muted: bool) -> Result<(), StorageError> {
+        let mut sources: Vec<i64> = match self.muted.write() {
+            Ok(mut all) => {
+                if muted {
+                    all.insert(source_id);
+                } else {
+                    all.remove(&source_id);
+                }
+                all.iter().cloned().collect()
+            }
+            Err(_) => return Err(StorageError::MutedSources),
+        };
+        sources.sort_unstable();
+        let json = serde_json::to_string(&sources).map_err(|_| StorageError::JsonSerialize)?;
+        tokio::fs::write(self.get_muted_sources_name(), json)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))
+    }
+
+    /// Returns statistics and favourite marks of the news sources, there are none if nothing is stored
+    pub async fn load_sources_async(&self) -> Result<Vec<SourceStats>, StorageError> {
+        let sources_file = self.get_sources_name();
+        if !Path::new(&sources_file).exists() {
+            return Ok(Vec::new());
+        }
+        let json = tokio::fs::read_to_string(&sources_file)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
+        serde_json::from_str(&json).map_err(|_| StorageError::JsonDeserialize)
+    }
+
+    /// Stores statistics and favourite marks of the news sources
+    pub async fn save_sources_async(&self, sources: &[SourceStats]) -> Result<(), StorageError> {
+        let json = serde_json::to_string(sources).map_err(|_| StorageError::JsonSerialize)?;
+        tokio::fs::write(self.get_sources_name(), json)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))
+    }
+
+    /// Persists news items to keep history across restarts
+    pub async fn append_news_async(&self, items: &[NewsItemModel]) -> Result<(), StorageError> {
+        news_log::append(self.get_news_log_name().as_str(), items)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))
+    }
+
+    /// Returns at most max_items of the most recent stored news, the most recent first
+    pub async fn load_news_async(
+        &self,
+        max_items: usize,
+    ) -> Result<Vec<NewsItemModel>, StorageError> {
+        let mut items = news_log::load(self.get_news_log_name().as_str(), max_items)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
+        // photos are kept in temporary files which are removed on every start,
+        // so they are downloaded again from the addresses logged along
+        for item in items.iter_mut() {
+            if let Some(photos) = item.photos.take() {
+                let mut restored = Vec::with_capacity(photos.len());
+                for mut photo in photos {
+                    if !Path::new(&photo.uri).exists() {
+                        match self.get_temp_file(&photo.source, "").await {
+                            Ok(file) => photo.uri = file,
+                            // logged without the address or failed downloading
+                            Err(_) => continue,
+                        }
+                    }
+                    restored.push(photo);
+                }
+                if !restored.is_empty() {
+                    item.photos = Some(restored);
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    /// Returns the known accounts with their access tokens, the cleartext auth.json
+    /// and the tokens accounts.json kept in cleartext are migrated to the credential store
+    pub async fn load_accounts_async(&self) -> Result<Accounts, StorageError> {
+        let accounts_file = self.get_accounts_file_name();
+        if Path::new(&accounts_file).exists() {
+            let mut file = TokioFile::open(&accounts_file)
+                .await
+                .map_err(|_| StorageError::OpenFile(accounts_file))?;
+            let mut content = vec![];
+            file.read_to_end(&mut content)
+                .await
+                .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
+            let mut accounts: Accounts = serde_json::from_str(
+                std::str::from_utf8(&content).map_err(|_| StorageError::JsonUtf8)?,
+            )
+            .map_err(|_| StorageError::JsonDeserialize)?;
+            let migrated = accounts.take_cleartext_auth();
+            accounts.list.retain(|entry| !entry.user_id.is_empty());
+            let credentials = self.get_credentials().await;
+            for entry in accounts.list.iter_mut() {
+                if !entry.auth.get_access_token().is_empty() {
+                    continue;
+                }
+                entry.auth = match credentials.load(&entry.user_id).await {
+                    Ok(Some(auth)) => auth,
+                    Ok(None) => AuthResponse::new("", &entry.user_id),
+                    Err(e) => {
+                        log::warn!("failed loading token of user {}: {}", &entry.user_id, e);
+                        AuthResponse::new("", &entry.user_id)
+                    }
+                };
+            }
+            if migrated {
+                log::info!(
+                    "migrating access tokens of {} to the credential store",
+                    &accounts_file
+                );
+                self.save_accounts_async(&accounts).await?;
+            }
+            Ok(accounts)
+        } else {
+            let mut accounts = Accounts::default();
+            if let Ok(auth) = self.load_auth_async().await {
+                log::info!("migrating stored auth of user {}", auth.get_user_id());
+                accounts.upsert(auth);
+                self.save_accounts_async(&accounts).await?;
+                // the token is not left in cleartext
+                let _ = tokio::fs::remove_file(self.get_auth_file_name()).await;
+            }
+            Ok(accounts)
+        }
+    }
+
+    /// Stores the list of accounts and puts their access tokens to the credential store
+    pub async fn save_accounts_async(&self, accounts: &Accounts) -> Result<(), StorageError> {
+        let credentials = self.get_credentials().await;
+        for entry in accounts.list.iter() {
+            if !entry.auth.get_access_token().is_empty() {
+                credentials
+                    .save(&entry.auth)
+                    .await
+                    .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
+            }
+        }
+        let v = serde_json::to_string(accounts).map_err(|_| StorageError::JsonSerialize)?;
+
+        let accounts_file = self.get_accounts_file_name();
+        let mut file = TokioFile::create(&accounts_file)
             .await
-            .map_err(|_| StorageError::CreateFile(auth_file))?;
+            .map_err(|_| StorageError::CreateFile(accounts_file))?;
 
         file.write_all(v.as_str().as_bytes())
             .await
-            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e).into()))?;
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
 
         Ok(())
     }
@@ -216,6 +515,8 @@ impl Storage {
     pub async fn get_file(&self, uri: &str, name_prefix: &str) -> Result<String, StorageError> {
         if uri.is_empty() {
             Err(StorageError::DownloadFile("name not set".into()))
+        } else if let Some(dir) = &self.fixture_files {
+            self.get_fixture_file(uri, dir)
         } else {
             {
                 if let Ok(files_read) = self.files.read() {
@@ -251,6 +552,8 @@ impl Storage {
     ) -> Result<String, StorageError> {
         if uri.is_empty() {
             Err(StorageError::DownloadFile("name not set".into()))
+        } else if let Some(dir) = &self.fixture_files {
+            self.get_fixture_file(uri, dir)
         } else if self.temp_files.is_empty() {
             // error log has already been produced upon start
             Err(StorageError::DownloadFile(
@@ -266,7 +569,52 @@ impl Storage {
         }
     }
 
-    pub async fn load_auth_async(&self) -> Result<AuthResponse, StorageError> {
+    fn get_fixture_file(&self, uri: &str, dir: &str) -> Result<String, StorageError> {
+        download::local_file(uri, dir).map_err(|e| {
+            log::debug!("fixture file error: {}", e);
+            StorageError::DownloadFile(uri.to_string())
+        })
+    }
+
+    /// Removes the account from the known ones and deletes its access token
+    pub async fn forget_account_async(&self, user_id: &str) -> Result<(), StorageError> {
+        let mut accounts = self.load_accounts_async().await?;
+        accounts.remove(user_id);
+        self.get_credentials()
+            .await
+            .delete(user_id)
+            .await
+            .map_err(|e| StorageError::ReadWriteFile(format!("{}", e)))?;
+        self.save_accounts_async(&accounts).await
+    }
+
+    /// Removes news history, statistics and marks of the sources and all the cached files
+    pub fn clear_cache(&self) {
+        clear_dir(&self.cache_files);
+        clear_dir(&self.temp_files);
+        for name in &[
+            self.get_news_log_name(),
+            self.get_cache_files_name(),
+            self.get_muted_sources_name(),
+            self.get_sources_name(),
+        ] {
+            if let Err(e) = std::fs::remove_file(name) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::error!("failed deleting file {}: {}", name, e);
+                }
+            }
+        }
+        if let Ok(mut files) = self.files.write() {
+            files.clear();
+        }
+        if let Ok(mut muted) = self.muted.write() {
+            muted.clear();
+        }
+        self.is_files_dirty.store(false, Ordering::SeqCst);
+    }
+
+    // loads cleartext auth stored by the previous versions
+    async fn load_auth_async(&self) -> Result<AuthResponse, StorageError> {
         let auth_file = self.get_auth_file_name();
         let mut file = TokioFile::open(&auth_file)
             .await
@@ -284,28 +632,6 @@ impl Storage {
 
         Ok(auth)
     }
-
-    // alternative sync version
-    #[allow(dead_code)]
-    pub fn save_auth(&self, auth: &AuthResponse) -> Result<(), StorageError> {
-        let v = serde_json::to_string(auth).map_err(|_| StorageError::JsonSerialize)?;
-        let auth_file = self.get_auth_file_name();
-        let path = Path::new(&auth_file);
-        write(&path, v.as_bytes()).map_err(|_| StorageError::CreateFile(auth_file))?;
-        Ok(())
-    }
-
-    // alternative sync version
-    #[allow(dead_code)]
-    pub fn load_auth(&self) -> Result<AuthResponse, StorageError> {
-        let auth_file = self.get_auth_file_name();
-        let path = Path::new(&auth_file);
-        let s = read_to_string(&path).map_err(|_| StorageError::OpenFile(auth_file))?;
-
-        let auth: AuthResponse =
-            serde_json::from_str(&s).map_err(|_| StorageError::JsonDeserialize)?;
-        Ok(auth)
-    }
 }
 
 fn clear_dir(dir: &str) {
@@ -325,3 +651,114 @@ fn clear_dir(dir: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Photo;
+    use credentials::CredentialStore;
+
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn restore_photos_of_logged_news() {
+        let storage = Storage::for_test("photos");
+        let dir = PathBuf::from(storage.get_cache_dir());
+        let files = dir.join("replay");
+        std::fs::create_dir_all(&files).unwrap();
+        std::fs::write(files.join("abc.jpg"), b"jpeg").unwrap();
+        let storage = storage.with_fixture_files(files.to_string_lossy().to_string());
+        let photo = |uri: &str, source: &str| Photo {
+            uri: uri.to_string(),
+            text: String::new(),
+            source: source.to_string(),
+        };
+        let item = NewsItemModel {
+            source_id: -1,
+            post_id: 1,
+            timestamp: 1,
+            author: String::new(),
+            avatar: String::new(),
+            itemtype: "post".to_string(),
+            datetime: String::new(),
+            content: String::new(),
+            photos: Some(vec![
+                // the temporary file removed on restart
+                photo(
+                    "/nonexistent/xabc.jpg",
+                    "https://sun9-1.userapi.com/c1/abc.jpg",
+                ),
+                // logged without the address
+                photo("/nonexistent/xdef.jpg", ""),
+            ]),
+            links: None,
+            likes: 0,
+            comments: 0,
+            reposts: 0,
+            views: 0,
+            liked: false,
+            content_id: 0,
+        };
+        assert!(storage.append_news_async(&[item]).await.is_ok());
+        let restored = storage.load_news_async(10).await.ok().unwrap();
+        let photos = restored[0].photos.as_ref().unwrap();
+        assert_eq!(photos.len(), 1);
+        assert_eq!(
+            photos[0].uri,
+            files.join("abc.jpg").to_string_lossy().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_user_state_in_data_dir() {
+        let storage = Storage::for_test("data");
+        let dir = PathBuf::from(storage.get_cache_dir());
+        let data = dir.join("data");
+        let storage = storage.with_data_dir(data.to_string_lossy().to_string());
+        assert!(storage.set_muted_async(-1, true).await.is_ok());
+        let mut stats = SourceStats::new(-2);
+        stats.favourite = true;
+        assert!(storage.save_sources_async(&[stats.clone()]).await.is_ok());
+        // nothing of them is in the cache
+        assert!(!dir.join("muted_sources.json").exists());
+        assert!(!dir.join("sources.json").exists());
+        let storage = Storage::with_cache_dir(dir.to_string_lossy().to_string())
+            .with_data_dir(data.to_string_lossy().to_string());
+        assert!(storage.is_muted(-1));
+        assert_eq!(
+            storage.load_sources_async().await.ok().unwrap(),
+            vec![stats]
+        );
+    }
+
+    #[tokio::test]
+    async fn migrate_cleartext_accounts() {
+        let storage = Storage::for_test("accounts");
+        let dir = PathBuf::from(storage.get_cache_dir());
+        let credentials = Arc::new(credentials::EncryptedFileStore::new(
+            dir.join("data").to_string_lossy().to_string(),
+        ));
+        let storage = storage.with_credentials(credentials.clone());
+        // accounts.json of the versions keeping the tokens in it
+        std::fs::write(
+            dir.join("accounts.json"),
+            r#"{"current":"1","list":[
+                {"auth":{"access_token":"1234","user_id":"1"},"name":"First"},
+                {"auth":{"access_token":"5678","user_id":"2"},"name":"Second"}]}"#,
+        )
+        .unwrap();
+        let accounts = storage.load_accounts_async().await.ok().unwrap();
+        assert_eq!(accounts.names().len(), 2);
+        let current = accounts.get_current().unwrap();
+        assert_eq!(current.name, "First");
+        assert_eq!(current.auth.get_access_token(), "1234");
+        // the tokens are moved to the credential store
+        let content = std::fs::read_to_string(dir.join("accounts.json")).unwrap();
+        assert!(!content.contains("1234") && !content.contains("5678"));
+        let stored = credentials.load("2").await.ok().flatten().unwrap();
+        assert_eq!(stored.get_access_token(), "5678");
+        // and are loaded from there next time
+        let accounts = storage.load_accounts_async().await.ok().unwrap();
+        assert_eq!(accounts.find("2").unwrap().auth.get_access_token(), "5678");
+    }
+}
@@ -0,0 +1,107 @@
+//! Registry of the authenticated vk.com accounts keyed by user_id.
+//! The registry file keeps no access tokens, they are kept in the credential store.
+use crate::vk_provider::AuthResponse;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountEntry {
+    #[serde(default)]
+    pub user_id: String,
+    // user name to display in account switcher
+    #[serde(default)]
+    pub name: String,
+    // access scopes user has granted in addition to the configured ones
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // loaded from the credential store
+    #[serde(skip)]
+    pub auth: AuthResponse,
+    // auth with the access token the previous versions kept in this file in cleartext
+    #[serde(default, rename = "auth", skip_serializing)]
+    cleartext_auth: Option<AuthResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Accounts {
+    // user_id of the account served last time
+    #[serde(default)]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub list: Vec<AccountEntry>,
+}
+
+impl Accounts {
+    pub fn find(&self, user_id: &str) -> Option<&AccountEntry> {
+        self.list.iter().find(|a| a.user_id == user_id)
+    }
+
+    /// Returns the account served last time if any
+    pub fn get_current(&self) -> Option<&AccountEntry> {
+        self.current.as_ref().and_then(|id| self.find(id))
+    }
+
+    /// Adds new account or replaces auth of the known one, then makes it current
+    pub fn upsert(&mut self, auth: AuthResponse) {
+        let user_id = auth.get_user_id().to_string();
+        match self.list.iter_mut().find(|a| a.user_id == user_id) {
+            Some(entry) => entry.auth = auth,
+            None => self.list.push(AccountEntry {
+                user_id: user_id.clone(),
+                name: String::new(),
+                scopes: Vec::new(),
+                auth,
+                cleartext_auth: None,
+            }),
+        }
+        self.current = Some(user_id);
+    }
+
+    /// Takes the access tokens the previous versions kept in the file in cleartext,
+    /// returns true if there were any, so the file is to be saved without them
+    pub fn take_cleartext_auth(&mut self) -> bool {
+        let mut taken = false;
+        for entry in self.list.iter_mut() {
+            if let Some(auth) = entry.cleartext_auth.take() {
+                if entry.user_id.is_empty() {
+                    entry.user_id = auth.get_user_id().to_string();
+                }
+                entry.auth = auth;
+                taken = true;
+            }
+        }
+        taken
+    }
+
+    pub fn set_name(&mut self, user_id: &str, name: &str) {
+        if let Some(entry) = self.list.iter_mut().find(|a| a.user_id == user_id) {
+            entry.name = name.to_string();
+        }
+    }
+
+    /// Remembers the scopes user has granted to request them on the next logins
+    pub fn grant(&mut self, user_id: &str, scopes: &[String]) {
+        if let Some(entry) = self.list.iter_mut().find(|a| a.user_id == user_id) {
+            for scope in scopes {
+                if !entry.scopes.contains(scope) {
+                    entry.scopes.push(scope.clone());
+                }
+            }
+        }
+    }
+
+    /// Forgets the account, there is no current one if it was current
+    pub fn remove(&mut self, user_id: &str) {
+        self.list.retain(|a| a.user_id != user_id);
+        if self.current.as_deref() == Some(user_id) {
+            self.current = None;
+        }
+    }
+
+    /// Returns (user_id, name) of every account
+    pub fn names(&self) -> Vec<(String, String)> {
+        self.list
+            .iter()
+            .map(|a| (a.user_id.clone(), a.name.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,151 @@
+//! Secure storage of the access tokens.
+//!
+//! * CredentialStore - the trait every backend implements, credentials are keyed by user_id
+//! * KeyringStore - keeps credentials in the Secret Service (GNOME Keyring, KWallet etc.) over D-Bus
+//! * EncryptedFileStore - the fallback if there is no Secret Service, keeps credentials encrypted
+//!   in `$XDG_DATA_HOME/gvk`, i.e. out of the cache which might be cleaned up
+use crate::vk_provider::AuthResponse;
+use async_trait::async_trait;
+use std::env::vars_os;
+use std::fmt;
+use std::sync::Arc;
+
+mod keyring;
+pub use keyring::KeyringStore;
+mod encrypted_file;
+pub use encrypted_file::EncryptedFileStore;
+
+pub type SharedCredentials = Arc<dyn CredentialStore>;
+
+pub enum CredentialError {
+    // backend is not available, e.g. there is no Secret Service on the bus
+    Unavailable(String),
+    // the stored credentials are broken
+    Malformed(String),
+    // reading or writing credentials failed
+    ReadWrite(String),
+}
+
+impl fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialError::Unavailable(err) => {
+                write!(f, "credential store is unavailable: {}", err)
+            }
+            CredentialError::Malformed(err) => write!(f, "malformed credentials: {}", err),
+            CredentialError::ReadWrite(err) => {
+                write!(f, "failed reading or writing credentials: {}", err)
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Returns the stored auth of the user if any
+    async fn load(&self, user_id: &str) -> Result<Option<AuthResponse>, CredentialError>;
+    /// Stores auth replacing the previous one of the same user
+    async fn save(&self, auth: &AuthResponse) -> Result<(), CredentialError>;
+    /// Forgets auth of the user
+    async fn delete(&self, user_id: &str) -> Result<(), CredentialError>;
+}
+
+/// Opens the Secret Service if it is available, otherwise the encrypted file in the data directory
+pub async fn open() -> SharedCredentials {
+    let keyring = KeyringStore::connect()
+        .await
+        .map(|store| Arc::new(store) as SharedCredentials);
+    select(keyring, data_dir())
+}
+
+// returns the connected Secret Service or the encrypted file in the directory
+fn select(keyring: Result<SharedCredentials, CredentialError>, dir: String) -> SharedCredentials {
+    match keyring {
+        Ok(store) => {
+            log::info!("credentials are kept in the Secret Service");
+            store
+        }
+        Err(e) => {
+            log::warn!("{}, credentials are kept encrypted in {}", e, &dir);
+            Arc::new(EncryptedFileStore::new(dir))
+        }
+    }
+}
+
+/// Returns the directory to keep application data in
+pub fn data_dir() -> String {
+    // see https://specifications.freedesktop.org/basedir-spec/latest/ar01s03.html
+    let mut home_dir = ".".to_string();
+    let mut data_dir: Option<String> = None;
+    for (key, value) in vars_os() {
+        if let Some(key) = key.to_str() {
+            if let Some(value) = value.to_str() {
+                match key {
+                    "HOME" => home_dir = value.to_string(),
+                    "XDG_DATA_HOME" => data_dir = Some(value.to_string()),
+                    &_ => {}
+                }
+            }
+        }
+    }
+    data_dir.unwrap_or_else(|| home_dir + "/.local/share") + "/gvk"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::TestDir;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    // stands in for the Secret Service
+    #[derive(Default)]
+    struct MemoryStore {
+        items: Mutex<HashMap<String, AuthResponse>>,
+    }
+
+    #[async_trait]
+    impl CredentialStore for MemoryStore {
+        async fn load(&self, user_id: &str) -> Result<Option<AuthResponse>, CredentialError> {
+            Ok(self.items.lock().unwrap().get(user_id).cloned())
+        }
+
+        async fn save(&self, auth: &AuthResponse) -> Result<(), CredentialError> {
+            self.items
+                .lock()
+                .unwrap()
+                .insert(auth.get_user_id().to_string(), auth.clone());
+            Ok(())
+        }
+
+        async fn delete(&self, user_id: &str) -> Result<(), CredentialError> {
+            self.items.lock().unwrap().remove(user_id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn select_keyring_if_connected() {
+        let test_dir = TestDir::new("select-keyring");
+        let dir = test_dir.join("data");
+        let keyring = Arc::new(MemoryStore::default());
+        let store = select(Ok(keyring.clone() as SharedCredentials), dir.clone());
+        assert!(store.save(&AuthResponse::new("1234", "1")).await.is_ok());
+        assert!(keyring.load("1").await.ok().flatten().is_some());
+        // nothing is written to the data directory
+        assert!(!Path::new(&dir).exists());
+    }
+
+    #[tokio::test]
+    async fn select_file_if_no_keyring() {
+        let test_dir = TestDir::new("select-file");
+        let dir = test_dir.join("data");
+        let unavailable = Err(CredentialError::Unavailable("no bus".to_string()));
+        let store = select(unavailable, dir.clone());
+        assert!(store.save(&AuthResponse::new("1234", "1")).await.is_ok());
+        let loaded = store.load("1").await.ok().flatten().unwrap();
+        assert_eq!(loaded.get_access_token(), "1234");
+        assert!(Path::new(&dir).exists());
+    }
+}
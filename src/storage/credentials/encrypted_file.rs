@@ -0,0 +1,205 @@
+use super::{CredentialError, CredentialStore};
+use crate::vk_provider::AuthResponse;
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use async_trait::async_trait;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs::{create_dir_all, read, OpenOptions};
+use tokio::io::AsyncWriteExt; // for write_all()
+use tokio::sync::Mutex;
+
+const CREDENTIALS_FILE_NAME: &str = "/credentials";
+const KEY_FILE_NAME: &str = "/credentials.key";
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+// identify the machine, see machine-id(5)
+const MACHINE_ID_FILES: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// Keeps credentials of all the users in a single file encrypted with AES-256-GCM.
+/// The key is generated on the first use and is kept in the file readable by the owner only,
+/// the ciphertext is bound to the machine id, so the files copied to another machine, e.g. with a backup,
+/// do not reveal the tokens. If the credentials fail to decrypt, their users log in again
+pub struct EncryptedFileStore {
+    dir: String,
+    // serializes read-modify-write of the file
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: String) -> Self {
+        EncryptedFileStore {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    async fn get_key(&self) -> Result<Vec<u8>, CredentialError> {
+        let key_file = self.dir.clone() + KEY_FILE_NAME;
+        if Path::new(&key_file).exists() {
+            let key = read(&key_file)
+                .await
+                .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+            if key.len() != KEY_SIZE {
+                return Err(CredentialError::Malformed(format!(
+                    "wrong size of the key in {}",
+                    key_file
+                )));
+            }
+            Ok(key)
+        } else {
+            let mut key = vec![0u8; KEY_SIZE];
+            rand::thread_rng().fill_bytes(&mut key);
+            write_private(&self.dir, &key_file, &key).await?;
+            Ok(key)
+        }
+    }
+
+    async fn load_all(&self) -> Result<HashMap<String, AuthResponse>, CredentialError> {
+        let credentials_file = self.dir.clone() + CREDENTIALS_FILE_NAME;
+        if !Path::new(&credentials_file).exists() {
+            return Ok(HashMap::new());
+        }
+        let content = read(&credentials_file)
+            .await
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        if content.len() < NONCE_SIZE {
+            return Err(CredentialError::Malformed(credentials_file));
+        }
+        let key = self.get_key().await?;
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let (nonce, encrypted) = content.split_at(NONCE_SIZE);
+        let machine_id = machine_id().await;
+        let payload = Payload {
+            msg: encrypted,
+            aad: &machine_id,
+        };
+        let decrypted = cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                CredentialError::Malformed(format!("failed decrypting {}", credentials_file))
+            })?;
+        serde_json::from_slice(&decrypted).map_err(|e| CredentialError::Malformed(format!("{}", e)))
+    }
+
+    async fn save_all(&self, all: &HashMap<String, AuthResponse>) -> Result<(), CredentialError> {
+        let json =
+            serde_json::to_vec(all).map_err(|e| CredentialError::Malformed(format!("{}", e)))?;
+        let key = self.get_key().await?;
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let mut nonce = vec![0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let machine_id = machine_id().await;
+        let payload = Payload {
+            msg: &json,
+            aad: &machine_id,
+        };
+        let encrypted = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| CredentialError::Malformed("failed encrypting credentials".to_string()))?;
+        nonce.extend(encrypted);
+        write_private(
+            &self.dir,
+            &(self.dir.clone() + CREDENTIALS_FILE_NAME),
+            &nonce,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl CredentialStore for EncryptedFileStore {
+    async fn load(&self, user_id: &str) -> Result<Option<AuthResponse>, CredentialError> {
+        let _guard = self.lock.lock().await;
+        self.load_all().await.map(|mut all| all.remove(user_id))
+    }
+
+    async fn save(&self, auth: &AuthResponse) -> Result<(), CredentialError> {
+        let _guard = self.lock.lock().await;
+        // the credentials which fail to decrypt are not recoverable, so they are replaced
+        let mut all = match self.load_all().await {
+            Err(CredentialError::Malformed(e)) => {
+                log::warn!("{}, the other users log in again", e);
+                HashMap::new()
+            }
+            loaded => loaded?,
+        };
+        all.insert(auth.get_user_id().to_string(), auth.clone());
+        self.save_all(&all).await
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), CredentialError> {
+        let _guard = self.lock.lock().await;
+        let mut all = self.load_all().await?;
+        if all.remove(user_id).is_some() {
+            self.save_all(&all).await
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// returns the machine id, empty if the system has none
+async fn machine_id() -> Vec<u8> {
+    for pathname in MACHINE_ID_FILES.iter() {
+        if let Ok(id) = read(pathname).await {
+            return String::from_utf8_lossy(&id).trim().as_bytes().to_vec();
+        }
+    }
+    Vec::new()
+}
+
+// writes file readable by the owner only
+async fn write_private(dir: &str, pathname: &str, content: &[u8]) -> Result<(), CredentialError> {
+    create_dir_all(dir)
+        .await
+        .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(pathname)
+        .await
+        .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+    file.write_all(content)
+        .await
+        .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::TestDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn encrypted_save_load_delete() {
+        let test_dir = TestDir::new("credentials");
+        let dir = test_dir.join("data");
+        let store = EncryptedFileStore::new(dir.clone());
+        assert!(store.load("1").await.ok().unwrap().is_none());
+        assert!(store.save(&AuthResponse::new("1234", "1")).await.is_ok());
+        assert!(store.save(&AuthResponse::new("5678", "2")).await.is_ok());
+        let loaded = store.load("1").await.ok().unwrap().unwrap();
+        assert_eq!(loaded.get_access_token(), "1234");
+        // the token is not kept in cleartext
+        let content = std::fs::read(dir.clone() + CREDENTIALS_FILE_NAME).unwrap();
+        assert!(!String::from_utf8_lossy(&content).contains("1234"));
+        // only the owner reads the key
+        let metadata = std::fs::metadata(dir.clone() + KEY_FILE_NAME).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert!(store.delete("1").await.is_ok());
+        assert!(store.load("1").await.ok().unwrap().is_none());
+        assert!(store.load("2").await.ok().unwrap().is_some());
+        // the credentials encrypted with another key are not readable, the user logs in again
+        std::fs::write(dir.clone() + KEY_FILE_NAME, [0u8; KEY_SIZE]).unwrap();
+        assert!(matches!(
+            store.load("2").await,
+            Err(CredentialError::Malformed(_))
+        ));
+        assert!(store.save(&AuthResponse::new("9012", "3")).await.is_ok());
+        assert!(store.load("3").await.ok().unwrap().is_some());
+    }
+}
@@ -0,0 +1,207 @@
+use super::{CredentialError, CredentialStore};
+use crate::vk_provider::AuthResponse;
+use async_trait::async_trait;
+use secret_service::{EncryptionType, SecretService};
+use std::collections::HashMap;
+
+// attributes every item of the application is marked with
+const ATTR_APPLICATION: &str = "application";
+const ATTR_USER_ID: &str = "user_id";
+const APPLICATION: &str = "gvk";
+
+/// Keeps credentials in the default collection of the Secret Service,
+/// <https://specifications.freedesktop.org/secret-service/latest/>
+pub struct KeyringStore {
+    service: SecretService<'static>,
+}
+
+impl KeyringStore {
+    /// Connects to the Secret Service on the session bus
+    pub async fn connect() -> Result<Self, CredentialError> {
+        let service = SecretService::connect(EncryptionType::Dh)
+            .await
+            .map_err(|e| CredentialError::Unavailable(format!("{}", e)))?;
+        // test the default collection is there
+        service
+            .get_default_collection()
+            .await
+            .map_err(|e| CredentialError::Unavailable(format!("{}", e)))?;
+        Ok(KeyringStore { service })
+    }
+
+    fn attributes(user_id: &str) -> HashMap<&str, &str> {
+        let mut attributes = HashMap::new();
+        attributes.insert(ATTR_APPLICATION, APPLICATION);
+        attributes.insert(ATTR_USER_ID, user_id);
+        attributes
+    }
+}
+
+#[async_trait]
+impl CredentialStore for KeyringStore {
+    async fn load(&self, user_id: &str) -> Result<Option<AuthResponse>, CredentialError> {
+        let found = self
+            .service
+            .search_items(KeyringStore::attributes(user_id))
+            .await
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        let item = match found.unlocked.into_iter().next() {
+            Some(item) => item,
+            None => match found.locked.into_iter().next() {
+                Some(item) => {
+                    // user is asked to unlock the keyring
+                    item.unlock()
+                        .await
+                        .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+                    item
+                }
+                None => return Ok(None),
+            },
+        };
+        let secret = item
+            .get_secret()
+            .await
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        serde_json::from_slice::<AuthResponse>(&secret)
+            .map(Some)
+            .map_err(|e| CredentialError::Malformed(format!("{}", e)))
+    }
+
+    async fn save(&self, auth: &AuthResponse) -> Result<(), CredentialError> {
+        let secret =
+            serde_json::to_vec(auth).map_err(|e| CredentialError::Malformed(format!("{}", e)))?;
+        let collection = self
+            .service
+            .get_default_collection()
+            .await
+            .map_err(|e| CredentialError::Unavailable(format!("{}", e)))?;
+        if collection
+            .is_locked()
+            .await
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?
+        {
+            collection
+                .unlock()
+                .await
+                .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        }
+        let label = format!("gvk access token of vk.com user {}", auth.get_user_id());
+        collection
+            .create_item(
+                label.as_str(),
+                KeyringStore::attributes(auth.get_user_id()),
+                &secret,
+                true, // replace
+                "application/json",
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), CredentialError> {
+        let found = self
+            .service
+            .search_items(KeyringStore::attributes(user_id))
+            .await
+            .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        for item in found.unlocked.iter().chain(found.locked.iter()) {
+            item.delete()
+                .await
+                .map_err(|e| CredentialError::ReadWrite(format!("{}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::TestDir;
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+
+    // the Secret Service of gnome-keyring on the private session bus, the keyring is kept in its own directory
+    struct SecretServiceStandIn {
+        bus: Child,
+        keyring: Child,
+        dir: TestDir,
+    }
+
+    impl SecretServiceStandIn {
+        // returns None if dbus-daemon or gnome-keyring-daemon is not installed
+        fn start() -> Option<Self> {
+            let dir = TestDir::new("keyring");
+            let mut bus = Command::new("dbus-daemon")
+                .args(&["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(bus.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            // the only test of the process talking to the session bus
+            std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+            let keyring = Command::new("gnome-keyring-daemon")
+                .args(&["--foreground", "--unlock", "--components=secrets"])
+                .env("XDG_DATA_HOME", dir.path())
+                .env("XDG_RUNTIME_DIR", dir.path())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn();
+            let mut keyring = match keyring {
+                Ok(keyring) => keyring,
+                Err(_) => {
+                    let _ = bus.kill();
+                    return None;
+                }
+            };
+            // the password of the login keyring it creates
+            let mut password = keyring.stdin.take().unwrap();
+            password.write_all(b"test").unwrap();
+            drop(password);
+            Some(SecretServiceStandIn { bus, keyring, dir })
+        }
+    }
+
+    impl Drop for SecretServiceStandIn {
+        fn drop(&mut self) {
+            let _ = self.keyring.kill();
+            let _ = self.bus.kill();
+            let _ = self.keyring.wait();
+            let _ = self.bus.wait();
+        }
+    }
+
+    #[tokio::test]
+    async fn keyring_save_load_delete() {
+        let _stand_in = match SecretServiceStandIn::start() {
+            Some(stand_in) => stand_in,
+            None => {
+                eprintln!("dbus-daemon and gnome-keyring-daemon are required to test the keyring");
+                return;
+            }
+        };
+        // the daemon takes a while to own its name on the bus
+        let mut store = None;
+        for _ in 0..50 {
+            if let Ok(connected) = KeyringStore::connect().await {
+                store = Some(connected);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let store = store.unwrap();
+        assert!(store.load("gvk-test-user").await.ok().unwrap().is_none());
+        let auth = AuthResponse::new("1234", "gvk-test-user");
+        assert!(store.save(&auth).await.is_ok());
+        // the item is replaced, not duplicated
+        assert!(store.save(&auth).await.is_ok());
+        let loaded = store.load("gvk-test-user").await.ok().unwrap().unwrap();
+        assert_eq!(loaded.get_access_token(), "1234");
+        assert!(store.delete("gvk-test-user").await.is_ok());
+        assert!(store.load("gvk-test-user").await.ok().unwrap().is_none());
+    }
+}
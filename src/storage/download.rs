@@ -2,6 +2,7 @@ use std::fmt;
 use std::fs::File;
 use std::io::copy;
 use std::io::Cursor;
+use std::path::Path;
 
 pub enum DownloadError {
     // Uri incorrect or empty
@@ -14,6 +15,8 @@ pub enum DownloadError {
     CreateFile(String),
     // saving downloaded content to file failed
     SaveFile(String),
+    // there is no local file for URI
+    NotFound(String),
 }
 
 impl fmt::Display for DownloadError {
@@ -24,10 +27,28 @@ impl fmt::Display for DownloadError {
             DownloadError::Content => write!(f, "reading content from response failed"),
             DownloadError::CreateFile(name) => write!(f, "failed creating file {}", name),
             DownloadError::SaveFile(name) => write!(f, "failed writing file {}", name),
+            DownloadError::NotFound(name) => write!(f, "there is no local file {}", name),
         }
     }
 }
 
+/// Resolves URI against local directory instead of downloading it,
+/// file is looked up by the last segment of the URI path the same way as downloaded files are named
+pub fn local_file(uri: &str, local_dir: &str) -> Result<String, DownloadError> {
+    let url = reqwest::Url::parse(uri).map_err(|_| DownloadError::Malformed)?;
+    let name = url
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .and_then(|name| if name.is_empty() { None } else { Some(name) })
+        .ok_or(DownloadError::Malformed)?;
+    let pathname = local_dir.to_string() + format!("/{}", name).as_str();
+    if Path::new(&pathname).is_file() {
+        Ok(pathname)
+    } else {
+        Err(DownloadError::NotFound(pathname))
+    }
+}
+
 pub async fn file(uri: &str, local_dir: &str, name_prefix: &str) -> Result<String, DownloadError> {
     if uri.is_empty() {
         return Err(DownloadError::Malformed);
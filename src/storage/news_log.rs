@@ -0,0 +1,194 @@
+//! Append-only JSON-lines log of the received news, one NewsItemModel per line.
+//! Keeps the news history across restarts of the application.
+use crate::models::NewsItemModel;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use tokio::fs::{read_to_string, write, OpenOptions};
+use tokio::io::AsyncWriteExt; // for write_all()
+
+/// The history kept before the most recent news, 30 days, the older news are dropped from the log
+pub const HISTORY_SEC: i64 = 30 * 86_400;
+
+pub enum NewsLogError {
+    // serializing item failed
+    Serialize,
+    // opening or reading log failed
+    Read(String),
+    // writing log failed
+    Write(String),
+}
+
+impl fmt::Display for NewsLogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NewsLogError::Serialize => write!(f, "failed serializing news item"),
+            NewsLogError::Read(err) => write!(f, "failed reading news log: {}", err),
+            NewsLogError::Write(err) => write!(f, "failed writing news log: {}", err),
+        }
+    }
+}
+
+pub async fn append(pathname: &str, items: &[NewsItemModel]) -> Result<(), NewsLogError> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for item in items {
+        let line = serde_json::to_string(item).map_err(|_| NewsLogError::Serialize)?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(pathname)
+        .await
+        .map_err(|e| NewsLogError::Write(format!("{}", e)))?;
+    file.write_all(lines.as_bytes())
+        .await
+        .map_err(|e| NewsLogError::Write(format!("{}", e)))
+}
+
+/// Returns at most max_items of the most recent news, the most recent first.
+/// The item re-fetched is logged once more, so its last logged copy is returned.
+/// If the log has grown too much with such copies or has news older than HISTORY_SEC before
+/// the most recent ones, it is compacted to the last copies of the news within HISTORY_SEC.
+pub async fn load(pathname: &str, max_items: usize) -> Result<Vec<NewsItemModel>, NewsLogError> {
+    if !Path::new(pathname).exists() {
+        return Ok(Vec::new());
+    }
+    let content = read_to_string(pathname)
+        .await
+        .map_err(|e| NewsLogError::Read(format!("{}", e)))?;
+    let mut cnt_lines: usize = 0;
+    let mut items: Vec<NewsItemModel> = Vec::new();
+    let mut positions = HashMap::new();
+    for line in content.lines() {
+        cnt_lines += 1;
+        match serde_json::from_str::<NewsItemModel>(line) {
+            Ok(item) => match positions.get(&item.id()) {
+                Some(&pos) => items[pos] = item,
+                None => {
+                    positions.insert(item.id(), items.len());
+                    items.push(item);
+                }
+            },
+            Err(e) => log::warn!("skip malformed news log line {}: {}", cnt_lines, e),
+        }
+    }
+    items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    let horizon = items
+        .first()
+        .map_or(0, |newest| newest.timestamp - HISTORY_SEC);
+    let kept = items
+        .iter()
+        .take_while(|item| item.timestamp >= horizon)
+        .count();
+    let expired = kept < items.len();
+    items.truncate(kept);
+    if expired || cnt_lines > 2 * items.len() {
+        compact(pathname, &items).await?;
+    }
+    items.truncate(max_items);
+    Ok(items)
+}
+
+async fn compact(pathname: &str, items: &[NewsItemModel]) -> Result<(), NewsLogError> {
+    let mut lines = String::new();
+    // keep chronological order of the log
+    for item in items.iter().rev() {
+        let line = serde_json::to_string(item).map_err(|_| NewsLogError::Serialize)?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+    write(pathname, lines.as_bytes())
+        .await
+        .map_err(|e| NewsLogError::Write(format!("{}", e)))?;
+    log::debug!("news log is compacted to {} items", items.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::TestDir;
+
+    fn item(timestamp: i64) -> NewsItemModel {
+        NewsItemModel {
+            source_id: -1,
+            post_id: timestamp,
+            timestamp,
+            author: "author".to_string(),
+            avatar: String::new(),
+            itemtype: "post".to_string(),
+            datetime: String::new(),
+            content: "content".to_string(),
+            photos: None,
+            links: None,
+            likes: 0,
+            comments: 0,
+            reposts: 0,
+            views: 0,
+            liked: false,
+            content_id: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn append_load_compact() {
+        let dir = TestDir::new("news-log");
+        let pathname = dir.join("news.jsonl");
+        assert!(load(&pathname, 10).await.ok().unwrap().is_empty());
+
+        let first: Vec<_> = (1..=3).rev().map(item).collect();
+        let second: Vec<_> = (4..=6).rev().map(item).collect();
+        assert!(append(&pathname, &first).await.is_ok());
+        assert!(append(&pathname, &second).await.is_ok());
+
+        let loaded = load(&pathname, 10).await.ok().unwrap();
+        let stamps: Vec<_> = loaded.iter().map(|i| i.timestamp).collect();
+        assert_eq!(stamps, vec![6, 5, 4, 3, 2, 1]);
+
+        // the re-fetched item replaces its previous copy
+        let mut refetched = item(3);
+        refetched.likes = 5;
+        assert!(append(&pathname, &[refetched]).await.is_ok());
+        let loaded = load(&pathname, 10).await.ok().unwrap();
+        assert_eq!(loaded.len(), 6);
+        assert_eq!(loaded[3].likes, 5);
+
+        // only the most recent items are returned, the rest are kept
+        let loaded = load(&pathname, 2).await.ok().unwrap();
+        let stamps: Vec<_> = loaded.iter().map(|i| i.timestamp).collect();
+        assert_eq!(stamps, vec![6, 5]);
+        assert_eq!(load(&pathname, 10).await.ok().unwrap().len(), 6);
+
+        // the log of re-fetched copies is compacted to the last ones
+        for likes in 1..=6 {
+            let mut refetched = item(1);
+            refetched.likes = likes;
+            assert!(append(&pathname, &[refetched]).await.is_ok());
+        }
+        let loaded = load(&pathname, 10).await.ok().unwrap();
+        assert_eq!(loaded.len(), 6);
+        assert_eq!(loaded[5].likes, 6);
+        let content = std::fs::read_to_string(&pathname).unwrap();
+        assert_eq!(content.lines().count(), 6);
+    }
+
+    #[tokio::test]
+    async fn drop_expired_history() {
+        let dir = TestDir::new("news-history");
+        let pathname = dir.join("news.jsonl");
+        let newest = 2 * HISTORY_SEC;
+        let logged = vec![item(newest), item(newest - HISTORY_SEC), item(1)];
+        assert!(append(&pathname, &logged).await.is_ok());
+        let loaded = load(&pathname, 10).await.ok().unwrap();
+        let stamps: Vec<_> = loaded.iter().map(|i| i.timestamp).collect();
+        assert_eq!(stamps, vec![newest, newest - HISTORY_SEC]);
+        // the expired news are dropped from the log too
+        let content = std::fs::read_to_string(&pathname).unwrap();
+        assert_eq!(content.lines().count(), 2);
+    }
+}
@@ -0,0 +1,32 @@
+//! Statistics and favourite marks of the news sources kept across restarts,
+//! the muted sources are kept apart as every news item is checked against them
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct SourceStats {
+    // source_id of the news, negative for groups
+    pub id: i64,
+    // a number of posts of the source received
+    #[serde(default)]
+    pub posts: u64,
+    // unix time of the most recent post, 0 if there are none
+    #[serde(default)]
+    pub last_post: i64,
+    #[serde(default)]
+    pub favourite: bool,
+}
+
+impl SourceStats {
+    pub fn new(id: i64) -> Self {
+        SourceStats {
+            id,
+            ..SourceStats::default()
+        }
+    }
+
+    /// Counts the post published at timestamp
+    pub fn count_post(&mut self, timestamp: i64) {
+        self.posts += 1;
+        self.last_post = self.last_post.max(timestamp);
+    }
+}
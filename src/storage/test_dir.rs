@@ -0,0 +1,44 @@
+//! Temporary directories of the tests
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// tells apart the directories of the tests running at once
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Empty directory of the test, it is removed with all its content on drop
+pub struct TestDir {
+    path: String,
+}
+
+impl TestDir {
+    /// Creates the directory, the name tells the test it belongs to
+    pub fn new(name: &str) -> Self {
+        let count = COUNT.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir()
+            .join(format!(
+                "gvk-test-{}-{}-{}",
+                name,
+                std::process::id(),
+                count
+            ))
+            .to_string_lossy()
+            .to_string();
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TestDir { path }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns pathname of the file or subdirectory in the directory
+    pub fn join(&self, name: &str) -> String {
+        format!("{}/{}", self.path, name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,31 +1,75 @@
-use crate::models::UserModel;
-use crate::vk_provider::{AccessTokenProvider, AuthResponse, NewsUpdate, SourcesUpdate};
+use crate::models::{ChatEvent, CommentModel, NewsIndex, NewsItemId, PostDraft, UserModel};
+use crate::settings::{config_path, Settings};
+use crate::vk_provider::{
+    AccessTokenProvider, AuthResponse, CommentsUpdate, CommunitiesUpdate, ConversationsUpdate,
+    FriendsUpdate, MessagesUpdate, NewsUpdate, SourcesUpdate,
+};
 use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::{
     AdjustmentExt, ApplicationWindow, Builder, ContainerExt, Image, Label, ListBoxExt,
     ScrolledWindow, Stack, WidgetExt,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use tokio::sync::{
     mpsc::{Receiver, Sender},
     oneshot,
 };
-use webkit2gtk::{LoadEvent, WebContext, WebView, WebViewExt};
+#[cfg(feature = "webkit")]
+use webkit2gtk::{CookieManagerExt, LoadEvent, WebContext, WebContextExt, WebView, WebViewExt};
 
+use crate::view_models::CommentVM;
+use crate::view_models::CommunityVM;
+use crate::view_models::ConversationVM;
+use crate::view_models::FriendVM;
+use crate::view_models::MessageVM;
 use crate::view_models::NewsItemVM;
 use crate::view_models::NewsSourceVM;
 
 type AuthResponseSender = oneshot::Sender<AuthResponse>;
 
+// id of the account switcher item to add one more account
+const ADD_ACCOUNT_ID: &str = "add_account";
+
+mod browser_auth_view;
+mod comment_composer;
+use comment_composer::CommentComposer;
+mod comment_list_box_row;
+mod communities_page;
+use communities_page::CommunitiesPage;
+mod community_list_box_row;
+mod conversation_list_box_row;
+mod friend_list_box_row;
+mod friends_page;
+use friends_page::FriendsPage;
+mod message_list_box_row;
 mod news_list_box_row;
+mod post_composer;
+use post_composer::PostComposer;
+mod post_page;
+use post_page::PostPage;
+mod preferences_dialog;
+mod source_filter;
+use source_filter::SourceFilter;
 mod sources_list_box_row;
+mod sources_pane;
+use sources_pane::SourcesPane;
 
 /// Communicating from VK provider to UI
 pub enum Message {
-    /// Request to display authentication page and get AuthResponse, argument is a send part of a oneshot channel
-    /// to send back the response with access_token etc.
-    Auth(AuthResponseSender),
+    /// Request to display authentication page and get AuthResponse, arguments are the provider of authorization URI
+    /// with the scopes to request and a send part of a oneshot channel to send back the response with access_token etc.
+    Auth(AccessTokenProvider, AuthResponseSender),
+    /// The same as Auth but to authenticate one more account, not the one already logged in
+    AddAccount(AccessTokenProvider, AuthResponseSender),
+    /// User has logged out, everything displayed for the account is to be forgotten
+    LoggedOut,
+    /// The known accounts as (user_id, name), the current one is being served
+    Accounts {
+        current: String,
+        list: Vec<(String, String)>,
+    },
     /// Updated own user info received
     OwnInfo(UserModel),
     /// New incoming message to display in the user's wall
@@ -34,6 +78,56 @@ pub enum Message {
     OlderNews(NewsUpdate),
     /// Updating news sources, friends and groups
     NewsSources(SourcesUpdate),
+    /// Conversations to list in the chats page, the most recent first
+    Conversations(ConversationsUpdate),
+    /// Messages of the conversation with peer_id, the most recent first,
+    /// older ones precede the displayed messages, the others follow them
+    ChatMessages {
+        peer_id: i64,
+        update: MessagesUpdate,
+        older: bool,
+    },
+    /// Real-time events of the chats: new messages, read conversations, unread counter, online statuses
+    ChatEvents(Vec<ChatEvent>),
+    /// Friends with their online status and friend lists
+    Friends(FriendsUpdate),
+    /// Communities user is subscribed to
+    Communities(CommunitiesUpdate),
+    /// User has joined (member) or left the community, or the failed request is to be taken back
+    Membership { group_id: i64, member: bool },
+    /// News of the source are muted or not, muted ones are no longer displayed
+    SourceMuted { source_id: i64, muted: bool },
+    /// Older posts of the source wall, the most recent first, the empty update ends the source history
+    SourceNews { source_id: i64, update: NewsUpdate },
+    /// User likes the post or not with the count of its likes,
+    /// the count is not known if the failed request is to be taken back
+    Liked {
+        item: NewsItemId,
+        liked: bool,
+        likes: Option<i64>,
+    },
+    /// Comments of the post, the first page of the opened post replaces the displayed comments,
+    /// replies to the top-level comment with id thread follow the displayed part of the thread,
+    /// other top-level comments follow the displayed ones
+    Comments {
+        post: NewsItemId,
+        thread: i64,
+        opened: bool,
+        update: CommentsUpdate,
+    },
+    /// The comment user has sent to the post, replying to the comment with id reply_to if it is not zero,
+    /// there is no comment if sending has failed
+    CommentSent {
+        post: NewsItemId,
+        reply_to: i64,
+        comment: Option<CommentModel>,
+    },
+    /// Communities the user administers, the post composer publishes to their walls
+    PostTargets(CommunitiesUpdate),
+    /// The post has been published or scheduled, there is no id if publishing has failed
+    Published(Option<i64>),
+    /// The request of user has failed, the error is to be shown
+    Error(String),
 }
 
 pub enum Request {
@@ -43,6 +137,78 @@ pub enum Request {
     NewsOlder,
     // request a portion of news after the most recent
     NewsNext,
+    // application window has been deactivated (true) or activated (false)
+    Background(bool),
+    // user has changed settings
+    Settings(Settings),
+    // switch to the known account with user_id
+    SwitchAccount(String),
+    // authenticate and switch to a new account
+    AddAccount,
+    // end the session and forget the current account
+    Logout,
+    // request of the chats page
+    Chat(ChatRequest),
+    // friends page has been opened
+    Friends,
+    // request of the communities page
+    Communities(CommunityRequest),
+    // hide news of the source (negative id for communities) or show them again
+    MuteSource { source_id: i64, muted: bool },
+    // mark the source as favourite to keep it on top of the sources pane or unmark it
+    FavouriteSource { source_id: i64, favourite: bool },
+    // like the post or take the like back
+    Like { item: NewsItemId, liked: bool },
+    // a portion of posts of the source wall prior the received ones, or the most recent ones if restart
+    SourceHistory { source_id: i64, restart: bool },
+    // request of the post page
+    Comments(CommentRequest),
+    // request of the post composer
+    Publish(PublishRequest),
+}
+
+pub enum ChatRequest {
+    // the most recent conversations
+    Conversations,
+    // the most recent messages of the conversation with peer_id
+    History(i64),
+    // a portion of messages prior the oldest received in the conversation with peer_id
+    Older(i64),
+    // send text to the conversation
+    Send { peer_id: i64, text: String },
+}
+
+pub enum CommentRequest {
+    // the first comments of the post
+    Open(NewsItemId),
+    // a portion of top-level comments following the received ones of the opened post
+    More,
+    // a portion of replies following the received ones of the top-level comment with id
+    Thread(i64),
+    // comment the opened post or reply to the comment with id reply_to from the thread of top-level one,
+    // both are zero for the top-level comment, photos are the files to attach
+    Send {
+        text: String,
+        reply_to: i64,
+        thread: i64,
+        photos: Vec<String>,
+    },
+}
+
+pub enum PublishRequest {
+    // communities the user can publish to besides the own wall
+    Targets,
+    // publish the post or schedule it
+    Post(PostDraft),
+}
+
+pub enum CommunityRequest {
+    // every community user is subscribed to
+    List,
+    // subscribe to the community with group_id
+    Join(i64),
+    // unsubscribe from the community with group_id
+    Leave(i64),
 }
 
 type MessageReceiver = Receiver<Message>;
@@ -58,62 +224,193 @@ pub fn build(application: &gtk::Application, rx_msg: MessageReceiver, tx_req: Re
         .expect("Couldn't get main_window");
     window.set_application(Some(application));
 
+    // actions
+    let refresh = gio::SimpleAction::new("refresh", None);
+    refresh.connect_activate(clone!(@strong tx_req => move |_, _| {
+        log::debug!("refreshing news");
+        send_request(&tx_req, Request::NewsNext);
+    }));
+    window.add_action(&refresh);
+    application.set_accels_for_action("win.refresh", &["<Primary>r", "F5"]);
+    let post_composer = PostComposer::build(&window, &tx_req);
+    let new_post = gio::SimpleAction::new("new_post", None);
+    new_post.connect_activate(clone!(@weak post_composer => move |_, _| {
+        post_composer.show();
+    }));
+    window.add_action(&new_post);
+    application.set_accels_for_action("win.new_post", &["<Primary>n"]);
+    let preferences = gio::SimpleAction::new("preferences", None);
+    preferences.connect_activate(clone!(@weak window => move |_, _| {
+        preferences_dialog::show(&window);
+    }));
+    window.add_action(&preferences);
+    application.set_accels_for_action("win.preferences", &["<Primary>comma"]);
+    let logout = gio::SimpleAction::new("logout", None);
+    logout.connect_activate(clone!(@strong tx_req => move |_, _| {
+        log::debug!("logging out");
+        send_request(&tx_req, Request::Logout);
+    }));
+    window.add_action(&logout);
+
+    // apply settings changed either in preferences dialog or by editing config file
+    let settings_monitor = watch_settings(&tx_req);
+    window.connect_destroy(move |_| {
+        if let Some(monitor) = &settings_monitor {
+            monitor.cancel();
+        }
+    });
+
+    // poll news rarer while user does not look at them
+    window.connect_property_is_active_notify(clone!(@strong tx_req => move |window| {
+        send_request(&tx_req, Request::Background(!window.is_active()));
+    }));
+
+    // errors of the user requests are shown until user closes them
+    let error_bar: gtk::InfoBar = builder
+        .get_object("error_bar")
+        .expect("Couldn't get error_bar widget");
+    error_bar.connect_response(|error_bar, _| error_bar.hide());
+
     // list news
     let news_item_model = gio::ListStore::new(NewsItemVM::static_type());
     let list_news: gtk::ListBox = builder
         .get_object("news_list")
         .expect("Couldn't get news_list widget");
-    list_news.bind_model(
-        Some(&news_item_model),
+    bind_news(&list_news, &news_item_model, &tx_req);
+
+    // sources list, selecting a source shows its news only
+    let source_filter = SourceFilter::build(&builder, &news_item_model, &tx_req);
+    let sources_pane = SourcesPane::build(&builder, &source_filter, &tx_req);
+
+    // chats
+    let conversation_model = gio::ListStore::new(ConversationVM::static_type());
+    let conversation_list: gtk::ListBox = builder
+        .get_object("conversation_list")
+        .expect("Couldn't get conversation_list widget");
+    conversation_list.bind_model(
+        Some(&conversation_model),
         clone!(@weak window => @default-panic, move |item| {
-            let item = item.downcast_ref::<NewsItemVM>().expect("News item view model is of wrong type");
-            let box_ = news_list_box_row::build(item);
+            let item = item.downcast_ref::<ConversationVM>().expect("Conversation view model is of wrong type");
+            let box_ = conversation_list_box_row::build(item);
             box_.upcast::<gtk::Widget>()
         }),
     );
-
-    // sources list
-    let sources_item_model = gio::ListStore::new(NewsSourceVM::static_type());
-    let list_sources: gtk::ListBox = builder
-        .get_object("news_sources")
-        .expect("Couldn't get news_sources widget");
-    list_sources.bind_model(
-        Some(&sources_item_model),
+    let message_model = gio::ListStore::new(MessageVM::static_type());
+    let message_list: gtk::ListBox = builder
+        .get_object("message_list")
+        .expect("Couldn't get message_list widget");
+    message_list.bind_model(
+        Some(&message_model),
         clone!(@weak window => @default-panic, move |item| {
-            let item = item.downcast_ref::<NewsSourceVM>().expect("News source view model is of wrong type");
-            let box_ = sources_list_box_row::build(item);
+            let item = item.downcast_ref::<MessageVM>().expect("Message view model is of wrong type");
+            let box_ = message_list_box_row::build(item);
             box_.upcast::<gtk::Widget>()
         }),
     );
+    // peer_id of the conversation displayed, 0 if there is none
+    let current_peer = Rc::new(Cell::new(0i64));
+    let message_entry: gtk::Entry = builder
+        .get_object("message_entry")
+        .expect("Couldn't get message_entry");
+    let message_send: gtk::Button = builder
+        .get_object("message_send")
+        .expect("Couldn't get message_send");
+    conversation_list.connect_row_activated(
+        clone!(@strong tx_req, @strong current_peer, @weak conversation_model, @weak message_model,
+            @weak message_entry, @weak message_send => move |_, row| {
+            if let Some(item) = conversation_model.get_object(row.get_index() as u32) {
+                if let Ok(conversation) = item.downcast::<ConversationVM>() {
+                    let peer_id = conversation.get_peer_id();
+                    log::debug!("opening conversation {}", peer_id);
+                    current_peer.set(peer_id);
+                    message_model.remove_all();
+                    message_entry.set_sensitive(true);
+                    message_send.set_sensitive(true);
+                    send_request(&tx_req, Request::Chat(ChatRequest::History(peer_id)));
+                }
+            }
+        }),
+    );
+    message_entry.connect_activate(
+        clone!(@strong tx_req, @strong current_peer => move |entry| {
+            send_chat_message(&tx_req, current_peer.get(), entry);
+        }),
+    );
+    message_send.connect_clicked(
+        clone!(@strong tx_req, @strong current_peer, @weak message_entry => move |_| {
+            send_chat_message(&tx_req, current_peer.get(), &message_entry);
+        }),
+    );
+
+    // friends
+    let friends_page = FriendsPage::build(&builder);
+
+    // communities
+    let communities_page = CommunitiesPage::build(&builder, &tx_req);
+
+    // post with comments opened from news
+    let post_page = PostPage::build(&builder, &tx_req);
+
+    // menu
+    let menu_news: gtk::LinkButton = builder
+        .get_object("menu_news")
+        .expect("Couldn't get menu_news");
+    menu_news.connect_activate_link(clone!(@strong builder => move |_| {
+        show_right_pane(&builder, "page_view_home");
+        gtk::Inhibit(true)
+    }));
+    let menu_chats: gtk::LinkButton = builder
+        .get_object("menu_chats")
+        .expect("Couldn't get menu_chats");
+    menu_chats.connect_activate_link(clone!(@strong builder, @strong tx_req => move |_| {
+        show_right_pane(&builder, "page_view_chats");
+        send_request(&tx_req, Request::Chat(ChatRequest::Conversations));
+        gtk::Inhibit(true)
+    }));
+    let menu_friends: gtk::LinkButton = builder
+        .get_object("menu_friends")
+        .expect("Couldn't get menu_friends");
+    menu_friends.connect_activate_link(clone!(@strong builder, @strong tx_req => move |_| {
+        show_right_pane(&builder, "page_view_friends");
+        send_request(&tx_req, Request::Friends);
+        gtk::Inhibit(true)
+    }));
+    let menu_communities: gtk::LinkButton = builder
+        .get_object("menu_communities")
+        .expect("Couldn't get menu_communities");
+    menu_communities.connect_activate_link(clone!(@strong builder, @strong tx_req => move |_| {
+        show_right_pane(&builder, "page_view_communities");
+        send_request(&tx_req, Request::Communities(CommunityRequest::List));
+        gtk::Inhibit(true)
+    }));
 
     // signals
     let tx_req_copy = tx_req.clone();
+    let current_peer_copy = current_peer.clone();
+    let source_filter_copy = source_filter.clone();
     builder.connect_signals(move |_, handler_name| {
         // This is the one-time callback to register signals.
         // Here we map each handler name to its handler.
         if handler_name == "news_edge_reached" {
             // Return the news scroll handler
             let tx_req_copy2 = tx_req_copy.clone();
+            let source_filter_copy2 = source_filter_copy.clone();
             Box::new(move |values| {
                 for val in values {
                     if let Some(pos) = val.downcast_ref::<gtk::PositionType>() {
                         if let Some(pos) = pos.get() {
                             match pos {
+                                // the selected source is scrolled back thru its wall
+                                gtk::PositionType::Top if source_filter_copy2.source_id() != 0 => {
+                                    source_filter_copy2.request_older();
+                                }
                                 gtk::PositionType::Top => {
                                     log::debug!("reached top, requesting older news");
-                                    let main_context = glib::MainContext::default();
-                                    let tx_req_copy3 = tx_req_copy2.clone();
-                                    main_context.spawn_local(async move {
-                                        let _ = tx_req_copy3.send(Request::NewsOlder).await;
-                                    });
+                                    send_request(&tx_req_copy2, Request::NewsOlder);
                                 }
                                 gtk::PositionType::Bottom => {
                                     log::debug!("reached bottom, requesting more recent news");
-                                    let main_context = glib::MainContext::default();
-                                    let tx_req_copy3 = tx_req_copy2.clone();
-                                    main_context.spawn_local(async move {
-                                        let _ = tx_req_copy3.send(Request::NewsNext).await;
-                                    });
+                                    send_request(&tx_req_copy2, Request::NewsNext);
                                 }
                                 gtk::PositionType::Right | gtk::PositionType::Left => (),
                                 _ => log::warn!("reached unreachable {:?}", pos),
@@ -123,16 +420,32 @@ pub fn build(application: &gtk::Application, rx_msg: MessageReceiver, tx_req: Re
                 }
                 None
             })
+        } else if handler_name == "messages_edge_reached" {
+            // Return the messages scroll handler
+            let tx_req_copy2 = tx_req_copy.clone();
+            let current_peer_copy2 = current_peer_copy.clone();
+            Box::new(move |values| {
+                for val in values {
+                    if let Some(pos) = val.downcast_ref::<gtk::PositionType>() {
+                        if pos.get() == Some(gtk::PositionType::Top)
+                            && current_peer_copy2.get() != 0
+                        {
+                            log::debug!("reached top, requesting older messages");
+                            send_request(
+                                &tx_req_copy2,
+                                Request::Chat(ChatRequest::Older(current_peer_copy2.get())),
+                            );
+                        }
+                    }
+                }
+                None
+            })
         } else if handler_name == "delete_main_window" {
             // Return the news exit handler
             let tx_req_copy2 = tx_req_copy.clone();
             Box::new(move |_| {
                 log::debug!("sending stop request to vk_provider");
-                let main_context = glib::MainContext::default();
-                let tx_req_copy3 = tx_req_copy2.clone();
-                main_context.spawn_local(async move {
-                    let _ = tx_req_copy3.send(Request::Stop).await;
-                });
+                send_request(&tx_req_copy2, Request::Stop);
                 Some(glib::Value::from(&false))
             })
         } else {
@@ -140,41 +453,208 @@ pub fn build(application: &gtk::Application, rx_msg: MessageReceiver, tx_req: Re
         }
     });
 
+    // account switcher
+    let current_account = Rc::new(RefCell::new(String::new()));
+    let account_switcher: gtk::ComboBoxText = builder
+        .get_object("account_switcher")
+        .expect("Couldn't get account_switcher");
+    account_switcher.connect_changed(
+        clone!(@strong tx_req, @strong current_account => move |switcher| {
+            if let Some(id) = switcher.get_active_id() {
+                if id.as_str() == ADD_ACCOUNT_ID {
+                    log::debug!("requesting one more account");
+                    send_request(&tx_req, Request::AddAccount);
+                } else if id.as_str() != current_account.borrow().as_str() {
+                    log::debug!("requesting switch to account {}", id);
+                    send_request(&tx_req, Request::SwitchAccount(id.to_string()));
+                }
+            }
+        }),
+    );
+
     // select visible right pane
     show_right_pane(&builder, "page_view_home");
 
     launch_msg_handler(
         BoundedModels {
             news: news_item_model,
-            sources: sources_item_model,
+            sources: sources_pane,
+            conversations: conversation_model,
+            messages: message_model,
+            friends: friends_page,
+            communities: communities_page,
+            post: post_page,
+            post_composer,
+            source_filter,
         },
         builder,
+        current_account,
+        current_peer,
         rx_msg,
     );
 
     window.show_all();
 }
 
+/// Watches config file and sends every valid change of settings to vk_provider
+fn watch_settings(tx_req: &RequestSender) -> Option<gio::FileMonitor> {
+    let file = gio::File::new_for_path(config_path());
+    match file.monitor_file(gio::FileMonitorFlags::NONE, gio::NONE_CANCELLABLE) {
+        Ok(monitor) => {
+            monitor.connect_changed(clone!(@strong tx_req => move |_, file, _, event| {
+                if event == gio::FileMonitorEvent::ChangesDoneHint {
+                    if let Some(path) = file.get_path() {
+                        match Settings::load_from(&path) {
+                            Ok(settings) => send_request(&tx_req, Request::Settings(settings)),
+                            Err(e) => log::error!("{}, changes are ignored", e),
+                        }
+                    }
+                }
+            }));
+            Some(monitor)
+        }
+        Err(e) => {
+            log::warn!(
+                "failed watching settings, changes are applied on restart: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Sends request to vk_provider from the main event loop
+fn send_request(tx_req: &RequestSender, request: Request) {
+    let main_context = glib::MainContext::default();
+    let tx_req = tx_req.clone();
+    main_context.spawn_local(async move {
+        let _ = tx_req.send(request).await;
+    });
+}
+
+/// Lets user choose photos to attach in the dialog above the window of the widget,
+/// returns the files chosen
+fn choose_photos(widget: &impl IsA<gtk::Widget>) -> Vec<String> {
+    let parent = widget
+        .get_toplevel()
+        .and_then(|widget| widget.downcast::<gtk::Window>().ok());
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some("Attach photos"),
+        parent.as_ref(),
+        gtk::FileChooserAction::Open,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Attach", gtk::ResponseType::Accept),
+        ],
+    );
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("Images"));
+    filter.add_pixbuf_formats();
+    dialog.add_filter(&filter);
+    dialog.set_select_multiple(true);
+    let chosen = if dialog.run() == gtk::ResponseType::Accept {
+        dialog
+            .get_filenames()
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    dialog.close();
+    chosen
+}
+
+/// Sends the entered text to the conversation displayed
+fn send_chat_message(tx_req: &RequestSender, peer_id: i64, entry: &gtk::Entry) {
+    let text = entry.get_text().to_string();
+    if peer_id != 0 && !text.trim().is_empty() {
+        send_request(tx_req, Request::Chat(ChatRequest::Send { peer_id, text }));
+        entry.set_text("");
+    }
+}
+
 /// Spawns message handler as a task on the main event loop
 struct BoundedModels {
     news: gio::ListStore,
-    sources: gio::ListStore,
+    sources: Rc<SourcesPane>,
+    conversations: gio::ListStore,
+    messages: gio::ListStore,
+    friends: Rc<FriendsPage>,
+    communities: Rc<CommunitiesPage>,
+    post: Rc<PostPage>,
+    post_composer: Rc<PostComposer>,
+    source_filter: Rc<SourceFilter>,
 }
 
-fn launch_msg_handler(models: BoundedModels, ui_builder: Builder, mut rx: MessageReceiver) {
+impl BoundedModels {
+    // forgets chats displayed for the account
+    fn clear_chats(&self, ui_builder: &Builder, current_peer: &Cell<i64>) {
+        self.conversations.remove_all();
+        self.messages.remove_all();
+        current_peer.set(0);
+        show_unread(ui_builder, 0);
+        for name in &["message_entry", "message_send"] {
+            let widget: gtk::Widget = ui_builder
+                .get_object(name)
+                .expect("Couldn't get message composer");
+            widget.set_sensitive(false);
+        }
+    }
+
+    // forgets everything displayed for the account, e.g. on logging out or switching accounts
+    fn reset_account_views(
+        &self,
+        ui_builder: &Builder,
+        current_peer: &Cell<i64>,
+        news_index: &mut NewsIndex<NewsItemVM>,
+    ) {
+        self.news.remove_all();
+        self.sources.clear();
+        self.clear_chats(ui_builder, current_peer);
+        self.friends.clear();
+        self.communities.clear();
+        self.post.clear();
+        self.source_filter.clear();
+        news_index.clear();
+    }
+}
+
+fn launch_msg_handler(
+    models: BoundedModels,
+    ui_builder: Builder,
+    current_account: Rc<RefCell<String>>,
+    current_peer: Rc<Cell<i64>>,
+    mut rx: MessageReceiver,
+) {
     let main_context = glib::MainContext::default();
     let future = async move {
         let mut cnt_news = 0;
+        // every news item displayed, to update ones received more than once instead of duplicating
+        let mut news_index: NewsIndex<NewsItemVM> = NewsIndex::new();
         while let Some(item) = rx.recv().await {
             match item {
-                Message::Auth(tx_response) => {
-                    let webview = build_auth_view(&ui_builder, tx_response);
-                    let web_auth: ScrolledWindow = ui_builder
-                        .get_object("web_auth")
-                        .expect("Couldn't get view_auth");
-                    web_auth.add(&webview);
-                    web_auth.show_all();
-                    show_right_pane(&ui_builder, "page_view_auth");
+                Message::Auth(access, tx_response) => {
+                    show_auth_view(&ui_builder, &access, false, tx_response);
+                }
+                Message::AddAccount(access, tx_response) => {
+                    show_auth_view(&ui_builder, &access, true, tx_response);
+                }
+                Message::LoggedOut => {
+                    models.reset_account_views(&ui_builder, &current_peer, &mut news_index);
+                    cnt_news = 0;
+                    current_account.borrow_mut().clear();
+                    show_logged_out(&ui_builder);
+                }
+                Message::Accounts { current, list } => {
+                    if *current_account.borrow() != current {
+                        // another account is being served, so forget everything displayed
+                        models.reset_account_views(&ui_builder, &current_peer, &mut news_index);
+                        cnt_news = 0;
+                    }
+                    // switcher reacts on changes ignoring the current account, so set it first
+                    *current_account.borrow_mut() = current.clone();
+                    show_accounts(&ui_builder, &current, &list);
                 }
                 Message::OwnInfo(vm) => {
                     show_user_info(&ui_builder, &vm);
@@ -183,8 +663,13 @@ fn launch_msg_handler(models: BoundedModels, ui_builder: Builder, mut rx: Messag
                     if !update.is_empty() {
                         let scroll_to_end = cnt_news == 0;
                         for view_model in update.into_iter().rev() {
-                            models.news.append(&NewsItemVM::new(&view_model));
-                            cnt_news += 1;
+                            if let Some(vm) =
+                                news_index.add(&view_model, NewsItemVM::new, NewsItemVM::update)
+                            {
+                                models.news.append(&vm);
+                                models.source_filter.add_newer(&vm);
+                                cnt_news += 1;
+                            }
                         }
                         if scroll_to_end && cnt_news > 0 {
                             let news_list: gtk::ListBox = ui_builder
@@ -213,8 +698,13 @@ fn launch_msg_handler(models: BoundedModels, ui_builder: Builder, mut rx: Messag
                     // natural news order is from most recent to oldest,
                     // so insert every next prior previous i.e. always at 0 position:
                     for view_model in update.into_iter() {
-                        models.news.insert(0, &NewsItemVM::new(&view_model));
-                        cnt_news += 1;
+                        if let Some(vm) =
+                            news_index.add(&view_model, NewsItemVM::new, NewsItemVM::update)
+                        {
+                            models.news.insert(0, &vm);
+                            models.source_filter.add_older(&vm);
+                            cnt_news += 1;
+                        }
                     }
                     if let Some(news_adjustment) = news_list.get_adjustment() {
                         let new_height = news_list.get_preferred_height().1;
@@ -224,10 +714,140 @@ fn launch_msg_handler(models: BoundedModels, ui_builder: Builder, mut rx: Messag
                         log::debug!("scroll news to {} after inserting older news", pos);
                     }
                 }
-                Message::NewsSources(update) => {
-                    // update sources pane from incoming data
+                Message::NewsSources(update) => models.sources.update(update),
+                Message::Conversations(update) => {
+                    models.conversations.remove_all();
                     for view_model in update.into_iter() {
-                        models.sources.append(&NewsSourceVM::new(&view_model));
+                        models
+                            .conversations
+                            .append(&ConversationVM::new(&view_model));
+                    }
+                }
+                Message::ChatMessages {
+                    peer_id,
+                    update,
+                    older,
+                } => {
+                    // user might have opened another conversation meanwhile
+                    if peer_id != current_peer.get() || update.is_empty() {
+                        continue;
+                    }
+                    let message_list: gtk::ListBox = ui_builder
+                        .get_object("message_list")
+                        .expect("Couldn't get message_list");
+                    let stored_height = message_list.get_preferred_height().1;
+                    if older {
+                        // the same as older news: insert every next prior previous
+                        for view_model in update.into_iter() {
+                            models.messages.insert(0, &MessageVM::new(&view_model));
+                        }
+                    } else {
+                        for view_model in update.into_iter().rev() {
+                            // the sent message might have been reported by long poll already
+                            if find_message(&models.messages, view_model.id).is_none() {
+                                models.messages.append(&MessageVM::new(&view_model));
+                            }
+                        }
+                    }
+                    if let Some(adjustment) = message_list.get_adjustment() {
+                        let new_height = message_list.get_preferred_height().1 as f64;
+                        let pos = if older {
+                            // keep the previous start message in place
+                            new_height - stored_height as f64
+                        } else {
+                            // scroll down to the most recent message
+                            adjustment.set_upper(new_height);
+                            new_height - adjustment.get_page_size()
+                        };
+                        adjustment.set_value(pos);
+                    }
+                }
+                Message::ChatEvents(events) => {
+                    for event in events {
+                        match event {
+                            ChatEvent::NewMessage(message) => {
+                                let is_open = message.peer_id == current_peer.get();
+                                if is_open && find_message(&models.messages, message.id).is_none() {
+                                    models.messages.append(&MessageVM::new(&message));
+                                }
+                                // the conversation goes to the top,
+                                // an unknown one appears on the next request of conversations
+                                if let Some((pos, vm)) =
+                                    find_conversation(&models.conversations, message.peer_id)
+                                {
+                                    vm.update_last(&message, !message.out && !is_open);
+                                    models.conversations.remove(pos);
+                                    models.conversations.insert(0, &vm);
+                                }
+                            }
+                            ChatEvent::Read(peer_id) => {
+                                if let Some((_, vm)) =
+                                    find_conversation(&models.conversations, peer_id)
+                                {
+                                    vm.set_unread(0);
+                                }
+                            }
+                            ChatEvent::Unread(count) => show_unread(&ui_builder, count),
+                            ChatEvent::Online { user_id, online } => {
+                                if let Some((_, vm)) =
+                                    find_conversation(&models.conversations, user_id)
+                                {
+                                    vm.set_online(online);
+                                }
+                                models.friends.set_online(user_id, online);
+                            }
+                        }
+                    }
+                }
+                Message::Friends(update) => models.friends.update(update),
+                Message::Communities(update) => models.communities.update(update),
+                Message::Membership { group_id, member } => {
+                    models.communities.set_member(group_id, member)
+                }
+                Message::SourceNews { source_id, update } => {
+                    models.source_filter.add_history(source_id, update)
+                }
+                Message::Liked { item, liked, likes } => {
+                    // the post might be displayed in the source history only
+                    let displayed = news_index
+                        .get(&item)
+                        .cloned()
+                        .or_else(|| models.source_filter.find(&item));
+                    if let Some(vm) = displayed {
+                        vm.set_liked(liked, likes);
+                    }
+                }
+                Message::Comments {
+                    post,
+                    thread,
+                    opened,
+                    update,
+                } => {
+                    if opened {
+                        // the post might be displayed in the source history only
+                        let displayed = news_index
+                            .get(&post)
+                            .cloned()
+                            .or_else(|| models.source_filter.find(&post));
+                        models.post.open(post, displayed.as_ref(), update);
+                        show_right_pane(&ui_builder, "page_view_post");
+                    } else {
+                        models.post.add(&post, thread, update);
+                    }
+                }
+                Message::CommentSent {
+                    post,
+                    reply_to,
+                    comment,
+                } => models.post.sent(&post, reply_to, comment),
+                Message::PostTargets(update) => models.post_composer.set_targets(Some(update)),
+                Message::Published(post_id) => models.post_composer.published(post_id),
+                Message::Error(text) => show_error(&ui_builder, &text),
+                Message::SourceMuted { source_id, muted } => {
+                    models.communities.set_muted(source_id, muted);
+                    models.sources.set_muted(source_id, muted);
+                    if muted {
+                        cnt_news -= remove_news_of(&models.news, &mut news_index, source_id);
                     }
                 }
             };
@@ -236,6 +856,74 @@ fn launch_msg_handler(models: BoundedModels, ui_builder: Builder, mut rx: Messag
     main_context.spawn_local(future);
 }
 
+/// Binds the news list to the model of news items to display
+fn bind_news(news_list: &gtk::ListBox, model: &gio::ListStore, tx_req: &RequestSender) {
+    news_list.bind_model(
+        Some(model),
+        clone!(@strong tx_req => move |item| {
+            let item = item
+                .downcast_ref::<NewsItemVM>()
+                .expect("News item view model is of wrong type");
+            let box_ = news_list_box_row::build(item, &tx_req);
+            box_.upcast::<gtk::Widget>()
+        }),
+    );
+}
+
+/// Returns position and view model of the conversation with peer_id if it is listed
+fn find_conversation(store: &gio::ListStore, peer_id: i64) -> Option<(u32, ConversationVM)> {
+    (0..store.get_n_items()).find_map(|pos| {
+        store
+            .get_object(pos)
+            .and_then(|item| item.downcast::<ConversationVM>().ok())
+            .filter(|vm| vm.get_peer_id() == peer_id)
+            .map(|vm| (pos, vm))
+    })
+}
+
+/// Returns position of the displayed message with id if any, the most recent messages are looked thru first
+fn find_message(store: &gio::ListStore, id: i64) -> Option<u32> {
+    (0..store.get_n_items()).rev().find(|pos| {
+        store
+            .get_object(*pos)
+            .and_then(|item| item.downcast::<MessageVM>().ok())
+            .map_or(false, |vm| vm.get_id() == id)
+    })
+}
+
+/// Shows a number of conversations having unread messages in the menu
+fn show_unread(ui_builder: &Builder, count: i64) {
+    let menu_chats: gtk::LinkButton = ui_builder
+        .get_object("menu_chats")
+        .expect("Couldn't get menu_chats");
+    if count > 0 {
+        menu_chats.set_label(&format!("chats ({})", count));
+    } else {
+        menu_chats.set_label("chats");
+    }
+}
+
+/// Removes the displayed news of the source, returns a number of the removed items
+fn remove_news_of(
+    store: &gio::ListStore,
+    index: &mut NewsIndex<NewsItemVM>,
+    source_id: i64,
+) -> i32 {
+    let removed = index.remove_source(source_id);
+    let mut count = 0;
+    for pos in (0..store.get_n_items()).rev() {
+        let is_removed = store
+            .get_object(pos)
+            .and_then(|item| item.downcast::<NewsItemVM>().ok())
+            .map_or(false, |vm| removed.contains(&vm));
+        if is_removed {
+            store.remove(pos);
+            count += 1;
+        }
+    }
+    count
+}
+
 fn test_property<'t, T, E>(prop: &'t Result<glib::Value, E>, value: T) -> bool
 where
     T: glib::value::FromValueOptional<'t> + PartialEq,
@@ -250,34 +938,71 @@ where
     false
 }
 
-fn build_auth_view(ui_builder: &Builder, tx_response: AuthResponseSender) -> WebView {
+/// Shows authentication page, add_account is set to authenticate one more account
+fn show_auth_view(
+    ui_builder: &Builder,
+    access: &AccessTokenProvider,
+    add_account: bool,
+    tx_response: AuthResponseSender,
+) {
+    // the response is sent by the way user logs in first
+    let tx_holder = Rc::new(RefCell::new(Some(tx_response)));
+    let view = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    let browser_view =
+        browser_auth_view::build(ui_builder, &view, access, add_account, tx_holder.clone());
+    view.pack_start(&browser_view, false, false, 0);
+    #[cfg(feature = "webkit")]
+    {
+        let auth_uri = if add_account {
+            access.get_add_account_uri()
+        } else {
+            access.get_auth_uri()
+        };
+        let webview = build_auth_view(ui_builder, &view, auth_uri.as_str(), tx_holder);
+        view.pack_start(&webview, true, true, 0);
+    }
+    let auth_comment: Label = ui_builder
+        .get_object("auth_comment")
+        .expect("Couldn't get auth_comment");
+    if access.get_required().is_empty() {
+        auth_comment.set_label(
+            "Authentication is required. Please login to your account and grant desired access",
+        );
+    } else {
+        auth_comment.set_label(&format!(
+            "The feature requires access to {}. Please grant it",
+            access.get_required().join(", ")
+        ));
+    }
+    let web_auth: ScrolledWindow = ui_builder
+        .get_object("web_auth")
+        .expect("Couldn't get view_auth");
+    web_auth.add(&view);
+    web_auth.show_all();
+    show_right_pane(ui_builder, "page_view_auth");
+}
+
+#[cfg(feature = "webkit")]
+fn build_auth_view(
+    ui_builder: &Builder,
+    auth_view: &gtk::Box,
+    auth_uri: &str,
+    tx_holder: Rc<RefCell<Option<AuthResponseSender>>>,
+) -> WebView {
     // create WebKit2GTK view
     let context = WebContext::get_default().unwrap();
     //context.set_web_extensions_directory("webkit2gtk-tmp/");
     let webview = WebView::with_context(&context);
-    webview.load_uri(AccessTokenProvider::get_auth_uri().as_str());
-    let tx_holder = RefCell::new(Some(tx_response));
+    webview.load_uri(auth_uri);
     webview.connect_load_changed(
-        clone!(@strong webview, @strong ui_builder => move |view, evt| {
+        clone!(@strong ui_builder, @weak auth_view => move |view, evt| {
             log::debug!("{} {}", evt, view.get_uri().unwrap());
             if evt == LoadEvent::Finished {
                 if let Some(uri) = view.get_uri() {
                     if AccessTokenProvider::is_auth_succeeded_uri(uri.as_str()) {
                         // parse auth response
                         if let Ok(auth) = uri.as_str().parse::<AuthResponse>() {
-                            log::debug!("authentication is successful: {}", auth);
-                            let tx_response = tx_holder.borrow_mut().take().unwrap();
-                            if let Err(e) = tx_response.send(auth) {
-                                log::error!("failed sending auth_response: {}", e);
-                            }
-                            // remove child to prevemt from using it more than once!
-                            let parent: ScrolledWindow = ui_builder
-                                .get_object("web_auth")
-                                .expect("Couldn't get view_auth");
-                            parent.remove(&webview);
-                            // view news page
-                            //todo: view previous page
-                            show_right_pane(&ui_builder, "page_view_home");
+                            complete_auth(&ui_builder, &auth_view, &tx_holder, auth);
                         }
                     }
                 }
@@ -287,6 +1012,33 @@ fn build_auth_view(ui_builder: &Builder, tx_response: AuthResponseSender) -> Web
     webview
 }
 
+/// Sends the response of the successful authentication and removes the authentication view
+/// to prevent from using it more than once, that also stops waiting for the browser
+fn complete_auth(
+    ui_builder: &Builder,
+    auth_view: &gtk::Box,
+    tx_holder: &RefCell<Option<AuthResponseSender>>,
+    auth: AuthResponse,
+) {
+    log::debug!("authentication is successful: {}", auth);
+    if let Some(tx_response) = tx_holder.borrow_mut().take() {
+        if let Err(e) = tx_response.send(auth) {
+            log::error!("failed sending auth_response: {}", e);
+        }
+    }
+    let parent: ScrolledWindow = ui_builder
+        .get_object("web_auth")
+        .expect("Couldn't get view_auth");
+    // the view is wrapped into viewport by the scrolled window
+    if let Some(child) = parent.get_child() {
+        parent.remove(&child);
+    }
+    auth_view.destroy();
+    // view news page
+    //todo: view previous page
+    show_right_pane(ui_builder, "page_view_home");
+}
+
 fn show_right_pane(ui_builder: &Builder, name: &str) {
     let right_pane: Stack = ui_builder
         .get_object("right_pane")
@@ -294,6 +1046,57 @@ fn show_right_pane(ui_builder: &Builder, name: &str) {
     right_pane.set_visible_child_name(name);
 }
 
+fn show_accounts(ui_builder: &Builder, current: &str, list: &[(String, String)]) {
+    let account_switcher: gtk::ComboBoxText = ui_builder
+        .get_object("account_switcher")
+        .expect("Couldn't get account_switcher widget");
+    account_switcher.remove_all();
+    for (user_id, name) in list {
+        let name = if name.is_empty() { user_id } else { name };
+        account_switcher.append(Some(user_id.as_str()), name.as_str());
+    }
+    account_switcher.append(Some(ADD_ACCOUNT_ID), "add account...");
+    account_switcher.set_active_id(Some(current));
+}
+
+/// Forgets the logged out account: the authentication page does not log in with its cookies anymore
+fn show_logged_out(ui_builder: &Builder) {
+    #[cfg(feature = "webkit")]
+    if let Some(cookies) =
+        WebContext::get_default().and_then(|context| context.get_cookie_manager())
+    {
+        cookies.delete_all_cookies();
+    }
+    let account_switcher: gtk::ComboBoxText = ui_builder
+        .get_object("account_switcher")
+        .expect("Couldn't get account_switcher widget");
+    account_switcher.remove_all();
+    let user_image: Image = ui_builder
+        .get_object("user_image")
+        .expect("Couldn't get user_image widget");
+    user_image.set_from_file("resources/images/vk44.png");
+    let user_name: Label = ui_builder
+        .get_object("user_name")
+        .expect("Couldn't get user_name widget");
+    user_name.set_label("User is not");
+    let user_status: Label = ui_builder
+        .get_object("user_status")
+        .expect("Couldn't get user_status widget");
+    user_status.set_label("authenticated");
+}
+
+/// Shows the error of the failed user request until user closes it
+fn show_error(ui_builder: &Builder, text: &str) {
+    let error_text: Label = ui_builder
+        .get_object("error_text")
+        .expect("Couldn't get error_text widget");
+    error_text.set_label(text);
+    let error_bar: gtk::InfoBar = ui_builder
+        .get_object("error_bar")
+        .expect("Couldn't get error_bar widget");
+    error_bar.show();
+}
+
 fn show_user_info(ui_builder: &Builder, view_model: &UserModel) {
     if !view_model.image.is_empty() {
         let user_image: Image = ui_builder
@@ -0,0 +1,94 @@
+use super::*;
+use crate::vk_provider::LoopbackError;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+
+/// Builds the part of authentication view auth_view to log in with the system browser, it is there
+/// with or without WebKit. The browser is redirected either to the loopback listener capturing
+/// the access token, or to the blank page of vk.com which address user pastes to the entry
+pub fn build(
+    ui_builder: &Builder,
+    auth_view: &gtk::Box,
+    access: &AccessTokenProvider,
+    add_account: bool,
+    tx_holder: Rc<RefCell<Option<AuthResponseSender>>>,
+) -> gtk::Box {
+    let view = gtk::Box::new(gtk::Orientation::Vertical, 10);
+    view.set_border_width(10);
+    // stops waiting for the browser when the view has gone
+    let cancel = Arc::new(AtomicBool::new(false));
+    let error = Label::new(None);
+    error.set_line_wrap(true);
+
+    match access.listen_loopback() {
+        Ok(listener) => {
+            let auth_uri = if add_account {
+                listener.get_add_account_uri()
+            } else {
+                listener.get_auth_uri()
+            };
+            let open_browser = gtk::Button::with_label("log in with the browser");
+            open_browser.connect_clicked(move |_| launch_browser(&auth_uri));
+            view.pack_start(&open_browser, false, false, 0);
+            // the listener blocks, so it waits in its own thread and passes the result to the main loop
+            let (tx_result, rx_result) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            let cancel_copy = cancel.clone();
+            thread::spawn(move || {
+                let _ = tx_result.send(listener.wait(&cancel_copy));
+            });
+            rx_result.attach(
+                None,
+                clone!(@strong tx_holder, @strong ui_builder, @weak auth_view, @weak error => @default-return glib::Continue(false), move |result| {
+                    match result {
+                        Ok(auth) => complete_auth(&ui_builder, &auth_view, &tx_holder, auth),
+                        Err(LoopbackError::Cancelled) => {}
+                        Err(e) => {
+                            log::warn!("{}", e);
+                            error.set_label(&format!("{}", e));
+                        }
+                    }
+                    glib::Continue(false)
+                }),
+            );
+        }
+        Err(e) => log::warn!(
+            "failed listening for browser redirect, the address is to be pasted: {}",
+            e
+        ),
+    }
+
+    // vk.com redirects to its blank page if the loopback is not allowed for the application
+    let paste_uri = if add_account {
+        access.get_add_account_uri()
+    } else {
+        access.get_auth_uri()
+    };
+    let open_paste = gtk::Button::with_label("log in with the browser and paste the address");
+    open_paste.connect_clicked(move |_| launch_browser(&paste_uri));
+    view.pack_start(&open_paste, false, false, 0);
+    let pasted = gtk::Entry::new();
+    pasted.set_placeholder_text(Some("https://oauth.vk.com/blank.html#access_token=..."));
+    view.pack_start(&pasted, false, false, 0);
+    let log_in = gtk::Button::with_label("log in");
+    log_in.connect_clicked(
+        clone!(@strong tx_holder, @strong ui_builder, @weak auth_view, @weak pasted, @weak error => move |_| {
+            match AccessTokenProvider::parse_pasted_uri(pasted.get_text().as_str()) {
+                Ok(auth) => complete_auth(&ui_builder, &auth_view, &tx_holder, auth),
+                Err(e) => error.set_label(&format!("{}", e)),
+            }
+        }),
+    );
+    view.pack_start(&log_in, false, false, 0);
+    view.pack_start(&error, false, false, 0);
+    view.connect_destroy(move |_| cancel.store(true, Ordering::SeqCst));
+    view
+}
+
+fn launch_browser(uri: &str) {
+    if let Err(e) = gio::AppInfo::launch_default_for_uri(uri, None::<&gio::AppLaunchContext>) {
+        log::error!("failed opening browser: {}", e);
+    }
+}
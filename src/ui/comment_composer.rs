@@ -0,0 +1,152 @@
+use super::*;
+
+/// Composer at the bottom of the post page: comments the opened post or replies to the comment
+/// starting the text with the mention of its author, photos chosen from disk are sent along,
+/// the composer is locked until the provider answers
+pub struct CommentComposer {
+    entry: gtk::Entry,
+    send: gtk::Button,
+    attach: gtk::Button,
+    photos_label: Label,
+    reply_bar: gtk::Box,
+    reply_label: Label,
+    // (the comment replied to, its top-level comment), zeros to comment the post
+    reply_to: Cell<(i64, i64)>,
+    // files of the photos to attach
+    photos: RefCell<Vec<String>>,
+    tx_req: RequestSender,
+}
+
+impl CommentComposer {
+    pub fn build(ui_builder: &Builder, tx_req: &RequestSender) -> Rc<Self> {
+        let composer = Rc::new(CommentComposer {
+            entry: ui_builder
+                .get_object("comment_entry")
+                .expect("Couldn't get comment_entry widget"),
+            send: ui_builder
+                .get_object("comment_send")
+                .expect("Couldn't get comment_send widget"),
+            attach: ui_builder
+                .get_object("comment_attach")
+                .expect("Couldn't get comment_attach widget"),
+            photos_label: ui_builder
+                .get_object("comment_photos")
+                .expect("Couldn't get comment_photos widget"),
+            reply_bar: ui_builder
+                .get_object("reply_bar")
+                .expect("Couldn't get reply_bar widget"),
+            reply_label: ui_builder
+                .get_object("reply_label")
+                .expect("Couldn't get reply_label widget"),
+            reply_to: Cell::new((0, 0)),
+            photos: RefCell::new(Vec::new()),
+            tx_req: tx_req.clone(),
+        });
+        composer
+            .entry
+            .connect_activate(clone!(@weak composer => move |_| composer.submit()));
+        composer
+            .send
+            .connect_clicked(clone!(@weak composer => move |_| composer.submit()));
+        composer
+            .attach
+            .connect_clicked(clone!(@weak composer => move |_| composer.choose_photos()));
+        let cancel: gtk::Button = ui_builder
+            .get_object("reply_cancel")
+            .expect("Couldn't get reply_cancel widget");
+        cancel.connect_clicked(clone!(@weak composer => move |_| composer.cancel_reply()));
+        composer
+    }
+
+    /// Starts the reply to the comment with the mention of its author
+    pub fn reply(&self, item: &CommentVM) {
+        let thread = match item.get_thread_id() {
+            0 => item.get_id(),
+            thread => thread,
+        };
+        self.reply_to.set((item.get_id(), thread));
+        let author = item.get_author();
+        self.reply_label.set_label(&format!("Reply to {}", author));
+        self.reply_bar.set_visible(true);
+        self.entry.set_text(&mention(item.get_from_id(), &author));
+        self.entry.grab_focus();
+        self.entry.set_position(-1);
+    }
+
+    /// The provider has answered, the composer is cleared if the comment has been sent,
+    /// otherwise it is kept for user to try again
+    pub fn sent(&self, ok: bool) {
+        if ok {
+            self.reset();
+        } else {
+            self.set_locked(false);
+        }
+    }
+
+    /// Forgets the comment being composed
+    pub fn reset(&self) {
+        self.set_locked(false);
+        self.entry.set_text("");
+        self.photos.borrow_mut().clear();
+        self.show_photos();
+        self.cancel_reply();
+    }
+
+    fn submit(&self) {
+        let text = self.entry.get_text().trim().to_string();
+        let photos = self.photos.borrow().clone();
+        if text.is_empty() && photos.is_empty() {
+            return;
+        }
+        let (reply_to, thread) = self.reply_to.get();
+        self.set_locked(true);
+        send_request(
+            &self.tx_req,
+            Request::Comments(CommentRequest::Send {
+                text,
+                reply_to,
+                thread,
+                photos,
+            }),
+        );
+    }
+
+    fn choose_photos(&self) {
+        let chosen = choose_photos(&self.attach);
+        if !chosen.is_empty() {
+            self.photos.borrow_mut().extend(chosen);
+            self.show_photos();
+        }
+    }
+
+    fn show_photos(&self) {
+        let count = self.photos.borrow().len();
+        self.photos_label.set_label(&format!("{} photo(s)", count));
+        self.photos_label.set_visible(count > 0);
+    }
+
+    fn cancel_reply(&self) {
+        self.reply_to.set((0, 0));
+        self.reply_bar.set_visible(false);
+    }
+
+    // the comment being sent is not editable
+    fn set_locked(&self, locked: bool) {
+        self.entry.set_sensitive(!locked);
+        self.send.set_sensitive(!locked);
+        self.attach.set_sensitive(!locked);
+    }
+}
+
+// vk.com mention of the user by the first name or of the community by its name,
+// e.g. [id1234|Ivan] or [club40316705|Новости RT на русском]
+fn mention(from_id: i64, author: &str) -> String {
+    let name = author.split_whitespace().next().unwrap_or(author);
+    if from_id > 0 {
+        format!("[id{}|{}], ", from_id, name)
+    } else if from_id < 0 {
+        format!("[club{}|{}], ", -from_id, author)
+    } else {
+        String::new()
+    }
+}
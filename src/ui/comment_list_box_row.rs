@@ -0,0 +1,85 @@
+use super::*;
+
+// replies are shifted to the right of their top-level comments
+const REPLY_INDENT: i32 = 40;
+
+pub fn build(
+    item: &CommentVM,
+    tx_req: &RequestSender,
+    composer: &Rc<CommentComposer>,
+) -> gtk::ListBoxRow {
+    let box_ = gtk::ListBoxRow::new();
+
+    let item_view_glade = include_str!("../comment_item_view.glade");
+    let builder = Builder::from_string(item_view_glade);
+    let item_view: gtk::Box = builder
+        .get_object("comment_view")
+        .expect("Couldn't get comment_view");
+
+    let avatar: gtk::Image = builder
+        .get_object("cmt_avatar")
+        .expect("Couldn't get cmt_avatar");
+    item.bind_property("avatar", &avatar, "file")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    for (name, id) in &[
+        ("author", "cmt_author"),
+        ("replyto", "cmt_reply_to"),
+        ("datetime", "cmt_datetime"),
+        ("text", "cmt_text"),
+    ] {
+        let label: gtk::Label = builder
+            .get_object(id)
+            .unwrap_or_else(|| panic!("Couldn't get {}", id));
+        item.bind_property(name, &label, "label")
+            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+            .build();
+    }
+
+    // photos
+    for i in 0..4 {
+        if test_property(&item.get_property(&format!("image{}vis", i)), true) {
+            let id = format!("image_{}", i);
+            let image: gtk::Image = builder
+                .get_object(&id)
+                .unwrap_or_else(|| panic!("Couldn't get {}", id));
+            image.set_visible(true);
+            item.bind_property(&format!("image{}", i), &image, "file")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .build();
+        }
+    }
+
+    // the rest of the thread is requested on demand
+    let more: gtk::Button = builder
+        .get_object("cmt_more")
+        .expect("Couldn't get cmt_more");
+    item.bind_property("more", &more, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    item.bind_property("morevis", &more, "visible")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    more.connect_clicked(clone!(@weak item, @strong tx_req => move |_| {
+        send_request(
+            &tx_req,
+            Request::Comments(CommentRequest::Thread(item.get_id())),
+        );
+    }));
+
+    let reply: gtk::Button = builder
+        .get_object("cmt_reply")
+        .expect("Couldn't get cmt_reply");
+    reply.connect_clicked(clone!(@weak item, @weak composer => move |_| {
+        composer.reply(&item);
+    }));
+
+    if item.get_thread_id() != 0 {
+        item_view.set_margin_start(REPLY_INDENT);
+    }
+
+    box_.add(&item_view);
+    box_.show();
+    box_
+}
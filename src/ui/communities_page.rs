@@ -0,0 +1,73 @@
+use super::*;
+use crate::vk_provider::CommunitiesUpdate;
+
+/// Communities page lists every community user is subscribed to, user joins, leaves and mutes them in place,
+/// so the communities left are listed until the page is opened again
+pub struct CommunitiesPage {
+    model: gio::ListStore,
+}
+
+impl CommunitiesPage {
+    pub fn build(ui_builder: &Builder, tx_req: &RequestSender) -> Rc<Self> {
+        let model = gio::ListStore::new(CommunityVM::static_type());
+        let communities_list: gtk::ListBox = ui_builder
+            .get_object("communities_list")
+            .expect("Couldn't get communities_list widget");
+        communities_list.bind_model(
+            Some(&model),
+            clone!(@strong tx_req => move |item| {
+                let item = item
+                    .downcast_ref::<CommunityVM>()
+                    .expect("Community view model is of wrong type");
+                let box_ = community_list_box_row::build(item, &tx_req);
+                box_.upcast::<gtk::Widget>()
+            }),
+        );
+        // open the community page in the browser
+        communities_list.connect_row_activated(clone!(@weak model => move |_, row| {
+            if let Some(item) = model.get_object(row.get_index() as u32) {
+                if let Ok(community) = item.downcast::<CommunityVM>() {
+                    let uri = community.get_uri();
+                    if let Err(e) = gio::AppInfo::launch_default_for_uri(&uri, None::<&gio::AppLaunchContext>) {
+                        log::error!("failed opening {}: {}", uri, e);
+                    }
+                }
+            }
+        }));
+        Rc::new(CommunitiesPage { model })
+    }
+
+    /// Replaces the displayed communities with the received ones
+    pub fn update(&self, update: CommunitiesUpdate) {
+        self.model.remove_all();
+        for community in update.into_iter() {
+            self.model.append(&CommunityVM::new(&community));
+        }
+    }
+
+    /// User has joined or left the community
+    pub fn set_member(&self, group_id: i64, member: bool) {
+        if let Some(vm) = self.find(group_id) {
+            vm.set_member(member);
+        }
+    }
+
+    /// News of the source have been muted or unmuted, the source is a community if its id is negative
+    pub fn set_muted(&self, source_id: i64, muted: bool) {
+        if let Some(vm) = self.find(-source_id) {
+            vm.set_muted(muted);
+        }
+    }
+
+    /// Forgets communities displayed for the account
+    pub fn clear(&self) {
+        self.model.remove_all();
+    }
+
+    fn find(&self, group_id: i64) -> Option<CommunityVM> {
+        (0..self.model.get_n_items())
+            .filter_map(|pos| self.model.get_object(pos))
+            .filter_map(|item| item.downcast::<CommunityVM>().ok())
+            .find(|vm| vm.get_id() == group_id)
+    }
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+pub fn build(item: &CommunityVM, tx_req: &RequestSender) -> gtk::ListBoxRow {
+    let box_ = gtk::ListBoxRow::new();
+
+    let item_view_glade = include_str!("../community_item_view.glade");
+    let builder = Builder::from_string(item_view_glade);
+    let item_view: gtk::Box = builder
+        .get_object("community_view")
+        .expect("Couldn't get community_view");
+
+    let avatar: gtk::Image = builder
+        .get_object("community_avatar")
+        .expect("Couldn't get community_avatar");
+    item.bind_property("avatar", &avatar, "file")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    for (property, id) in &[
+        ("name", "community_name"),
+        ("info", "community_info"),
+        ("description", "community_description"),
+    ] {
+        let label: gtk::Label = builder
+            .get_object(id)
+            .unwrap_or_else(|| panic!("Couldn't get {}", id));
+        item.bind_property(property, &label, "label")
+            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+            .build();
+    }
+
+    // the check buttons show what vk_provider has confirmed, a click requests the change,
+    // so nothing is requested when the button follows the view model
+    let member: gtk::CheckButton = builder
+        .get_object("community_member")
+        .expect("Couldn't get community_member");
+    item.bind_property("member", &member, "active")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    member.connect_toggled(clone!(@weak item, @strong tx_req => move |button| {
+        let active = button.get_active();
+        if active != item.is_member() {
+            let group_id = item.get_id();
+            let request = if active {
+                CommunityRequest::Join(group_id)
+            } else {
+                CommunityRequest::Leave(group_id)
+            };
+            send_request(&tx_req, Request::Communities(request));
+        }
+    }));
+
+    let muted: gtk::CheckButton = builder
+        .get_object("community_muted")
+        .expect("Couldn't get community_muted");
+    item.bind_property("muted", &muted, "active")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    muted.connect_toggled(clone!(@weak item, @strong tx_req => move |button| {
+        let active = button.get_active();
+        if active != item.is_muted() {
+            send_request(
+                &tx_req,
+                Request::MuteSource {
+                    source_id: -item.get_id(),
+                    muted: active,
+                },
+            );
+        }
+    }));
+
+    box_.add(&item_view);
+    box_.show();
+    box_
+}
@@ -0,0 +1,57 @@
+use super::*;
+
+pub fn build(item: &ConversationVM) -> gtk::ListBoxRow {
+    let box_ = gtk::ListBoxRow::new();
+
+    let item_view_glade = include_str!("../conversation_item_view.glade");
+    let builder = Builder::from_string(item_view_glade);
+    let item_view: gtk::Box = builder
+        .get_object("conversation_view")
+        .expect("Couldn't get conversation_view");
+
+    let avatar: gtk::Image = builder
+        .get_object("conv_avatar")
+        .expect("Couldn't get conv_avatar");
+    item.bind_property("avatar", &avatar, "file")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let title: gtk::Label = builder
+        .get_object("conv_title")
+        .expect("Couldn't get conv_title");
+    item.bind_property("title", &title, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let unread: gtk::Label = builder
+        .get_object("conv_unread")
+        .expect("Couldn't get conv_unread");
+    item.bind_property("unread", &unread, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let online: gtk::Label = builder
+        .get_object("conv_online")
+        .expect("Couldn't get conv_online");
+    item.bind_property("online", &online, "visible")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let last_message: gtk::Label = builder
+        .get_object("conv_last_message")
+        .expect("Couldn't get conv_last_message");
+    item.bind_property("lastmessage", &last_message, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let datetime: gtk::Label = builder
+        .get_object("conv_datetime")
+        .expect("Couldn't get conv_datetime");
+    item.bind_property("datetime", &datetime, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    box_.add(&item_view);
+    box_.show();
+    box_
+}
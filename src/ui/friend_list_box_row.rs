@@ -0,0 +1,43 @@
+use super::*;
+
+pub fn build(item: &FriendVM) -> gtk::ListBoxRow {
+    let box_ = gtk::ListBoxRow::new();
+
+    let item_view_glade = include_str!("../friend_item_view.glade");
+    let builder = Builder::from_string(item_view_glade);
+    let item_view: gtk::Box = builder
+        .get_object("friend_view")
+        .expect("Couldn't get friend_view");
+
+    let avatar: gtk::Image = builder
+        .get_object("friend_avatar")
+        .expect("Couldn't get friend_avatar");
+    item.bind_property("avatar", &avatar, "file")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let name: gtk::Label = builder
+        .get_object("friend_name")
+        .expect("Couldn't get friend_name");
+    item.bind_property("name", &name, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let status: gtk::Label = builder
+        .get_object("friend_status")
+        .expect("Couldn't get friend_status");
+    item.bind_property("status", &status, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let lists: gtk::Label = builder
+        .get_object("friend_lists")
+        .expect("Couldn't get friend_lists");
+    item.bind_property("lists", &lists, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    box_.add(&item_view);
+    box_.show();
+    box_
+}
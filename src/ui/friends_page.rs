@@ -0,0 +1,138 @@
+use super::*;
+use crate::models::{arrange_friends, FriendModel, FriendsOrder};
+
+// id of the friend list filter item to show all friends
+const ALL_FRIENDS_ID: &str = "all";
+// ids of the other items are the list names with the prefix, so a list can't be taken for all friends
+const LIST_ID_PREFIX: &str = "list:";
+
+/// Friends page keeps the friends received last time to search, sort and group them without querying vk.com,
+/// the list is rebuilt on every change as ListBox filter and sort functions do not work with bound models
+pub struct FriendsPage {
+    friends: RefCell<Vec<FriendModel>>,
+    model: gio::ListStore,
+    ui_builder: Builder,
+}
+
+impl FriendsPage {
+    pub fn build(ui_builder: &Builder) -> Rc<Self> {
+        let model = gio::ListStore::new(FriendVM::static_type());
+        let friends_list: gtk::ListBox = ui_builder
+            .get_object("friends_list")
+            .expect("Couldn't get friends_list widget");
+        friends_list.bind_model(Some(&model), |item| {
+            let item = item
+                .downcast_ref::<FriendVM>()
+                .expect("Friend view model is of wrong type");
+            let box_ = friend_list_box_row::build(item);
+            box_.upcast::<gtk::Widget>()
+        });
+        // open the friend's page in the browser
+        friends_list.connect_row_activated(clone!(@weak model => move |_, row| {
+            if let Some(item) = model.get_object(row.get_index() as u32) {
+                if let Ok(friend) = item.downcast::<FriendVM>() {
+                    let uri = friend.get_uri();
+                    if let Err(e) = gio::AppInfo::launch_default_for_uri(&uri, None::<&gio::AppLaunchContext>) {
+                        log::error!("failed opening {}: {}", uri, e);
+                    }
+                }
+            }
+        }));
+
+        let page = Rc::new(FriendsPage {
+            friends: RefCell::new(Vec::new()),
+            model,
+            ui_builder: ui_builder.clone(),
+        });
+        let search: gtk::SearchEntry = ui_builder
+            .get_object("friends_search")
+            .expect("Couldn't get friends_search");
+        search.connect_search_changed(clone!(@weak page => move |_| page.show()));
+        page.list_filter()
+            .connect_changed(clone!(@weak page => move |_| page.show()));
+        let order: gtk::ComboBoxText = ui_builder
+            .get_object("friends_order")
+            .expect("Couldn't get friends_order");
+        order.connect_changed(clone!(@weak page => move |_| page.show()));
+        page
+    }
+
+    /// Replaces the displayed friends with the received ones
+    pub fn update(&self, update: FriendsUpdate) {
+        let list_filter = self.list_filter();
+        let active = list_filter.get_active_id();
+        let lists = update.lists().to_vec();
+        *self.friends.borrow_mut() = update.into_iter().collect();
+        // the selected list is kept if it is still there
+        list_filter.remove_all();
+        list_filter.append(Some(ALL_FRIENDS_ID), "all friends");
+        for list in &lists {
+            list_filter.append(Some(&format!("{}{}", LIST_ID_PREFIX, list)), list.as_str());
+        }
+        let kept = active.map(|id| id.to_string()).filter(|id| {
+            lists
+                .iter()
+                .any(|list| id == &format!("{}{}", LIST_ID_PREFIX, list))
+        });
+        list_filter.set_active_id(Some(kept.as_deref().unwrap_or(ALL_FRIENDS_ID)));
+        self.show();
+    }
+
+    /// The friend has gone online or offline
+    pub fn set_online(&self, user_id: i64, online: bool) {
+        let found = match self
+            .friends
+            .borrow_mut()
+            .iter_mut()
+            .find(|friend| friend.id == user_id)
+        {
+            Some(friend) => {
+                friend.set_online(online, chrono::Utc::now().timestamp());
+                true
+            }
+            None => false,
+        };
+        if found {
+            self.show();
+        }
+    }
+
+    /// Forgets friends displayed for the account
+    pub fn clear(&self) {
+        self.friends.borrow_mut().clear();
+        self.model.remove_all();
+    }
+
+    fn list_filter(&self) -> gtk::ComboBoxText {
+        self.ui_builder
+            .get_object("friends_list_filter")
+            .expect("Couldn't get friends_list_filter")
+    }
+
+    // fills the list with the friends matching the search and the list selected, in the order selected
+    fn show(&self) {
+        let search: gtk::SearchEntry = self
+            .ui_builder
+            .get_object("friends_search")
+            .expect("Couldn't get friends_search");
+        let order: gtk::ComboBoxText = self
+            .ui_builder
+            .get_object("friends_order")
+            .expect("Couldn't get friends_order");
+        let list = self
+            .list_filter()
+            .get_active_id()
+            .and_then(|id| id.strip_prefix(LIST_ID_PREFIX).map(|list| list.to_string()));
+        let order = FriendsOrder::from_id(order.get_active_id().as_deref().unwrap_or(""));
+        let selected = arrange_friends(
+            &self.friends.borrow(),
+            search.get_text().as_str(),
+            list.as_deref(),
+            order,
+        );
+        self.model.remove_all();
+        for friend in &selected {
+            self.model.append(&FriendVM::new(friend));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::*;
+
+pub fn build(item: &MessageVM) -> gtk::ListBoxRow {
+    let box_ = gtk::ListBoxRow::new();
+
+    let item_view_glade = include_str!("../message_item_view.glade");
+    let builder = Builder::from_string(item_view_glade);
+    let item_view: gtk::Box = builder
+        .get_object("message_view")
+        .expect("Couldn't get message_view");
+
+    let avatar: gtk::Image = builder
+        .get_object("msg_avatar")
+        .expect("Couldn't get msg_avatar");
+    item.bind_property("avatar", &avatar, "file")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let author: gtk::Label = builder
+        .get_object("msg_author")
+        .expect("Couldn't get msg_author");
+    item.bind_property("author", &author, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let datetime: gtk::Label = builder
+        .get_object("msg_datetime")
+        .expect("Couldn't get msg_datetime");
+    item.bind_property("datetime", &datetime, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    let text: gtk::Label = builder
+        .get_object("msg_text")
+        .expect("Couldn't get msg_text");
+    item.bind_property("text", &text, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    // photo
+    if test_property(&item.get_property("imagevis"), true) {
+        let image: gtk::Image = builder
+            .get_object("msg_image")
+            .expect("Couldn't get msg_image");
+        image.set_visible(true);
+        item.bind_property("image", &image, "file")
+            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+            .build();
+    }
+
+    // own messages are shifted to the right
+    if test_property(&item.get_property("out"), true) {
+        item_view.set_margin_start(50);
+    } else {
+        item_view.set_margin_end(50);
+    }
+
+    box_.add(&item_view);
+    box_.show();
+    box_
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-pub fn build(item: &NewsItemVM) -> gtk::ListBoxRow {
+pub fn build(item: &NewsItemVM, tx_req: &RequestSender) -> gtk::ListBoxRow {
     let box_ = gtk::ListBoxRow::new();
 
     let news_item_view_glade = include_str!("../news_item_view.glade");
@@ -229,6 +229,53 @@ pub fn build(item: &NewsItemVM) -> gtk::ListBoxRow {
             .build();
     }
 
+    // counters
+    if test_property(&item.get_property("countersvis"), true) {
+        let counters: gtk::Box = builder
+            .get_object("counters")
+            .expect("Couldn't get counters");
+        counters.set_visible(true);
+        for name in &["likes", "comments", "reposts", "views"] {
+            let id = format!("{}_count", name);
+            let count: gtk::Label = builder
+                .get_object(&id)
+                .unwrap_or_else(|| panic!("Couldn't get {}", id));
+            item.bind_property(name, &count, "label")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .build();
+        }
+        // the change is shown at once, vk_provider takes it back if it fails
+        let like: gtk::ToggleButton = builder
+            .get_object("like_button")
+            .expect("Couldn't get like_button");
+        item.bind_property("liked", &like, "active")
+            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+            .build();
+        like.connect_toggled(clone!(@weak item, @strong tx_req => move |button| {
+            let liked = button.get_active();
+            if liked != item.is_liked() {
+                item.set_liked(liked, None);
+                send_request(
+                    &tx_req,
+                    Request::Like {
+                        item: item.get_id(),
+                        liked,
+                    },
+                );
+            }
+        }));
+        // the post page is shown as soon as the comments are received
+        let comments: gtk::Button = builder
+            .get_object("comments_button")
+            .expect("Couldn't get comments_button");
+        comments.connect_clicked(clone!(@weak item, @strong tx_req => move |_| {
+            send_request(
+                &tx_req,
+                Request::Comments(CommentRequest::Open(item.get_id())),
+            );
+        }));
+    }
+
     box_.add(&news_item_view);
     box_.show();
     box_
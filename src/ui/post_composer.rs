@@ -0,0 +1,212 @@
+use super::*;
+use crate::utils::timestamp_from_local;
+
+// id of the own wall in the list of walls to publish to
+const OWN_WALL_ID: &str = "0";
+
+/// Dialog publishing the post to the user's own wall or to the wall of the community
+/// the user administers, the post is published at once or scheduled to the date entered,
+/// the dialog is kept open until the provider answers to let user fix the post if it has failed
+pub struct PostComposer {
+    dialog: gtk::Dialog,
+    form: gtk::Grid,
+    target: gtk::ComboBoxText,
+    from_group: gtk::CheckButton,
+    text: gtk::TextView,
+    attach: gtk::Button,
+    photos_label: Label,
+    schedule: gtk::CheckButton,
+    publish_date: gtk::Entry,
+    publish: gtk::Button,
+    error: Label,
+    // files of the photos to attach
+    photos: RefCell<Vec<String>>,
+    tx_req: RequestSender,
+}
+
+impl PostComposer {
+    pub fn build(parent: &ApplicationWindow, tx_req: &RequestSender) -> Rc<Self> {
+        let post_composer_glade = include_str!("../post_composer.glade");
+        let builder = Builder::from_string(post_composer_glade);
+        let composer = Rc::new(PostComposer {
+            dialog: builder
+                .get_object("post_composer_dialog")
+                .expect("Couldn't get post_composer_dialog"),
+            form: builder
+                .get_object("post_grid")
+                .expect("Couldn't get post_grid"),
+            target: builder
+                .get_object("post_target")
+                .expect("Couldn't get post_target"),
+            from_group: builder
+                .get_object("post_from_group")
+                .expect("Couldn't get post_from_group"),
+            text: builder
+                .get_object("post_text")
+                .expect("Couldn't get post_text"),
+            attach: builder
+                .get_object("post_attach")
+                .expect("Couldn't get post_attach"),
+            photos_label: builder
+                .get_object("post_photos")
+                .expect("Couldn't get post_photos"),
+            schedule: builder
+                .get_object("post_schedule")
+                .expect("Couldn't get post_schedule"),
+            publish_date: builder
+                .get_object("post_publish_date")
+                .expect("Couldn't get post_publish_date"),
+            publish: builder
+                .get_object("post_publish")
+                .expect("Couldn't get post_publish"),
+            error: builder
+                .get_object("post_error")
+                .expect("Couldn't get post_error"),
+            photos: RefCell::new(Vec::new()),
+            tx_req: tx_req.clone(),
+        });
+        composer.dialog.set_transient_for(Some(parent));
+        // the dialog is built once and shown on demand
+        composer
+            .dialog
+            .connect_delete_event(|dialog, _| dialog.hide_on_delete());
+        composer
+            .dialog
+            .connect_response(clone!(@weak composer => move |dialog, response| {
+                if response == gtk::ResponseType::Apply {
+                    composer.submit();
+                } else {
+                    dialog.hide();
+                }
+            }));
+        // only the community signs its posts
+        composer
+            .target
+            .connect_changed(clone!(@weak composer => move |target| {
+                let own_wall = target.get_active_id().map_or(true, |id| id == OWN_WALL_ID);
+                composer.from_group.set_sensitive(!own_wall);
+                if own_wall {
+                    composer.from_group.set_active(false);
+                }
+            }));
+        composer
+            .schedule
+            .connect_toggled(clone!(@weak composer => move |schedule| {
+                composer.publish_date.set_sensitive(schedule.get_active());
+            }));
+        composer
+            .attach
+            .connect_clicked(clone!(@weak composer => move |attach| {
+                composer.photos.borrow_mut().extend(choose_photos(attach));
+                composer.show_photos();
+            }));
+        composer
+    }
+
+    /// Shows the empty composer, the walls to publish to are requested anew
+    /// since user might have become an admin of one more community
+    pub fn show(&self) {
+        self.set_locked(false);
+        self.set_targets(None);
+        self.from_group.set_active(false);
+        if let Some(buffer) = self.text.get_buffer() {
+            buffer.set_text("");
+        }
+        self.photos.borrow_mut().clear();
+        self.show_photos();
+        self.schedule.set_active(false);
+        self.publish_date.set_text("");
+        self.error.set_label("");
+        send_request(&self.tx_req, Request::Publish(PublishRequest::Targets));
+        self.dialog.show_all();
+        self.text.grab_focus();
+    }
+
+    /// Lists the own wall followed by the communities the user administers
+    pub fn set_targets(&self, update: Option<CommunitiesUpdate>) {
+        let selected = self.target.get_active_id();
+        self.target.remove_all();
+        self.target.append(Some(OWN_WALL_ID), "My wall");
+        if let Some(update) = update {
+            for community in update.items() {
+                self.target
+                    .append(Some(&community.id.to_string()), &community.name);
+            }
+        }
+        // keep the wall chosen while the communities have been received
+        let selected = selected.map_or(OWN_WALL_ID.to_string(), |id| id.to_string());
+        if !self.target.set_active_id(Some(&selected)) {
+            self.target.set_active_id(Some(OWN_WALL_ID));
+        }
+    }
+
+    /// The provider has answered, the composer is closed if the post has been published,
+    /// otherwise it is kept for user to try again
+    pub fn published(&self, post_id: Option<i64>) {
+        self.set_locked(false);
+        match post_id {
+            Some(_) => self.dialog.hide(),
+            None => self.error.set_label("The post has not been published"),
+        }
+    }
+
+    fn submit(&self) {
+        let text = self
+            .text
+            .get_buffer()
+            .and_then(|buffer| {
+                let (start, end) = buffer.get_bounds();
+                buffer.get_text(&start, &end, false)
+            })
+            .map_or(String::new(), |text| text.trim().to_string());
+        let photos = self.photos.borrow().clone();
+        if text.is_empty() && photos.is_empty() {
+            self.error.set_label("Enter the text or attach photos");
+            return;
+        }
+        let publish_date = if self.schedule.get_active() {
+            match timestamp_from_local(&self.publish_date.get_text()) {
+                Some(date) if date > chrono::Utc::now().timestamp() => Some(date),
+                Some(_) => {
+                    self.error.set_label("The date to publish at has passed");
+                    return;
+                }
+                None => {
+                    self.error
+                        .set_label("Enter the date to publish at like 2021-01-31 18:30");
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let group_id = self
+            .target
+            .get_active_id()
+            .and_then(|id| id.parse::<i64>().ok())
+            .unwrap_or(0);
+        let draft = PostDraft {
+            group_id,
+            text,
+            photos,
+            from_group: group_id != 0 && self.from_group.get_active(),
+            publish_date,
+        };
+        log::debug!("publishing {}", draft);
+        self.error.set_label("");
+        self.set_locked(true);
+        send_request(&self.tx_req, Request::Publish(PublishRequest::Post(draft)));
+    }
+
+    fn show_photos(&self) {
+        let count = self.photos.borrow().len();
+        self.photos_label.set_label(&format!("{} photo(s)", count));
+        self.photos_label.set_visible(count > 0);
+    }
+
+    // the post being published is not editable
+    fn set_locked(&self, locked: bool) {
+        self.form.set_sensitive(!locked);
+        self.publish.set_sensitive(!locked);
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+
+/// Post page shows the post opened from news with its comments,
+/// replies follow their top-level comments and the rest of every thread is requested on demand
+pub struct PostPage {
+    model: gio::ListStore,
+    header: gtk::ListBox,
+    more: gtk::Button,
+    composer: Rc<CommentComposer>,
+    // the post displayed, comments of other posts are late and ignored
+    post: RefCell<Option<NewsItemId>>,
+    tx_req: RequestSender,
+}
+
+impl PostPage {
+    pub fn build(ui_builder: &Builder, tx_req: &RequestSender) -> Rc<Self> {
+        let model = gio::ListStore::new(CommentVM::static_type());
+        let composer = CommentComposer::build(ui_builder, tx_req);
+        let comment_list: gtk::ListBox = ui_builder
+            .get_object("comment_list")
+            .expect("Couldn't get comment_list widget");
+        comment_list.bind_model(
+            Some(&model),
+            clone!(@strong tx_req, @strong composer => move |item| {
+                let item = item
+                    .downcast_ref::<CommentVM>()
+                    .expect("Comment view model is of wrong type");
+                let box_ = comment_list_box_row::build(item, &tx_req, &composer);
+                box_.upcast::<gtk::Widget>()
+            }),
+        );
+        let header: gtk::ListBox = ui_builder
+            .get_object("post_header")
+            .expect("Couldn't get post_header widget");
+        let more: gtk::Button = ui_builder
+            .get_object("comments_more")
+            .expect("Couldn't get comments_more widget");
+        more.connect_clicked(clone!(@strong tx_req => move |_| {
+            send_request(&tx_req, Request::Comments(CommentRequest::More));
+        }));
+        let back: gtk::Button = ui_builder
+            .get_object("post_back")
+            .expect("Couldn't get post_back widget");
+        back.connect_clicked(clone!(@strong ui_builder => move |_| {
+            show_right_pane(&ui_builder, "page_view_home");
+        }));
+        Rc::new(PostPage {
+            model,
+            header,
+            more,
+            composer,
+            post: RefCell::new(None),
+            tx_req: tx_req.clone(),
+        })
+    }
+
+    /// Displays the post with the first page of its comments instead of the previous one,
+    /// the post itself is shown if it is displayed in news
+    pub fn open(&self, post: NewsItemId, item: Option<&NewsItemVM>, update: CommentsUpdate) {
+        self.clear();
+        if let Some(item) = item {
+            self.header
+                .add(&news_list_box_row::build(item, &self.tx_req));
+        }
+        *self.post.borrow_mut() = Some(post.clone());
+        self.add(&post, 0, update);
+    }
+
+    /// Adds comments of the post: replies to the top-level comment with id thread follow
+    /// the displayed part of the thread, top-level comments follow the displayed ones
+    pub fn add(&self, post: &NewsItemId, thread: i64, update: CommentsUpdate) {
+        if self.post.borrow().as_ref() != Some(post) {
+            return;
+        }
+        if thread == 0 {
+            self.more.set_visible(update.has_more());
+        }
+        let thread_over = thread != 0 && !update.has_more();
+        let mut threads = Vec::new();
+        for comment in update.into_iter() {
+            if self.find(comment.id).is_some() {
+                continue;
+            }
+            let vm = CommentVM::new(&comment);
+            if comment.thread_id != 0 {
+                threads.push(comment.thread_id);
+                match self.find_thread_end(comment.thread_id) {
+                    Some(pos) => self.model.insert(pos + 1, &vm),
+                    None => self.model.append(&vm),
+                }
+            } else {
+                threads.push(comment.id);
+                self.model.append(&vm);
+            }
+        }
+        if thread_over {
+            threads.push(thread);
+        }
+        threads.sort_unstable();
+        threads.dedup();
+        for id in threads {
+            if let Some((_, root)) = self.find(id) {
+                let left = if id == thread && thread_over {
+                    0
+                } else {
+                    root.get_thread_count() - self.count_replies(id)
+                };
+                root.set_replies_left(left);
+            }
+        }
+    }
+
+    /// Displays the comment user has sent to the post after the displayed ones,
+    /// the reply follows the displayed part of its thread
+    pub fn sent(&self, post: &NewsItemId, reply_to: i64, comment: Option<CommentModel>) {
+        self.composer.sent(comment.is_some());
+        if self.post.borrow().as_ref() != Some(post) {
+            return;
+        }
+        let mut comment = match comment {
+            Some(comment) => comment,
+            None => return,
+        };
+        if comment.thread_id != 0 {
+            if let Some((_, replied)) = self.find(reply_to) {
+                comment.reply_to = replied.get_author();
+            }
+            // the count of replies left to show stays the same
+            if let Some((_, root)) = self.find(comment.thread_id) {
+                root.add_reply();
+            }
+        }
+        let thread_end = match comment.thread_id {
+            0 => None,
+            thread => self.find_thread_end(thread),
+        };
+        let vm = CommentVM::new(&comment);
+        match thread_end {
+            Some(pos) => self.model.insert(pos + 1, &vm),
+            None => self.model.append(&vm),
+        }
+    }
+
+    /// Forgets the post displayed for the account
+    pub fn clear(&self) {
+        self.composer.reset();
+        *self.post.borrow_mut() = None;
+        self.model.remove_all();
+        for child in self.header.get_children() {
+            self.header.remove(&child);
+        }
+        self.more.set_visible(false);
+    }
+
+    fn comments(&self) -> impl Iterator<Item = (u32, CommentVM)> + '_ {
+        (0..self.model.get_n_items()).filter_map(move |pos| {
+            self.model
+                .get_object(pos)
+                .and_then(|item| item.downcast::<CommentVM>().ok())
+                .map(|vm| (pos, vm))
+        })
+    }
+
+    fn find(&self, id: i64) -> Option<(u32, CommentVM)> {
+        self.comments().find(|(_, vm)| vm.get_id() == id)
+    }
+
+    // position of the last displayed comment of the thread, i.e. of the top-level comment or its last reply
+    fn find_thread_end(&self, thread_id: i64) -> Option<u32> {
+        self.comments()
+            .filter(|(_, vm)| vm.get_id() == thread_id || vm.get_thread_id() == thread_id)
+            .map(|(pos, _)| pos)
+            .last()
+    }
+
+    fn count_replies(&self, thread_id: i64) -> i64 {
+        self.comments()
+            .filter(|(_, vm)| vm.get_thread_id() == thread_id)
+            .count() as i64
+    }
+}
@@ -0,0 +1,77 @@
+use super::*;
+use crate::settings::Settings;
+
+/// Shows dialog editing user settings,
+/// the saved ones are applied by the watcher of the config file (see watch_settings)
+pub fn show(parent: &ApplicationWindow) {
+    let preferences_glade = include_str!("../preferences.glade");
+    let builder = Builder::from_string(preferences_glade);
+    let dialog: gtk::Dialog = builder
+        .get_object("preferences_dialog")
+        .expect("Couldn't get preferences_dialog");
+    dialog.set_transient_for(Some(parent));
+
+    let poll_interval: gtk::SpinButton = builder
+        .get_object("preferences_poll_interval")
+        .expect("Couldn't get preferences_poll_interval");
+    let initial_news: gtk::SpinButton = builder
+        .get_object("preferences_initial_news")
+        .expect("Couldn't get preferences_initial_news");
+    let news_page_size: gtk::SpinButton = builder
+        .get_object("preferences_news_page_size")
+        .expect("Couldn't get preferences_news_page_size");
+    let photo_sizes: gtk::Entry = builder
+        .get_object("preferences_photo_sizes")
+        .expect("Couldn't get preferences_photo_sizes");
+    let log_filter: gtk::Entry = builder
+        .get_object("preferences_log_filter")
+        .expect("Couldn't get preferences_log_filter");
+    let cache_dir: gtk::Entry = builder
+        .get_object("preferences_cache_dir")
+        .expect("Couldn't get preferences_cache_dir");
+    let error: Label = builder
+        .get_object("preferences_error")
+        .expect("Couldn't get preferences_error");
+
+    let settings = Settings::load();
+    poll_interval.set_value(settings.poll_interval_sec as f64);
+    initial_news.set_value(settings.initial_news_sec as f64);
+    news_page_size.set_value(settings.news_page_size as f64);
+    photo_sizes.set_text(&settings.photo_sizes.join(", "));
+    log_filter.set_text(&settings.log_filter);
+    cache_dir.set_text(settings.cache_dir.as_deref().unwrap_or(""));
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Apply {
+            let cache_dir = cache_dir.get_text().trim().to_string();
+            let edited = Settings {
+                poll_interval_sec: poll_interval.get_value_as_int() as u64,
+                initial_news_sec: initial_news.get_value_as_int() as u64,
+                news_page_size: news_page_size.get_value_as_int() as u32,
+                photo_sizes: photo_sizes
+                    .get_text()
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect(),
+                log_filter: log_filter.get_text().trim().to_string(),
+                cache_dir: if cache_dir.is_empty() {
+                    None
+                } else {
+                    Some(cache_dir)
+                },
+                // edited in the file only
+                ..settings.clone()
+            };
+            if let Err(e) = edited.save() {
+                // keep dialog open to let user fix the value
+                error.set_label(&format!("{}", e));
+                return;
+            }
+            log::info!("settings are saved");
+        }
+        dialog.close();
+    });
+
+    dialog.show_all();
+}
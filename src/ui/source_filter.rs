@@ -0,0 +1,177 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Filters the news list to the source selected in the sources pane. ListBox filter functions do not work
+/// with bound models, so the news list is bound to the own model of the source news while it is selected.
+/// Scrolled back, the source news are followed by the posts of the source wall (the source history mode)
+pub struct SourceFilter {
+    // source_id of the selected source, 0 if news of all the sources are displayed
+    source_id: Cell<i64>,
+    // the source wall has been requested since the source was selected
+    history: Cell<bool>,
+    model: gio::ListStore,
+    // items of the model, a post might come both with news and from the wall
+    items: RefCell<HashMap<NewsItemId, NewsItemVM>>,
+    all_news: gio::ListStore,
+    ui_builder: Builder,
+    tx_req: RequestSender,
+}
+
+impl SourceFilter {
+    pub fn build(
+        ui_builder: &Builder,
+        all_news: &gio::ListStore,
+        tx_req: &RequestSender,
+    ) -> Rc<Self> {
+        let filter = Rc::new(SourceFilter {
+            source_id: Cell::new(0),
+            history: Cell::new(false),
+            model: gio::ListStore::new(NewsItemVM::static_type()),
+            items: RefCell::new(HashMap::new()),
+            all_news: all_news.clone(),
+            ui_builder: ui_builder.clone(),
+            tx_req: tx_req.clone(),
+        });
+        let news_sources: gtk::ListBox = ui_builder
+            .get_object("news_sources")
+            .expect("Couldn't get news_sources widget");
+        let news_all: gtk::Button = ui_builder
+            .get_object("news_all")
+            .expect("Couldn't get news_all");
+        news_all.connect_clicked(clone!(@weak news_sources => move |_| {
+            news_sources.unselect_all();
+        }));
+        filter
+    }
+
+    /// Returns source_id of the selected source, 0 if news of all the sources are displayed
+    pub fn source_id(&self) -> i64 {
+        self.source_id.get()
+    }
+
+    /// The more recent news item has been added to the news, it is displayed if it is of the selected source
+    pub fn add_newer(&self, vm: &NewsItemVM) {
+        if self.accepts(vm) {
+            self.model.append(vm);
+        }
+    }
+
+    /// The older news item has been added to the news, it is displayed if it is of the selected source
+    pub fn add_older(&self, vm: &NewsItemVM) {
+        if self.accepts(vm) {
+            self.model.insert(0, vm);
+        }
+    }
+
+    /// Requests posts of the source wall prior the displayed ones,
+    /// the first time after selecting the source the wall is paged from its most recent post
+    pub fn request_older(&self) {
+        let source_id = self.source_id.get();
+        if source_id != 0 {
+            log::debug!("reached top, requesting older posts of {}", source_id);
+            let restart = !self.history.replace(true);
+            send_request(&self.tx_req, Request::SourceHistory { source_id, restart });
+        }
+    }
+
+    /// Displays posts of the source wall before the displayed ones, skipping the displayed posts
+    pub fn add_history(&self, source_id: i64, update: NewsUpdate) {
+        // user might have selected another source meanwhile
+        if source_id != self.source_id.get() || update.is_empty() {
+            return;
+        }
+        let news_list = self.news_list();
+        let stored_height = news_list.get_preferred_height().1;
+        // the most recent first, so insert every next prior previous like older news
+        for model in update.into_iter() {
+            let id = model.id();
+            let displayed = self.items.borrow().get(&id).cloned();
+            match displayed {
+                Some(vm) => vm.update(&model),
+                None => {
+                    let vm = NewsItemVM::new(&model);
+                    self.model.insert(0, &vm);
+                    self.items.borrow_mut().insert(id, vm);
+                }
+            }
+        }
+        if let Some(adjustment) = news_list.get_adjustment() {
+            // keep the previous start item in place
+            let new_height = news_list.get_preferred_height().1;
+            adjustment.set_value(new_height as f64 - stored_height as f64);
+        }
+    }
+
+    /// Returns the displayed item of the selected source
+    pub fn find(&self, id: &NewsItemId) -> Option<NewsItemVM> {
+        self.items.borrow().get(id).cloned()
+    }
+
+    /// Forgets the selected source and displays news of all the sources
+    pub fn clear(&self) {
+        let news_sources: gtk::ListBox = self
+            .ui_builder
+            .get_object("news_sources")
+            .expect("Couldn't get news_sources widget");
+        news_sources.unselect_all();
+        self.show_all();
+    }
+
+    /// Displays the news of the source received so far
+    pub fn select(&self, source_id: i64) {
+        log::debug!("showing news of {}", source_id);
+        self.source_id.set(source_id);
+        self.history.set(false);
+        self.model.remove_all();
+        self.items.borrow_mut().clear();
+        for pos in 0..self.all_news.get_n_items() {
+            if let Some(vm) = self
+                .all_news
+                .get_object(pos)
+                .and_then(|item| item.downcast::<NewsItemVM>().ok())
+            {
+                self.add_newer(&vm);
+            }
+        }
+        bind_news(&self.news_list(), &self.model, &self.tx_req);
+        self.news_all().set_sensitive(true);
+    }
+
+    /// Displays news of all the sources
+    pub fn show_all(&self) {
+        if self.source_id.replace(0) != 0 {
+            log::debug!("showing news of all the sources");
+            bind_news(&self.news_list(), &self.all_news, &self.tx_req);
+        }
+        self.model.remove_all();
+        self.items.borrow_mut().clear();
+        self.news_all().set_sensitive(false);
+    }
+
+    // the item is of the selected source and is not displayed yet, then it is counted as displayed
+    fn accepts(&self, vm: &NewsItemVM) -> bool {
+        let id = vm.get_id();
+        if self.source_id.get() == 0 || id.source_id != self.source_id.get() {
+            return false;
+        }
+        let mut items = self.items.borrow_mut();
+        if items.contains_key(&id) {
+            false
+        } else {
+            items.insert(id, vm.clone());
+            true
+        }
+    }
+
+    fn news_list(&self) -> gtk::ListBox {
+        self.ui_builder
+            .get_object("news_list")
+            .expect("Couldn't get news_list")
+    }
+
+    fn news_all(&self) -> gtk::Button {
+        self.ui_builder
+            .get_object("news_all")
+            .expect("Couldn't get news_all")
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-pub fn build(item: &NewsSourceVM) -> gtk::ListBoxRow {
+pub fn build(item: &NewsSourceVM, tx_req: &RequestSender) -> gtk::ListBoxRow {
     let box_ = gtk::ListBoxRow::new();
 
     let item_view_glade = include_str!("../source_item_view.glade");
@@ -42,6 +42,52 @@ pub fn build(item: &NewsSourceVM) -> gtk::ListBoxRow {
         .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
         .build();
 
+    let activity: gtk::Label = builder
+        .get_object("src_activity")
+        .expect("Couldn't get src_activity");
+    item.bind_property("activity", &activity, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+
+    // the same as on the communities page: a click requests the change confirmed by vk_provider
+    let favourite: gtk::CheckButton = builder
+        .get_object("src_favourite")
+        .expect("Couldn't get src_favourite");
+    item.bind_property("favourite", &favourite, "active")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    favourite.connect_toggled(clone!(@weak item, @strong tx_req => move |button| {
+        let active = button.get_active();
+        if active != item.is_favourite() {
+            send_request(
+                &tx_req,
+                Request::FavouriteSource {
+                    source_id: item.get_id(),
+                    favourite: active,
+                },
+            );
+        }
+    }));
+
+    let muted: gtk::CheckButton = builder
+        .get_object("src_muted")
+        .expect("Couldn't get src_muted");
+    item.bind_property("muted", &muted, "active")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .build();
+    muted.connect_toggled(clone!(@weak item, @strong tx_req => move |button| {
+        let active = button.get_active();
+        if active != item.is_muted() {
+            send_request(
+                &tx_req,
+                Request::MuteSource {
+                    source_id: item.get_id(),
+                    muted: active,
+                },
+            );
+        }
+    }));
+
     box_.add(&item_view);
     box_.show();
     box_
@@ -0,0 +1,125 @@
+use super::*;
+use crate::models::{arrange_sources, NewsSourceModel, SourcesOrder};
+
+/// Sources pane keeps the sources met in news to order them by their activity,
+/// the list is rebuilt on every change like the friends page is
+pub struct SourcesPane {
+    sources: RefCell<Vec<NewsSourceModel>>,
+    model: gio::ListStore,
+    // the list is being rebuilt, so the selection is not changed by user
+    rebuilding: Cell<bool>,
+    filter: Rc<SourceFilter>,
+    ui_builder: Builder,
+}
+
+impl SourcesPane {
+    pub fn build(
+        ui_builder: &Builder,
+        filter: &Rc<SourceFilter>,
+        tx_req: &RequestSender,
+    ) -> Rc<Self> {
+        let model = gio::ListStore::new(NewsSourceVM::static_type());
+        let news_sources: gtk::ListBox = ui_builder
+            .get_object("news_sources")
+            .expect("Couldn't get news_sources widget");
+        news_sources.bind_model(
+            Some(&model),
+            clone!(@strong tx_req => move |item| {
+                let item = item
+                    .downcast_ref::<NewsSourceVM>()
+                    .expect("News source view model is of wrong type");
+                let box_ = sources_list_box_row::build(item, &tx_req);
+                box_.upcast::<gtk::Widget>()
+            }),
+        );
+
+        let pane = Rc::new(SourcesPane {
+            sources: RefCell::new(Vec::new()),
+            model,
+            rebuilding: Cell::new(false),
+            filter: filter.clone(),
+            ui_builder: ui_builder.clone(),
+        });
+        // selecting a source shows its news only
+        news_sources.connect_row_selected(clone!(@weak pane => move |_, row| {
+            if pane.rebuilding.get() {
+                return;
+            }
+            let source = row
+                .and_then(|row| pane.model.get_object(row.get_index() as u32))
+                .and_then(|item| item.downcast::<NewsSourceVM>().ok());
+            match source {
+                Some(source) => pane.filter.select(source.get_id()),
+                None => pane.filter.show_all(),
+            }
+        }));
+        let order: gtk::ComboBoxText = ui_builder
+            .get_object("sources_order")
+            .expect("Couldn't get sources_order");
+        order.connect_changed(clone!(@weak pane => move |_| pane.show()));
+        pane
+    }
+
+    /// Adds the sources met the first time and replaces the known ones
+    pub fn update(&self, update: SourcesUpdate) {
+        {
+            let mut sources = self.sources.borrow_mut();
+            for item in update.into_iter() {
+                match sources.iter_mut().find(|source| source.id == item.id) {
+                    Some(source) => *source = item,
+                    None => sources.push(item),
+                }
+            }
+        }
+        self.show();
+    }
+
+    /// News of the source have been muted or unmuted
+    pub fn set_muted(&self, source_id: i64, muted: bool) {
+        let found = match self
+            .sources
+            .borrow_mut()
+            .iter_mut()
+            .find(|source| source.id == source_id)
+        {
+            Some(source) => {
+                source.muted = muted;
+                true
+            }
+            None => false,
+        };
+        if found {
+            self.show();
+        }
+    }
+
+    /// Forgets sources displayed for the account
+    pub fn clear(&self) {
+        self.sources.borrow_mut().clear();
+        self.model.remove_all();
+    }
+
+    // fills the list in the order selected keeping the selected source selected
+    fn show(&self) {
+        let order: gtk::ComboBoxText = self
+            .ui_builder
+            .get_object("sources_order")
+            .expect("Couldn't get sources_order");
+        let order = SourcesOrder::from_id(order.get_active_id().as_deref().unwrap_or(""));
+        let arranged = arrange_sources(&self.sources.borrow(), order);
+        let selected = self.filter.source_id();
+        self.rebuilding.set(true);
+        self.model.remove_all();
+        for source in &arranged {
+            self.model.append(&NewsSourceVM::new(source));
+        }
+        if let Some(pos) = arranged.iter().position(|source| source.id == selected) {
+            let news_sources: gtk::ListBox = self
+                .ui_builder
+                .get_object("news_sources")
+                .expect("Couldn't get news_sources widget");
+            news_sources.select_row(news_sources.get_row_at_index(pos as i32).as_ref());
+        }
+        self.rebuilding.set(false);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
 
 pub fn local_from_timestamp(timestamp: i64) -> DateTime<Local> {
     utc_from_timestamp(timestamp).with_timezone(&Local)
@@ -9,10 +9,20 @@ pub fn utc_from_timestamp(timestamp: i64) -> DateTime<Utc> {
     DateTime::<Utc>::from_utc(naive, Utc)
 }
 
+/// Returns unix time of the local date and time entered like `2021-01-31 18:30`
+pub fn timestamp_from_local(text: &str) -> Option<i64> {
+    let naive = NaiveDateTime::parse_from_str(text.trim(), "%Y-%m-%d %H:%M").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.timestamp())
+}
+
 // perform desired text processing before display it
 pub fn process_text(text: &str) -> String {
     let result = glib::markup_escape_text(text).to_string();
-    markup_html_links(result.as_str())
+    // mentions go after links not to take their addresses for links once again
+    markup_mentions(markup_html_links(result.as_str()).as_str())
 }
 
 fn markup_html_links(text: &str) -> String {
@@ -38,10 +48,62 @@ fn markup_html_links(text: &str) -> String {
     result
 }
 
+// mentions of vk.com pages like [id1234|Ivan] or [club1|Community] become links to the pages
+fn markup_mentions(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut src = text;
+    while let Some(start) = src.find('[') {
+        result.push_str(&src[..start]);
+        let rest = &src[start..];
+        let mention = rest
+            .find(']')
+            .and_then(|end| parse_mention(&rest[1..end]).map(|mention| (end, mention)));
+        match mention {
+            Some((end, (page, name))) => {
+                result.push_str(&format!(
+                    r#"<a href="https://vk.com/{}">{}</a>"#,
+                    page, name
+                ));
+                src = &rest[end + 1..];
+            }
+            None => {
+                result.push('[');
+                src = &rest[1..];
+            }
+        }
+    }
+    result.push_str(src);
+    result
+}
+
+// (page, name) of the mention without brackets, e.g. ("id1234", "Ivan") of id1234|Ivan
+fn parse_mention(mention: &str) -> Option<(&str, &str)> {
+    let (page, name) = mention.split_at(mention.find('|')?);
+    let id = ["id", "club", "public", "event"]
+        .iter()
+        .find_map(|prefix| page.strip_prefix(prefix))?;
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) || name.len() < 2 {
+        return None;
+    }
+    Some((page, &name[1..]))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
+    fn test_timestamp_from_local() {
+        let timestamp = timestamp_from_local(" 2021-01-31 18:30 ").unwrap();
+        assert_eq!(
+            local_from_timestamp(timestamp)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            "2021-01-31 18:30"
+        );
+        assert_eq!(timestamp_from_local("2021-01-31"), None);
+        assert_eq!(timestamp_from_local("tomorrow"), None);
+    }
+    #[test]
     fn test_markup_escape_text() {
         assert_eq!(
             glib::markup_escape_text("Justerini & Brooks")
@@ -80,6 +142,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_markup_mentions() {
+        assert_eq!(
+            markup_mentions("[id1234|Ivan], agree"),
+            r#"<a href="https://vk.com/id1234">Ivan</a>, agree"#
+        );
+        assert_eq!(
+            markup_mentions("thanks [club40316705|RT] and [public1|Public]"),
+            r#"thanks <a href="https://vk.com/club40316705">RT</a> and <a href="https://vk.com/public1">Public</a>"#
+        );
+        // not mentions
+        assert_eq!(
+            markup_mentions("[1] [idea|x] [id12|] [a [id1|b]"),
+            r#"[1] [idea|x] [id12|] [a <a href="https://vk.com/id1">b</a>"#
+        );
+        assert_eq!(
+            process_text("[id1|A&B] see https://vk.com"),
+            r#"<a href="https://vk.com/id1">A&amp;B</a> see <a href="https://vk.com">https://vk.com</a>"#
+        );
+    }
+
     #[test]
     fn test_link_formatting() {
         let src_url = "https://habr.com/ru/post/538874/?utm_campaign=538874&utm_source=habrahabr&utm_medium=rss";
@@ -2,3 +2,13 @@ mod news_item_row_data;
 pub use news_item_row_data::NewsItemVM; // view model
 mod news_source_row_data;
 pub use news_source_row_data::NewsSourceVM; // view model
+mod conversation_row_data;
+pub use conversation_row_data::ConversationVM; // view model
+mod message_row_data;
+pub use message_row_data::MessageVM; // view model
+mod friend_row_data;
+pub use friend_row_data::FriendVM; // view model
+mod community_row_data;
+pub use community_row_data::CommunityVM; // view model
+mod comment_row_data;
+pub use comment_row_data::CommentVM; // view model
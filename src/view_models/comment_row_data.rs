@@ -0,0 +1,423 @@
+// Our GObject subclass for carrying a comment of the post for the comment_list ListBox model
+//
+// (!) Store any property in a RefCell to allow for interior mutability
+// Properties are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+use crate::models::CommentModel;
+use gio::prelude::*;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use glib::translate::*;
+use glib::ParamSpec as Param;
+
+const FLAGS: glib::ParamFlags = glib::ParamFlags::READWRITE;
+
+// photos of the attachments to display
+const MAX_IMAGES: usize = 4;
+
+// Implementation sub-module of the GObject
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    // The actual data structure that stores our values. This is not accessible
+    // directly from the outside.
+    pub struct RowData {
+        // comment id
+        id: RefCell<i64>,
+        // the author, positive for users, negative for groups
+        fromid: RefCell<i64>,
+        // the top-level comment of the reply, zero for top-level comments
+        threadid: RefCell<i64>,
+        // replies to the top-level comment
+        threadcount: RefCell<i64>,
+        // author name
+        author: RefCell<Option<String>>,
+        // author image / portrait
+        avatar: RefCell<Option<String>>,
+        // date and time
+        datetime: RefCell<Option<String>>,
+        // text
+        text: RefCell<Option<String>>,
+        // the author replied to
+        replyto: RefCell<Option<String>>,
+        // photos of the attachments
+        image0: RefCell<Option<String>>,
+        image0vis: RefCell<bool>,
+        image1: RefCell<Option<String>>,
+        image1vis: RefCell<bool>,
+        image2: RefCell<Option<String>>,
+        image2vis: RefCell<bool>,
+        image3: RefCell<Option<String>>,
+        image3vis: RefCell<bool>,
+        // replies of the thread left to show
+        more: RefCell<Option<String>>,
+        morevis: RefCell<bool>,
+    }
+
+    // GObject property definitions for our values
+    static PROPERTIES: [subclass::Property; 19] = [
+        subclass::Property("id", |val| {
+            Param::int64(val, "Id", "Id", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("fromid", |val| {
+            Param::int64(val, "Fromid", "Fromid", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("threadid", |val| {
+            Param::int64(val, "Threadid", "Threadid", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("threadcount", |val| {
+            Param::int64(
+                val,
+                "Threadcount",
+                "Threadcount",
+                i64::MIN,
+                i64::MAX,
+                0,
+                FLAGS,
+            )
+        }),
+        subclass::Property("author", |val| {
+            Param::string(val, "Author", "Author", None, FLAGS)
+        }),
+        subclass::Property("avatar", |val| {
+            Param::string(val, "Avatar", "Avatar", None, FLAGS)
+        }),
+        subclass::Property("datetime", |val| {
+            Param::string(val, "Datetime", "Datetime", None, FLAGS)
+        }),
+        subclass::Property("text", |val| {
+            Param::string(val, "Text", "Text", None, FLAGS)
+        }),
+        subclass::Property("replyto", |val| {
+            Param::string(val, "Replyto", "Replyto", None, FLAGS)
+        }),
+        subclass::Property("image0", |val| {
+            Param::string(val, "Image0", "Image0", None, FLAGS)
+        }),
+        subclass::Property("image0vis", |val| {
+            Param::boolean(val, "Image0vis", "Image0vis", false, FLAGS)
+        }),
+        subclass::Property("image1", |val| {
+            Param::string(val, "Image1", "Image1", None, FLAGS)
+        }),
+        subclass::Property("image1vis", |val| {
+            Param::boolean(val, "Image1vis", "Image1vis", false, FLAGS)
+        }),
+        subclass::Property("image2", |val| {
+            Param::string(val, "Image2", "Image2", None, FLAGS)
+        }),
+        subclass::Property("image2vis", |val| {
+            Param::boolean(val, "Image2vis", "Image2vis", false, FLAGS)
+        }),
+        subclass::Property("image3", |val| {
+            Param::string(val, "Image3", "Image3", None, FLAGS)
+        }),
+        subclass::Property("image3vis", |val| {
+            Param::boolean(val, "Image3vis", "Image3vis", false, FLAGS)
+        }),
+        subclass::Property("more", |val| {
+            Param::string(val, "More", "More", None, FLAGS)
+        }),
+        subclass::Property("morevis", |val| {
+            Param::boolean(val, "Morevis", "Morevis", false, FLAGS)
+        }),
+    ];
+
+    // Basic declaration of our type for the GObject type system
+    impl ObjectSubclass for RowData {
+        const NAME: &'static str = "CommentVM";
+        type ParentType = glib::Object;
+        type Instance = subclass::simple::InstanceStruct<Self>;
+        type Class = subclass::simple::ClassStruct<Self>;
+
+        glib_object_subclass!();
+
+        // Called exactly once before the first instantiation of an instance. This
+        // sets up any type-specific things, in this specific case it installs the
+        // properties so that GObject knows about their existence and they can be
+        // used on instances of our type
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_properties(&PROPERTIES);
+        }
+
+        // Called once at the very beginning of instantiation of each instance and
+        // creates the data structure that contains all our state
+        fn new() -> Self {
+            Self {
+                id: RefCell::new(0),
+                fromid: RefCell::new(0),
+                threadid: RefCell::new(0),
+                threadcount: RefCell::new(0),
+                author: RefCell::new(None),
+                avatar: RefCell::new(None),
+                datetime: RefCell::new(None),
+                text: RefCell::new(None),
+                replyto: RefCell::new(None),
+                image0: RefCell::new(None),
+                image0vis: RefCell::new(false),
+                image1: RefCell::new(None),
+                image1vis: RefCell::new(false),
+                image2: RefCell::new(None),
+                image2vis: RefCell::new(false),
+                image3: RefCell::new(None),
+                image3vis: RefCell::new(false),
+                more: RefCell::new(None),
+                morevis: RefCell::new(false),
+            }
+        }
+    }
+
+    // The ObjectImpl trait provides the setters/getters for GObject properties.
+    // Here we need to provide the values that are internally stored back to the
+    // caller, or store whatever new value the caller is providing.
+    //
+    // This maps between the GObject properties and our internal storage of the
+    // corresponding values of the properties.
+    impl ObjectImpl for RowData {
+        glib_object_impl!();
+
+        fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => {
+                    self.id
+                        .replace(value.get().expect("id set_property").unwrap_or(0));
+                }
+                subclass::Property("fromid", ..) => {
+                    self.fromid
+                        .replace(value.get().expect("fromid set_property").unwrap_or(0));
+                }
+                subclass::Property("threadid", ..) => {
+                    self.threadid
+                        .replace(value.get().expect("threadid set_property").unwrap_or(0));
+                }
+                subclass::Property("threadcount", ..) => {
+                    self.threadcount
+                        .replace(value.get().expect("threadcount set_property").unwrap_or(0));
+                }
+                subclass::Property("author", ..) => {
+                    self.author
+                        .replace(value.get().expect("author set_property"));
+                }
+                subclass::Property("avatar", ..) => {
+                    self.avatar
+                        .replace(value.get().expect("avatar set_property"));
+                }
+                subclass::Property("datetime", ..) => {
+                    self.datetime
+                        .replace(value.get().expect("datetime set_property"));
+                }
+                subclass::Property("text", ..) => {
+                    self.text.replace(value.get().expect("text set_property"));
+                }
+                subclass::Property("replyto", ..) => {
+                    self.replyto
+                        .replace(value.get().expect("replyto set_property"));
+                }
+                subclass::Property("image0", ..) => {
+                    self.image0
+                        .replace(value.get().expect("image0 set_property"));
+                }
+                subclass::Property("image0vis", ..) => {
+                    self.image0vis.replace(
+                        value
+                            .get()
+                            .expect("image0vis set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("image1", ..) => {
+                    self.image1
+                        .replace(value.get().expect("image1 set_property"));
+                }
+                subclass::Property("image1vis", ..) => {
+                    self.image1vis.replace(
+                        value
+                            .get()
+                            .expect("image1vis set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("image2", ..) => {
+                    self.image2
+                        .replace(value.get().expect("image2 set_property"));
+                }
+                subclass::Property("image2vis", ..) => {
+                    self.image2vis.replace(
+                        value
+                            .get()
+                            .expect("image2vis set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("image3", ..) => {
+                    self.image3
+                        .replace(value.get().expect("image3 set_property"));
+                }
+                subclass::Property("image3vis", ..) => {
+                    self.image3vis.replace(
+                        value
+                            .get()
+                            .expect("image3vis set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("more", ..) => {
+                    self.more.replace(value.get().expect("more set_property"));
+                }
+                subclass::Property("morevis", ..) => {
+                    self.morevis
+                        .replace(value.get().expect("morevis set_property").unwrap_or(false));
+                }
+                //
+                _ => unimplemented!(),
+            }
+        }
+
+        fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => Ok(self.id.borrow().to_value()),
+                subclass::Property("fromid", ..) => Ok(self.fromid.borrow().to_value()),
+                subclass::Property("threadid", ..) => Ok(self.threadid.borrow().to_value()),
+                subclass::Property("threadcount", ..) => Ok(self.threadcount.borrow().to_value()),
+                subclass::Property("author", ..) => Ok(self.author.borrow().to_value()),
+                subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
+                subclass::Property("datetime", ..) => Ok(self.datetime.borrow().to_value()),
+                subclass::Property("text", ..) => Ok(self.text.borrow().to_value()),
+                subclass::Property("replyto", ..) => Ok(self.replyto.borrow().to_value()),
+                subclass::Property("image0", ..) => Ok(self.image0.borrow().to_value()),
+                subclass::Property("image0vis", ..) => Ok(self.image0vis.borrow().to_value()),
+                subclass::Property("image1", ..) => Ok(self.image1.borrow().to_value()),
+                subclass::Property("image1vis", ..) => Ok(self.image1vis.borrow().to_value()),
+                subclass::Property("image2", ..) => Ok(self.image2.borrow().to_value()),
+                subclass::Property("image2vis", ..) => Ok(self.image2vis.borrow().to_value()),
+                subclass::Property("image3", ..) => Ok(self.image3.borrow().to_value()),
+                subclass::Property("image3vis", ..) => Ok(self.image3vis.borrow().to_value()),
+                subclass::Property("more", ..) => Ok(self.more.borrow().to_value()),
+                subclass::Property("morevis", ..) => Ok(self.morevis.borrow().to_value()),
+                //
+                _ => unimplemented!(),
+            }
+        }
+    }
+}
+
+// Public part of the CommentVM type. This behaves like a normal gtk-rs-style GObject
+// binding
+glib_wrapper! {
+    pub struct CommentVM(
+        Object<subclass::simple::InstanceStruct<imp::RowData>,
+        subclass::simple::ClassStruct<imp::RowData>, CommentVMClass>
+    );
+
+    match fn {
+        get_type => || imp::RowData::get_type().to_glib(),
+    }
+}
+
+// Constructor for new instances. This simply calls glib::Object::new() with
+// initial values for our properties and then returns the new instance
+impl CommentVM {
+    pub fn new(model: &CommentModel) -> CommentVM {
+        let mut image_file: [String; MAX_IMAGES] = Default::default();
+        let mut image_vis: [bool; MAX_IMAGES] = [false; MAX_IMAGES];
+        if let Some(photos) = &model.photos {
+            for (i, photo) in photos.iter().take(MAX_IMAGES).enumerate() {
+                image_file[i] = photo.uri.clone();
+                image_vis[i] = !photo.uri.is_empty();
+            }
+        }
+        let replyto = if model.reply_to.is_empty() {
+            String::new()
+        } else {
+            format!("to {}", model.reply_to)
+        };
+        glib::Object::new(
+            Self::static_type(),
+            &[
+                ("id", &model.id),
+                ("fromid", &model.from_id),
+                ("threadid", &model.thread_id),
+                ("threadcount", &model.thread_count),
+                ("author", &model.author),
+                ("avatar", &model.avatar),
+                ("datetime", &model.datetime),
+                ("text", &model.text),
+                ("replyto", &replyto),
+                ("image0", &image_file[0]),
+                ("image0vis", &image_vis[0]),
+                ("image1", &image_file[1]),
+                ("image1vis", &image_vis[1]),
+                ("image2", &image_file[2]),
+                ("image2vis", &image_vis[2]),
+                ("image3", &image_file[3]),
+                ("image3vis", &image_vis[3]),
+            ],
+        )
+        .expect("Failed to create row data")
+        .downcast()
+        .expect("Created row data is of wrong type")
+    }
+
+    /// Returns the comment id
+    pub fn get_id(&self) -> i64 {
+        self.get_number("id")
+    }
+
+    /// Returns the author, positive for users, negative for groups
+    pub fn get_from_id(&self) -> i64 {
+        self.get_number("fromid")
+    }
+
+    /// Returns name of the author
+    pub fn get_author(&self) -> String {
+        self.get_property("author")
+            .ok()
+            .and_then(|value| value.get::<String>().ok().flatten())
+            .unwrap_or_default()
+    }
+
+    /// Returns the top-level comment the reply belongs to, zero for top-level comments
+    pub fn get_thread_id(&self) -> i64 {
+        self.get_number("threadid")
+    }
+
+    /// Returns a number of replies to the top-level comment
+    pub fn get_thread_count(&self) -> i64 {
+        self.get_number("threadcount")
+    }
+
+    /// Counts the reply user has just sent to the top-level comment
+    pub fn add_reply(&self) {
+        let count = self.get_thread_count() + 1;
+        if let Err(e) = self.set_property("threadcount", &count) {
+            log::error!("failed updating comment replies: {}", e);
+        }
+    }
+
+    /// Offers to show the replies of the thread which are not displayed yet
+    pub fn set_replies_left(&self, left: i64) {
+        let more = if left > 0 {
+            format!("show {} more replies", left)
+        } else {
+            String::new()
+        };
+        if let Err(e) = self
+            .set_property("more", &more)
+            .and_then(|_| self.set_property("morevis", &(left > 0)))
+        {
+            log::error!("failed updating comment replies: {}", e);
+        }
+    }
+
+    fn get_number(&self, name: &str) -> i64 {
+        self.get_property(name)
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,246 @@
+// Our GObject subclass for carrying a community with its membership and muting for the communities_list ListBox model
+//
+// (!) Store any property in a RefCell to allow for interior mutability
+// Properties are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+use crate::models::CommunityModel;
+use gio::prelude::*;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use glib::translate::*;
+use glib::ParamSpec as Param;
+
+const FLAGS: glib::ParamFlags = glib::ParamFlags::READWRITE;
+
+// Implementation sub-module of the GObject
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    // The actual data structure that stores our values. This is not accessible
+    // directly from the outside.
+    pub struct RowData {
+        // positive id of the community
+        id: RefCell<i64>,
+        name: RefCell<Option<String>>,
+        // community image
+        avatar: RefCell<Option<String>>,
+        // kind and count of members
+        info: RefCell<Option<String>>,
+        description: RefCell<Option<String>>,
+        // url to the community page
+        uri: RefCell<Option<String>>,
+        // user is subscribed to the community
+        member: RefCell<bool>,
+        // posts of the community are not shown in news
+        muted: RefCell<bool>,
+    }
+
+    // GObject property definitions for our values
+    static PROPERTIES: [subclass::Property; 8] = [
+        subclass::Property("id", |val| {
+            Param::int64(val, "Id", "Id", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("name", |val| {
+            Param::string(val, "Name", "Name", None, FLAGS)
+        }),
+        subclass::Property("avatar", |val| {
+            Param::string(val, "Avatar", "Avatar", None, FLAGS)
+        }),
+        subclass::Property("info", |val| {
+            Param::string(val, "Info", "Info", None, FLAGS)
+        }),
+        subclass::Property("description", |val| {
+            Param::string(val, "Description", "Description", None, FLAGS)
+        }),
+        subclass::Property("uri", |val| Param::string(val, "Uri", "Uri", None, FLAGS)),
+        subclass::Property("member", |val| {
+            Param::boolean(val, "Member", "Member", false, FLAGS)
+        }),
+        subclass::Property("muted", |val| {
+            Param::boolean(val, "Muted", "Muted", false, FLAGS)
+        }),
+    ];
+
+    // Basic declaration of our type for the GObject type system
+    impl ObjectSubclass for RowData {
+        const NAME: &'static str = "CommunityVM";
+        type ParentType = glib::Object;
+        type Instance = subclass::simple::InstanceStruct<Self>;
+        type Class = subclass::simple::ClassStruct<Self>;
+
+        glib_object_subclass!();
+
+        // Called exactly once before the first instantiation of an instance. This
+        // sets up any type-specific things, in this specific case it installs the
+        // properties so that GObject knows about their existence and they can be
+        // used on instances of our type
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_properties(&PROPERTIES);
+        }
+
+        // Called once at the very beginning of instantiation of each instance and
+        // creates the data structure that contains all our state
+        fn new() -> Self {
+            Self {
+                id: RefCell::new(0),
+                name: RefCell::new(None),
+                avatar: RefCell::new(None),
+                info: RefCell::new(None),
+                description: RefCell::new(None),
+                uri: RefCell::new(None),
+                member: RefCell::new(false),
+                muted: RefCell::new(false),
+            }
+        }
+    }
+
+    // The ObjectImpl trait provides the setters/getters for GObject properties.
+    // Here we need to provide the values that are internally stored back to the
+    // caller, or store whatever new value the caller is providing.
+    //
+    // This maps between the GObject properties and our internal storage of the
+    // corresponding values of the properties.
+    impl ObjectImpl for RowData {
+        glib_object_impl!();
+
+        fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => {
+                    self.id
+                        .replace(value.get().expect("id set_property").unwrap_or(0));
+                }
+                subclass::Property("name", ..) => {
+                    self.name.replace(value.get().expect("name set_property"));
+                }
+                subclass::Property("avatar", ..) => {
+                    self.avatar
+                        .replace(value.get().expect("avatar set_property"));
+                }
+                subclass::Property("info", ..) => {
+                    self.info.replace(value.get().expect("info set_property"));
+                }
+                subclass::Property("description", ..) => {
+                    self.description
+                        .replace(value.get().expect("description set_property"));
+                }
+                subclass::Property("uri", ..) => {
+                    self.uri.replace(value.get().expect("uri set_property"));
+                }
+                subclass::Property("member", ..) => {
+                    self.member
+                        .replace(value.get().expect("member set_property").unwrap_or(false));
+                }
+                subclass::Property("muted", ..) => {
+                    self.muted
+                        .replace(value.get().expect("muted set_property").unwrap_or(false));
+                }
+                //
+                _ => unimplemented!(),
+            }
+        }
+
+        fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => Ok(self.id.borrow().to_value()),
+                subclass::Property("name", ..) => Ok(self.name.borrow().to_value()),
+                subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
+                subclass::Property("info", ..) => Ok(self.info.borrow().to_value()),
+                subclass::Property("description", ..) => Ok(self.description.borrow().to_value()),
+                subclass::Property("uri", ..) => Ok(self.uri.borrow().to_value()),
+                subclass::Property("member", ..) => Ok(self.member.borrow().to_value()),
+                subclass::Property("muted", ..) => Ok(self.muted.borrow().to_value()),
+                //
+                _ => unimplemented!(),
+            }
+        }
+    }
+}
+
+// Public part of the CommunityVM type. This behaves like a normal gtk-rs-style GObject
+// binding
+glib_wrapper! {
+    pub struct CommunityVM(
+        Object<subclass::simple::InstanceStruct<imp::RowData>,
+        subclass::simple::ClassStruct<imp::RowData>, CommunityVMClass>
+    );
+
+    match fn {
+        get_type => || imp::RowData::get_type().to_glib(),
+    }
+}
+
+// Constructor for new instances. This simply calls glib::Object::new() with
+// initial values for our properties and then returns the new instance
+impl CommunityVM {
+    pub fn new(model: &CommunityModel) -> CommunityVM {
+        let info = if model.members.is_empty() {
+            model.kind.clone()
+        } else {
+            format!("{}, {}", model.kind, model.members)
+        };
+        glib::Object::new(
+            Self::static_type(),
+            &[
+                ("id", &model.id),
+                ("name", &model.name),
+                ("avatar", &model.avatar),
+                ("info", &info),
+                ("description", &model.description),
+                ("uri", &model.uri),
+                ("member", &model.is_member),
+                ("muted", &model.muted),
+            ],
+        )
+        .expect("Failed to create row data")
+        .downcast()
+        .expect("Created row data is of wrong type")
+    }
+
+    /// Returns positive id of the community
+    pub fn get_id(&self) -> i64 {
+        self.get_property("id")
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0)
+    }
+
+    /// Returns url to the community page
+    pub fn get_uri(&self) -> String {
+        self.get_property("uri")
+            .ok()
+            .and_then(|value| value.get::<String>().ok().flatten())
+            .unwrap_or_default()
+    }
+
+    pub fn is_member(&self) -> bool {
+        self.get_flag("member")
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.get_flag("muted")
+    }
+
+    pub fn set_member(&self, member: bool) {
+        if let Err(e) = self.set_property("member", &member) {
+            log::error!("failed updating community property member: {}", e);
+        }
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        if let Err(e) = self.set_property("muted", &muted) {
+            log::error!("failed updating community property muted: {}", e);
+        }
+    }
+
+    fn get_flag(&self, name: &str) -> bool {
+        self.get_property(name)
+            .ok()
+            .and_then(|value| value.get::<bool>().ok().flatten())
+            .unwrap_or(false)
+    }
+}
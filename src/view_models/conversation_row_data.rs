@@ -0,0 +1,243 @@
+// Our GObject subclass for carrying a title, avatar and the last message of a conversation for the conversation_list ListBox model
+//
+// (!) Store any property in a RefCell to allow for interior mutability
+// Properties are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+use crate::models::{ConversationModel, MessageModel};
+use gio::prelude::*;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use glib::translate::*;
+use glib::ParamSpec as Param;
+
+const FLAGS: glib::ParamFlags = glib::ParamFlags::READWRITE;
+
+// Implementation sub-module of the GObject
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    // The actual data structure that stores our values. This is not accessible
+    // directly from the outside.
+    pub struct RowData {
+        // user id, -group id or 2000000000 + chat id
+        peer: RefCell<i64>,
+        // chat title, user or group name
+        title: RefCell<Option<String>>,
+        // chat, user or group image
+        avatar: RefCell<Option<String>>,
+        // text of the last message
+        lastmessage: RefCell<Option<String>>,
+        // date and time of the last message
+        datetime: RefCell<Option<String>>,
+        // a number of unread messages, empty if there are none
+        unread: RefCell<Option<String>>,
+        // the user is online
+        online: RefCell<bool>,
+    }
+
+    // GObject property definitions for our values
+    static PROPERTIES: [subclass::Property; 7] = [
+        subclass::Property("peer", |val| {
+            Param::int64(val, "Peer", "Peer", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("title", |val| {
+            Param::string(val, "Title", "Title", None, FLAGS)
+        }),
+        subclass::Property("avatar", |val| {
+            Param::string(val, "Avatar", "Avatar", None, FLAGS)
+        }),
+        subclass::Property("lastmessage", |val| {
+            Param::string(val, "LastMessage", "LastMessage", None, FLAGS)
+        }),
+        subclass::Property("datetime", |val| {
+            Param::string(val, "Datetime", "Datetime", None, FLAGS)
+        }),
+        subclass::Property("unread", |val| {
+            Param::string(val, "Unread", "Unread", None, FLAGS)
+        }),
+        subclass::Property("online", |val| {
+            Param::boolean(val, "Online", "Online", false, FLAGS)
+        }),
+    ];
+
+    // Basic declaration of our type for the GObject type system
+    impl ObjectSubclass for RowData {
+        const NAME: &'static str = "ConversationVM";
+        type ParentType = glib::Object;
+        type Instance = subclass::simple::InstanceStruct<Self>;
+        type Class = subclass::simple::ClassStruct<Self>;
+
+        glib_object_subclass!();
+
+        // Called exactly once before the first instantiation of an instance. This
+        // sets up any type-specific things, in this specific case it installs the
+        // properties so that GObject knows about their existence and they can be
+        // used on instances of our type
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_properties(&PROPERTIES);
+        }
+
+        // Called once at the very beginning of instantiation of each instance and
+        // creates the data structure that contains all our state
+        fn new() -> Self {
+            Self {
+                peer: RefCell::new(0),
+                title: RefCell::new(None),
+                avatar: RefCell::new(None),
+                lastmessage: RefCell::new(None),
+                datetime: RefCell::new(None),
+                unread: RefCell::new(None),
+                online: RefCell::new(false),
+            }
+        }
+    }
+
+    // The ObjectImpl trait provides the setters/getters for GObject properties.
+    // Here we need to provide the values that are internally stored back to the
+    // caller, or store whatever new value the caller is providing.
+    //
+    // This maps between the GObject properties and our internal storage of the
+    // corresponding values of the properties.
+    impl ObjectImpl for RowData {
+        glib_object_impl!();
+
+        fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("peer", ..) => {
+                    self.peer
+                        .replace(value.get().expect("peer set_property").unwrap_or(0));
+                }
+                subclass::Property("title", ..) => {
+                    self.title.replace(value.get().expect("title set_property"));
+                }
+                subclass::Property("avatar", ..) => {
+                    self.avatar
+                        .replace(value.get().expect("avatar set_property"));
+                }
+                subclass::Property("lastmessage", ..) => {
+                    self.lastmessage
+                        .replace(value.get().expect("lastmessage set_property"));
+                }
+                subclass::Property("datetime", ..) => {
+                    self.datetime
+                        .replace(value.get().expect("datetime set_property"));
+                }
+                subclass::Property("unread", ..) => {
+                    self.unread
+                        .replace(value.get().expect("unread set_property"));
+                }
+                subclass::Property("online", ..) => {
+                    self.online
+                        .replace(value.get().expect("online set_property").unwrap_or(false));
+                }
+                //
+                _ => unimplemented!(),
+            }
+        }
+
+        fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("peer", ..) => Ok(self.peer.borrow().to_value()),
+                subclass::Property("title", ..) => Ok(self.title.borrow().to_value()),
+                subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
+                subclass::Property("lastmessage", ..) => Ok(self.lastmessage.borrow().to_value()),
+                subclass::Property("datetime", ..) => Ok(self.datetime.borrow().to_value()),
+                subclass::Property("unread", ..) => Ok(self.unread.borrow().to_value()),
+                subclass::Property("online", ..) => Ok(self.online.borrow().to_value()),
+                //
+                _ => unimplemented!(),
+            }
+        }
+    }
+}
+
+// Public part of the ConversationVM type. This behaves like a normal gtk-rs-style GObject
+// binding
+glib_wrapper! {
+    pub struct ConversationVM(
+        Object<subclass::simple::InstanceStruct<imp::RowData>,
+        subclass::simple::ClassStruct<imp::RowData>, ConversationVMClass>
+    );
+
+    match fn {
+        get_type => || imp::RowData::get_type().to_glib(),
+    }
+}
+
+// Constructor for new instances. This simply calls glib::Object::new() with
+// initial values for our properties and then returns the new instance
+impl ConversationVM {
+    pub fn new(model: &ConversationModel) -> ConversationVM {
+        let unread = if model.unread > 0 {
+            format!("{}", model.unread)
+        } else {
+            String::new()
+        };
+        glib::Object::new(
+            Self::static_type(),
+            &[
+                ("peer", &model.peer_id),
+                ("title", &model.title),
+                ("avatar", &model.avatar),
+                ("lastmessage", &model.last_message),
+                ("datetime", &model.datetime),
+                ("unread", &unread),
+            ],
+        )
+        .expect("Failed to create row data")
+        .downcast()
+        .expect("Created row data is of wrong type")
+    }
+
+    /// Shows the message as the last one, incoming messages are counted as unread
+    pub fn update_last(&self, message: &MessageModel, count_unread: bool) {
+        for (name, value) in &[
+            ("lastmessage", &message.text),
+            ("datetime", &message.datetime),
+        ] {
+            if let Err(e) = self.set_property(*name, *value) {
+                log::error!("failed updating conversation property {}: {}", name, e);
+            }
+        }
+        if count_unread {
+            let unread = self
+                .get_property("unread")
+                .ok()
+                .and_then(|value| value.get::<String>().ok().flatten())
+                .and_then(|unread| unread.parse::<i64>().ok())
+                .unwrap_or(0);
+            self.set_unread(unread + 1);
+        }
+    }
+
+    /// Sets a number of unread messages, nothing is shown if there are none
+    pub fn set_unread(&self, unread: i64) {
+        let unread = if unread > 0 {
+            format!("{}", unread)
+        } else {
+            String::new()
+        };
+        if let Err(e) = self.set_property("unread", &unread) {
+            log::error!("failed updating conversation property unread: {}", e);
+        }
+    }
+
+    pub fn set_online(&self, online: bool) {
+        if let Err(e) = self.set_property("online", &online) {
+            log::error!("failed updating conversation property online: {}", e);
+        }
+    }
+
+    /// Returns the conversation peer_id
+    pub fn get_peer_id(&self) -> i64 {
+        self.get_property("peer")
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0)
+    }
+}
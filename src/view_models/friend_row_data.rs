@@ -0,0 +1,172 @@
+// Our GObject subclass for carrying a name, avatar and online status of a friend for the friends_list ListBox model
+//
+// (!) Store any property in a RefCell to allow for interior mutability
+// Properties are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+use crate::models::FriendModel;
+use gio::prelude::*;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use glib::translate::*;
+use glib::ParamSpec as Param;
+
+const FLAGS: glib::ParamFlags = glib::ParamFlags::READWRITE;
+
+// Implementation sub-module of the GObject
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    // The actual data structure that stores our values. This is not accessible
+    // directly from the outside.
+    pub struct RowData {
+        // first and last name
+        name: RefCell<Option<String>>,
+        // user image
+        avatar: RefCell<Option<String>>,
+        // online or when the friend was last seen
+        status: RefCell<Option<String>>,
+        // names of the friend lists the friend is in
+        lists: RefCell<Option<String>>,
+        // url to the friend's page
+        uri: RefCell<Option<String>>,
+    }
+
+    // GObject property definitions for our values
+    static PROPERTIES: [subclass::Property; 5] = [
+        subclass::Property("name", |val| {
+            Param::string(val, "Name", "Name", None, FLAGS)
+        }),
+        subclass::Property("avatar", |val| {
+            Param::string(val, "Avatar", "Avatar", None, FLAGS)
+        }),
+        subclass::Property("status", |val| {
+            Param::string(val, "Status", "Status", None, FLAGS)
+        }),
+        subclass::Property("lists", |val| {
+            Param::string(val, "Lists", "Lists", None, FLAGS)
+        }),
+        subclass::Property("uri", |val| Param::string(val, "Uri", "Uri", None, FLAGS)),
+    ];
+
+    // Basic declaration of our type for the GObject type system
+    impl ObjectSubclass for RowData {
+        const NAME: &'static str = "FriendVM";
+        type ParentType = glib::Object;
+        type Instance = subclass::simple::InstanceStruct<Self>;
+        type Class = subclass::simple::ClassStruct<Self>;
+
+        glib_object_subclass!();
+
+        // Called exactly once before the first instantiation of an instance. This
+        // sets up any type-specific things, in this specific case it installs the
+        // properties so that GObject knows about their existence and they can be
+        // used on instances of our type
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_properties(&PROPERTIES);
+        }
+
+        // Called once at the very beginning of instantiation of each instance and
+        // creates the data structure that contains all our state
+        fn new() -> Self {
+            Self {
+                name: RefCell::new(None),
+                avatar: RefCell::new(None),
+                status: RefCell::new(None),
+                lists: RefCell::new(None),
+                uri: RefCell::new(None),
+            }
+        }
+    }
+
+    // The ObjectImpl trait provides the setters/getters for GObject properties.
+    // Here we need to provide the values that are internally stored back to the
+    // caller, or store whatever new value the caller is providing.
+    //
+    // This maps between the GObject properties and our internal storage of the
+    // corresponding values of the properties.
+    impl ObjectImpl for RowData {
+        glib_object_impl!();
+
+        fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("name", ..) => {
+                    self.name.replace(value.get().expect("name set_property"));
+                }
+                subclass::Property("avatar", ..) => {
+                    self.avatar
+                        .replace(value.get().expect("avatar set_property"));
+                }
+                subclass::Property("status", ..) => {
+                    self.status
+                        .replace(value.get().expect("status set_property"));
+                }
+                subclass::Property("lists", ..) => {
+                    self.lists.replace(value.get().expect("lists set_property"));
+                }
+                subclass::Property("uri", ..) => {
+                    self.uri.replace(value.get().expect("uri set_property"));
+                }
+                //
+                _ => unimplemented!(),
+            }
+        }
+
+        fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("name", ..) => Ok(self.name.borrow().to_value()),
+                subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
+                subclass::Property("status", ..) => Ok(self.status.borrow().to_value()),
+                subclass::Property("lists", ..) => Ok(self.lists.borrow().to_value()),
+                subclass::Property("uri", ..) => Ok(self.uri.borrow().to_value()),
+                //
+                _ => unimplemented!(),
+            }
+        }
+    }
+}
+
+// Public part of the FriendVM type. This behaves like a normal gtk-rs-style GObject
+// binding
+glib_wrapper! {
+    pub struct FriendVM(
+        Object<subclass::simple::InstanceStruct<imp::RowData>,
+        subclass::simple::ClassStruct<imp::RowData>, FriendVMClass>
+    );
+
+    match fn {
+        get_type => || imp::RowData::get_type().to_glib(),
+    }
+}
+
+// Constructor for new instances. This simply calls glib::Object::new() with
+// initial values for our properties and then returns the new instance
+impl FriendVM {
+    pub fn new(model: &FriendModel) -> FriendVM {
+        glib::Object::new(
+            Self::static_type(),
+            &[
+                ("name", &model.name),
+                ("avatar", &model.avatar),
+                ("status", &model.status()),
+                ("lists", &model.lists.join(", ")),
+                ("uri", &model.uri),
+            ],
+        )
+        .expect("Failed to create row data")
+        .downcast()
+        .expect("Created row data is of wrong type")
+    }
+
+    /// Returns url to the friend's page
+    pub fn get_uri(&self) -> String {
+        self.get_property("uri")
+            .ok()
+            .and_then(|value| value.get::<String>().ok().flatten())
+            .unwrap_or_default()
+    }
+}
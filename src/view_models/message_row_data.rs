@@ -0,0 +1,215 @@
+// Our GObject subclass for carrying an author, text and photo of a chat message for the message_list ListBox model
+//
+// (!) Store any property in a RefCell to allow for interior mutability
+// Properties are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+use crate::models::MessageModel;
+use gio::prelude::*;
+use glib::subclass;
+use glib::subclass::prelude::*;
+use glib::translate::*;
+use glib::ParamSpec as Param;
+
+const FLAGS: glib::ParamFlags = glib::ParamFlags::READWRITE;
+
+// Implementation sub-module of the GObject
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    // The actual data structure that stores our values. This is not accessible
+    // directly from the outside.
+    pub struct RowData {
+        // message id
+        id: RefCell<i64>,
+        // author name
+        author: RefCell<Option<String>>,
+        // author image / portrait
+        avatar: RefCell<Option<String>>,
+        // date and time
+        datetime: RefCell<Option<String>>,
+        // text
+        text: RefCell<Option<String>>,
+        // the first photo of the attachments
+        image: RefCell<Option<String>>,
+        imagevis: RefCell<bool>,
+        // sent by the user
+        out: RefCell<bool>,
+    }
+
+    // GObject property definitions for our values
+    static PROPERTIES: [subclass::Property; 8] = [
+        subclass::Property("id", |val| {
+            Param::int64(val, "Id", "Id", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("author", |val| {
+            Param::string(val, "Author", "Author", None, FLAGS)
+        }),
+        subclass::Property("avatar", |val| {
+            Param::string(val, "Avatar", "Avatar", None, FLAGS)
+        }),
+        subclass::Property("datetime", |val| {
+            Param::string(val, "Datetime", "Datetime", None, FLAGS)
+        }),
+        subclass::Property("text", |val| {
+            Param::string(val, "Text", "Text", None, FLAGS)
+        }),
+        subclass::Property("image", |val| {
+            Param::string(val, "Image", "Image", None, FLAGS)
+        }),
+        subclass::Property("imagevis", |val| {
+            Param::boolean(val, "Imvis", "Imvis", false, FLAGS)
+        }),
+        subclass::Property("out", |val| Param::boolean(val, "Out", "Out", false, FLAGS)),
+    ];
+
+    // Basic declaration of our type for the GObject type system
+    impl ObjectSubclass for RowData {
+        const NAME: &'static str = "MessageVM";
+        type ParentType = glib::Object;
+        type Instance = subclass::simple::InstanceStruct<Self>;
+        type Class = subclass::simple::ClassStruct<Self>;
+
+        glib_object_subclass!();
+
+        // Called exactly once before the first instantiation of an instance. This
+        // sets up any type-specific things, in this specific case it installs the
+        // properties so that GObject knows about their existence and they can be
+        // used on instances of our type
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_properties(&PROPERTIES);
+        }
+
+        // Called once at the very beginning of instantiation of each instance and
+        // creates the data structure that contains all our state
+        fn new() -> Self {
+            Self {
+                id: RefCell::new(0),
+                author: RefCell::new(None),
+                avatar: RefCell::new(None),
+                datetime: RefCell::new(None),
+                text: RefCell::new(None),
+                image: RefCell::new(None),
+                imagevis: RefCell::new(false),
+                out: RefCell::new(false),
+            }
+        }
+    }
+
+    // The ObjectImpl trait provides the setters/getters for GObject properties.
+    // Here we need to provide the values that are internally stored back to the
+    // caller, or store whatever new value the caller is providing.
+    //
+    // This maps between the GObject properties and our internal storage of the
+    // corresponding values of the properties.
+    impl ObjectImpl for RowData {
+        glib_object_impl!();
+
+        fn set_property(&self, _obj: &glib::Object, id: usize, value: &glib::Value) {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => {
+                    self.id
+                        .replace(value.get().expect("id set_property").unwrap_or(0));
+                }
+                subclass::Property("author", ..) => {
+                    self.author
+                        .replace(value.get().expect("author set_property"));
+                }
+                subclass::Property("avatar", ..) => {
+                    self.avatar
+                        .replace(value.get().expect("avatar set_property"));
+                }
+                subclass::Property("datetime", ..) => {
+                    self.datetime
+                        .replace(value.get().expect("datetime set_property"));
+                }
+                subclass::Property("text", ..) => {
+                    self.text.replace(value.get().expect("text set_property"));
+                }
+                subclass::Property("image", ..) => {
+                    self.image.replace(value.get().expect("image set_property"));
+                }
+                subclass::Property("imagevis", ..) => {
+                    self.imagevis
+                        .replace(value.get().expect("imagevis set_property").unwrap_or(false));
+                }
+                subclass::Property("out", ..) => {
+                    self.out
+                        .replace(value.get().expect("out set_property").unwrap_or(false));
+                }
+                //
+                _ => unimplemented!(),
+            }
+        }
+
+        fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+            let prop = &PROPERTIES[id];
+
+            match *prop {
+                subclass::Property("id", ..) => Ok(self.id.borrow().to_value()),
+                subclass::Property("author", ..) => Ok(self.author.borrow().to_value()),
+                subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
+                subclass::Property("datetime", ..) => Ok(self.datetime.borrow().to_value()),
+                subclass::Property("text", ..) => Ok(self.text.borrow().to_value()),
+                subclass::Property("image", ..) => Ok(self.image.borrow().to_value()),
+                subclass::Property("imagevis", ..) => Ok(self.imagevis.borrow().to_value()),
+                subclass::Property("out", ..) => Ok(self.out.borrow().to_value()),
+                //
+                _ => unimplemented!(),
+            }
+        }
+    }
+}
+
+// Public part of the MessageVM type. This behaves like a normal gtk-rs-style GObject
+// binding
+glib_wrapper! {
+    pub struct MessageVM(
+        Object<subclass::simple::InstanceStruct<imp::RowData>,
+        subclass::simple::ClassStruct<imp::RowData>, MessageVMClass>
+    );
+
+    match fn {
+        get_type => || imp::RowData::get_type().to_glib(),
+    }
+}
+
+// Constructor for new instances. This simply calls glib::Object::new() with
+// initial values for our properties and then returns the new instance
+impl MessageVM {
+    pub fn new(model: &MessageModel) -> MessageVM {
+        let image = model
+            .photos
+            .as_ref()
+            .and_then(|photos| photos.first())
+            .map(|photo| photo.uri.clone())
+            .unwrap_or_default();
+        let imagevis = !image.is_empty();
+        glib::Object::new(
+            Self::static_type(),
+            &[
+                ("id", &model.id),
+                ("author", &model.author),
+                ("avatar", &model.avatar),
+                ("datetime", &model.datetime),
+                ("text", &model.text),
+                ("image", &image),
+                ("imagevis", &imagevis),
+                ("out", &model.out),
+            ],
+        )
+        .expect("Failed to create row data")
+        .downcast()
+        .expect("Created row data is of wrong type")
+    }
+
+    /// Returns the message id
+    pub fn get_id(&self) -> i64 {
+        self.get_property("id")
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0)
+    }
+}
@@ -3,7 +3,7 @@
 // (!) Store any property in a RefCell to allow for interior mutability
 // Properties are exposed via normal GObject properties. This allows us to use property
 // bindings below to bind the values with what widgets display in the UI
-use crate::models::NewsItemModel;
+use crate::models::{format_count, NewsItemId, NewsItemModel};
 use gio::prelude::*;
 use glib::subclass;
 use glib::subclass::prelude::*;
@@ -20,6 +20,12 @@ mod imp {
     // The actual data structure that stores our values. This is not accessible
     // directly from the outside.
     pub struct RowData {
+        // source_id of the item, negative for groups
+        source: RefCell<i64>,
+        // post_id, or time of the item if it is not a post
+        post: RefCell<i64>,
+        // id of the photo or friend the item is about if it is not a post
+        contentid: RefCell<i64>,
         // author name
         author: RefCell<Option<String>>,
         // author image / portrait
@@ -74,10 +80,29 @@ mod imp {
         link3txt: RefCell<Option<String>>,
         link3url: RefCell<Option<String>>,
         link3vis: RefCell<bool>,
+        // engagement counters shortened to display
+        likes: RefCell<Option<String>>,
+        comments: RefCell<Option<String>>,
+        reposts: RefCell<Option<String>>,
+        views: RefCell<Option<String>>,
+        // the item is a post, others have no counters
+        countersvis: RefCell<bool>,
+        // user likes the post, the count of its likes
+        liked: RefCell<bool>,
+        likecount: RefCell<i64>,
     }
 
     // GObject property definitions for our three values
-    static PROPERTIES: [subclass::Property; 47] = [
+    static PROPERTIES: [subclass::Property; 57] = [
+        subclass::Property("source", |val| {
+            Param::int64(val, "Source", "Source", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("post", |val| {
+            Param::int64(val, "Post", "Post", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
+        subclass::Property("contentid", |val| {
+            Param::int64(val, "ContentId", "ContentId", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
         subclass::Property("author", |val| {
             Param::string(val, "Author", "Author", None, FLAGS)
         }),
@@ -220,6 +245,27 @@ mod imp {
         subclass::Property("link3vis", |val| {
             Param::boolean(val, "L3vis", "L3vis", false, FLAGS)
         }),
+        subclass::Property("likes", |val| {
+            Param::string(val, "Likes", "Likes", None, FLAGS)
+        }),
+        subclass::Property("comments", |val| {
+            Param::string(val, "Comments", "Comments", None, FLAGS)
+        }),
+        subclass::Property("reposts", |val| {
+            Param::string(val, "Reposts", "Reposts", None, FLAGS)
+        }),
+        subclass::Property("views", |val| {
+            Param::string(val, "Views", "Views", None, FLAGS)
+        }),
+        subclass::Property("countersvis", |val| {
+            Param::boolean(val, "Countersvis", "Countersvis", false, FLAGS)
+        }),
+        subclass::Property("liked", |val| {
+            Param::boolean(val, "Liked", "Liked", false, FLAGS)
+        }),
+        subclass::Property("likecount", |val| {
+            Param::int64(val, "Likecount", "Likecount", 0, i64::MAX, 0, FLAGS)
+        }),
     ];
 
     // Basic declaration of our type for the GObject type system
@@ -243,6 +289,9 @@ mod imp {
         // creates the data structure that contains all our state
         fn new() -> Self {
             Self {
+                source: RefCell::new(0),
+                post: RefCell::new(0),
+                contentid: RefCell::new(0),
                 author: RefCell::new(None),
                 avatar: RefCell::new(None),
                 itemtype: RefCell::new(None),
@@ -292,6 +341,13 @@ mod imp {
                 link3txt: RefCell::new(None),
                 link3url: RefCell::new(None),
                 link3vis: RefCell::new(false),
+                likes: RefCell::new(None),
+                comments: RefCell::new(None),
+                reposts: RefCell::new(None),
+                views: RefCell::new(None),
+                countersvis: RefCell::new(false),
+                liked: RefCell::new(false),
+                likecount: RefCell::new(0),
             }
         }
     }
@@ -309,6 +365,18 @@ mod imp {
             let prop = &PROPERTIES[id];
 
             match *prop {
+                subclass::Property("source", ..) => {
+                    self.source
+                        .replace(value.get().expect("source set_property").unwrap_or(0));
+                }
+                subclass::Property("post", ..) => {
+                    self.post
+                        .replace(value.get().expect("post set_property").unwrap_or(0));
+                }
+                subclass::Property("contentid", ..) => {
+                    self.contentid
+                        .replace(value.get().expect("contentid set_property").unwrap_or(0));
+                }
                 subclass::Property("author", ..) => {
                     self.author
                         .replace(value.get().expect("author set_property"));
@@ -538,6 +606,36 @@ mod imp {
                     self.link3vis
                         .replace(value.get().expect("link3vis set_property").unwrap_or(false));
                 }
+                subclass::Property("likes", ..) => {
+                    self.likes.replace(value.get().expect("likes set_property"));
+                }
+                subclass::Property("comments", ..) => {
+                    self.comments
+                        .replace(value.get().expect("comments set_property"));
+                }
+                subclass::Property("reposts", ..) => {
+                    self.reposts
+                        .replace(value.get().expect("reposts set_property"));
+                }
+                subclass::Property("views", ..) => {
+                    self.views.replace(value.get().expect("views set_property"));
+                }
+                subclass::Property("countersvis", ..) => {
+                    self.countersvis.replace(
+                        value
+                            .get()
+                            .expect("countersvis set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("liked", ..) => {
+                    self.liked
+                        .replace(value.get().expect("liked set_property").unwrap_or(false));
+                }
+                subclass::Property("likecount", ..) => {
+                    self.likecount
+                        .replace(value.get().expect("likecount set_property").unwrap_or(0));
+                }
                 //
                 _ => unimplemented!(),
             }
@@ -547,6 +645,9 @@ mod imp {
             let prop = &PROPERTIES[id];
 
             match *prop {
+                subclass::Property("source", ..) => Ok(self.source.borrow().to_value()),
+                subclass::Property("post", ..) => Ok(self.post.borrow().to_value()),
+                subclass::Property("contentid", ..) => Ok(self.contentid.borrow().to_value()),
                 subclass::Property("author", ..) => Ok(self.author.borrow().to_value()),
                 subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
                 subclass::Property("itemtype", ..) => Ok(self.itemtype.borrow().to_value()),
@@ -595,6 +696,13 @@ mod imp {
                 subclass::Property("link3txt", ..) => Ok(self.link3txt.borrow().to_value()),
                 subclass::Property("link3url", ..) => Ok(self.link3url.borrow().to_value()),
                 subclass::Property("link3vis", ..) => Ok(self.link3vis.borrow().to_value()),
+                subclass::Property("likes", ..) => Ok(self.likes.borrow().to_value()),
+                subclass::Property("comments", ..) => Ok(self.comments.borrow().to_value()),
+                subclass::Property("reposts", ..) => Ok(self.reposts.borrow().to_value()),
+                subclass::Property("views", ..) => Ok(self.views.borrow().to_value()),
+                subclass::Property("countersvis", ..) => Ok(self.countersvis.borrow().to_value()),
+                subclass::Property("liked", ..) => Ok(self.liked.borrow().to_value()),
+                subclass::Property("likecount", ..) => Ok(self.likecount.borrow().to_value()),
                 //
                 _ => unimplemented!(),
             }
@@ -619,6 +727,7 @@ glib_wrapper! {
 // initial values for our two properties and then returns the new instance
 impl NewsItemVM {
     pub fn new(model: &NewsItemModel) -> NewsItemVM {
+        let id = model.id();
         const MAX_IMAGES: usize = 10;
         let mut image_file: [String; MAX_IMAGES] = Default::default(); // [String::new(); MAX_IMAGES];
         let mut image_text: [String; MAX_IMAGES] = Default::default(); // [String::new(); MAX_IMAGES];
@@ -650,6 +759,9 @@ impl NewsItemVM {
         glib::Object::new(
             Self::static_type(),
             &[
+                ("source", &id.source_id),
+                ("post", &id.post_id),
+                ("contentid", &id.content_id),
                 ("author", &model.author),
                 ("avatar", &model.avatar),
                 ("itemtype", &model.itemtype),
@@ -698,10 +810,84 @@ impl NewsItemVM {
                 ("link3url", &link_url[3]),
                 ("link3txt", &link_txt[3]),
                 ("link3vis", &link_vis[3]),
+                // counters
+                ("likes", &format_count(model.likes)),
+                ("comments", &format_count(model.comments)),
+                ("reposts", &format_count(model.reposts)),
+                ("views", &format_count(model.views)),
+                ("countersvis", &(model.post_id != 0)),
+                ("liked", &model.liked),
+                ("likecount", &model.likes),
             ],
         )
         .expect("Failed to create row data")
         .downcast()
         .expect("Created row data is of wrong type")
     }
+
+    /// Returns identity of the item
+    pub fn get_id(&self) -> NewsItemId {
+        let number = |name: &str| {
+            self.get_property(name)
+                .ok()
+                .and_then(|value| value.get::<i64>().ok().flatten())
+                .unwrap_or(0)
+        };
+        NewsItemId {
+            source_id: number("source"),
+            post_id: number("post"),
+            itemtype: self
+                .get_property("itemtype")
+                .ok()
+                .and_then(|value| value.get::<String>().ok().flatten())
+                .unwrap_or_default(),
+            content_id: number("contentid"),
+        }
+    }
+
+    /// Updates the item re-fetched from vk.com, e.g. its text has been edited or its counters have changed
+    pub fn update(&self, model: &NewsItemModel) {
+        for (name, value) in &[
+            ("author", &model.author),
+            ("avatar", &model.avatar),
+            ("datetime", &model.datetime),
+            ("content", &model.content),
+            ("comments", &format_count(model.comments)),
+            ("reposts", &format_count(model.reposts)),
+            ("views", &format_count(model.views)),
+        ] {
+            if let Err(e) = self.set_property(*name, *value) {
+                log::error!("failed updating news item property {}: {}", name, e);
+            }
+        }
+        self.set_liked(model.liked, Some(model.likes));
+    }
+
+    pub fn is_liked(&self) -> bool {
+        self.get_property("liked")
+            .ok()
+            .and_then(|value| value.get::<bool>().ok().flatten())
+            .unwrap_or(false)
+    }
+
+    /// User likes the post or not, if the count of likes is not known it follows the like
+    pub fn set_liked(&self, liked: bool, likes: Option<i64>) {
+        let count = self
+            .get_property("likecount")
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0);
+        let count = match likes {
+            Some(likes) => likes,
+            None if liked != self.is_liked() => (count + if liked { 1 } else { -1 }).max(0),
+            None => count,
+        };
+        let updated = self
+            .set_property("liked", &liked)
+            .and_then(|_| self.set_property("likecount", &count))
+            .and_then(|_| self.set_property("likes", &format_count(count)));
+        if let Err(e) = updated {
+            log::error!("failed updating likes of news item: {}", e);
+        }
+    }
 }
@@ -20,6 +20,8 @@ mod imp {
     // The actual data structure that stores our values. This is not accessible
     // directly from the outside.
     pub struct RowData {
+        // source_id of its news, negative for groups
+        id: RefCell<i64>,
         // author name
         name: RefCell<Option<String>>,
         // author image / portrait
@@ -30,10 +32,17 @@ mod imp {
         uri: RefCell<Option<String>>,
         // text
         comment: RefCell<Option<String>>,
+        // a number of posts and the time of the most recent one
+        activity: RefCell<Option<String>>,
+        favourite: RefCell<bool>,
+        muted: RefCell<bool>,
     }
 
     // GObject property definitions for our three values
-    static PROPERTIES: [subclass::Property; 5] = [
+    static PROPERTIES: [subclass::Property; 9] = [
+        subclass::Property("id", |val| {
+            Param::int64(val, "Id", "Id", i64::MIN, i64::MAX, 0, FLAGS)
+        }),
         subclass::Property("name", |val| {
             Param::string(val, "Name", "Name", None, FLAGS)
         }),
@@ -47,6 +56,15 @@ mod imp {
         subclass::Property("comment", |val| {
             Param::string(val, "Coment", "Comment", None, FLAGS)
         }),
+        subclass::Property("activity", |val| {
+            Param::string(val, "Activity", "Activity", None, FLAGS)
+        }),
+        subclass::Property("favourite", |val| {
+            Param::boolean(val, "Favourite", "Favourite", false, FLAGS)
+        }),
+        subclass::Property("muted", |val| {
+            Param::boolean(val, "Muted", "Muted", false, FLAGS)
+        }),
     ];
 
     // Basic declaration of our type for the GObject type system
@@ -70,11 +88,15 @@ mod imp {
         // creates the data structure that contains all our state
         fn new() -> Self {
             Self {
+                id: RefCell::new(0),
                 name: RefCell::new(None),
                 avatar: RefCell::new(None),
                 desc: RefCell::new(None),
                 uri: RefCell::new(None),
                 comment: RefCell::new(None),
+                activity: RefCell::new(None),
+                favourite: RefCell::new(false),
+                muted: RefCell::new(false),
             }
         }
     }
@@ -92,6 +114,10 @@ mod imp {
             let prop = &PROPERTIES[id];
 
             match *prop {
+                subclass::Property("id", ..) => {
+                    self.id
+                        .replace(value.get().expect("id set_property").unwrap_or(0));
+                }
                 subclass::Property("name", ..) => {
                     self.name.replace(value.get().expect("name set_property"));
                 }
@@ -109,6 +135,22 @@ mod imp {
                     self.comment
                         .replace(value.get().expect("comment set_property"));
                 }
+                subclass::Property("activity", ..) => {
+                    self.activity
+                        .replace(value.get().expect("activity set_property"));
+                }
+                subclass::Property("favourite", ..) => {
+                    self.favourite.replace(
+                        value
+                            .get()
+                            .expect("favourite set_property")
+                            .unwrap_or(false),
+                    );
+                }
+                subclass::Property("muted", ..) => {
+                    self.muted
+                        .replace(value.get().expect("muted set_property").unwrap_or(false));
+                }
                 //
                 _ => unimplemented!(),
             }
@@ -118,11 +160,15 @@ mod imp {
             let prop = &PROPERTIES[id];
 
             match *prop {
+                subclass::Property("id", ..) => Ok(self.id.borrow().to_value()),
                 subclass::Property("name", ..) => Ok(self.name.borrow().to_value()),
                 subclass::Property("avatar", ..) => Ok(self.avatar.borrow().to_value()),
                 subclass::Property("desc", ..) => Ok(self.desc.borrow().to_value()),
                 subclass::Property("uri", ..) => Ok(self.uri.borrow().to_value()),
                 subclass::Property("comment", ..) => Ok(self.comment.borrow().to_value()),
+                subclass::Property("activity", ..) => Ok(self.activity.borrow().to_value()),
+                subclass::Property("favourite", ..) => Ok(self.favourite.borrow().to_value()),
+                subclass::Property("muted", ..) => Ok(self.muted.borrow().to_value()),
                 //
                 _ => unimplemented!(),
             }
@@ -150,15 +196,42 @@ impl NewsSourceVM {
         glib::Object::new(
             Self::static_type(),
             &[
+                ("id", &model.id),
                 ("name", &model.name),
                 ("avatar", &model.avatar),
                 ("desc", &model.desc),
                 ("uri", &model.uri),
                 ("comment", &model.comment),
+                ("activity", &model.activity()),
+                ("favourite", &model.favourite),
+                ("muted", &model.muted),
             ],
         )
         .expect("Failed to create row data")
         .downcast()
         .expect("Created row data is of wrong type")
     }
+
+    /// Returns source_id of the source news
+    pub fn get_id(&self) -> i64 {
+        self.get_property("id")
+            .ok()
+            .and_then(|value| value.get::<i64>().ok().flatten())
+            .unwrap_or(0)
+    }
+
+    pub fn is_favourite(&self) -> bool {
+        self.get_flag("favourite")
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.get_flag("muted")
+    }
+
+    fn get_flag(&self, name: &str) -> bool {
+        self.get_property(name)
+            .ok()
+            .and_then(|value| value.get::<bool>().ok().flatten())
+            .unwrap_or(false)
+    }
 }
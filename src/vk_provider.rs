@@ -1,40 +1,101 @@
-use crate::storage::{SharedStorage, Storage};
+use crate::settings::Settings;
+use crate::storage::{Accounts, SharedStorage, Storage};
 use crate::ui::{Message, Request};
-use rvk::APIClient;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
 use tokio::runtime::Builder;
 use tokio::sync::{
     mpsc::{error::TrySendError, Receiver, Sender},
-    oneshot,
+    oneshot, watch, Notify, RwLock,
 };
 use tokio::time::{sleep, Duration};
 
 mod access_token_provider;
 pub use access_token_provider::AccessTokenProvider;
 pub use access_token_provider::AuthResponse;
+pub use access_token_provider::LoopbackError;
 mod account;
 pub use account::{Account, AccountProvider};
 pub mod constants;
+use constants::SCOPES_NEWSFEED;
 mod user;
 pub use user::User;
+mod vk_api;
+pub use vk_api::{is_auth_error, is_permission_error, RvkApi, SharedApi, VkApi};
+mod fixture_api;
+pub use fixture_api::FixtureApi;
 mod news_provider;
 pub use news_provider::NewsProvider;
+mod poll_schedule;
+use poll_schedule::PollSchedule;
 mod news_update;
 pub use news_update::NewsUpdate;
 mod sources_update;
 pub use sources_update::SourcesUpdate;
 mod sources_manager;
 use sources_manager::SourcesManager;
+mod chat_objects;
+mod chat_provider;
+use chat_provider::ChatProvider;
+mod chat_update;
+pub use chat_update::{ConversationsUpdate, MessagesUpdate};
+mod friend_objects;
+mod friends_provider;
+mod friends_update;
+mod in_order;
+mod long_poll;
+pub use friends_update::FriendsUpdate;
+mod communities_provider;
+mod communities_update;
+mod community_objects;
+pub use communities_update::CommunitiesUpdate;
+mod comment_objects;
+mod comments_provider;
+use comments_provider::CommentsProvider;
+mod comments_update;
+pub use comments_update::CommentsUpdate;
+mod likes_provider;
+mod photo_upload;
+use photo_upload::PhotoUploader;
+mod post_publisher;
+mod request_handler;
+use request_handler::RequestHandler;
+mod scope_granter;
+use scope_granter::ScopeGranter;
+#[cfg(test)]
+mod stand_in;
+mod upload_objects;
+mod wall_objects;
+mod wall_provider;
+use wall_provider::WallProvider;
 
 type MessageSender = Sender<Message>;
 type RequestReceiver = Receiver<Request>;
 type StopReceiver = oneshot::Receiver<()>;
+// a maximal number of news items restored from storage on start
+const MAX_RESTORED_NEWS: usize = 300;
+
+/// Creates API client for the access token
+type ApiFactory = Box<dyn Fn(&str) -> SharedApi + Send + Sync>;
+
+/// Account to authenticate and serve
+enum Target {
+    // the account served last time
+    Current,
+    // known account with user_id
+    Known(String),
+    // account which has not been authenticated yet
+    New,
+}
 
 /// Spawn separate thread to handle communication.
 pub fn run_with_own_runtime(
     rx_stop: StopReceiver,
     tx_msg: MessageSender,
     rx_req: RequestReceiver,
+    settings: Settings,
     stack_size: usize,
     thread_pool_size: usize,
 ) {
@@ -50,148 +111,50 @@ pub fn run_with_own_runtime(
         log::info!("starting main worker");
         // main task, executes until inner error or rx_stop is received
         let worker = async move {
-            let storage: SharedStorage = Arc::new(Storage::new());
-
-            // test access to vk.com account
-            // test stored auth
-            let mut auth: Option<AuthResponse> = None;
-            let mut account: Option<Account> = None;
-            let access_token_valid = if let Ok(a) = storage.load_auth_async().await {
-                //todo: logging
-                //test auth
-                // create VK client
-                let vk_api = APIClient::new(a.get_access_token());
-                account = AccountProvider::query_async(&vk_api).await;
-                auth = Some(a);
-                account.is_some()
+            if let Some(replay) = FixtureApi::from_env() {
+                // offline mode, no authentication is required
+                let storage: SharedStorage =
+                    Arc::new(Storage::new(&settings).with_fixture_files(FixtureApi::files_dir()));
+                let auth = AuthResponse::new("", "0");
+                serve(Arc::new(replay), auth, settings, storage, tx_msg, rx_req).await;
             } else {
-                false
-            };
-            if !access_token_valid {
-                let (tx_response, rx_response) = oneshot::channel::<AuthResponse>();
-                if let Ok(_) = tx_msg.send(Message::Auth(tx_response)).await {
-                    if let Ok(a) = rx_response.await {
-                        if let Err(e) = storage.save_auth_async(&a).await {
-                            log::warn!("failed to store auth data: {}", e);
-                        }
-                        // create VK client
-                        let vk_api = APIClient::new(a.get_access_token());
-                        account = AccountProvider::query_async(&vk_api).await;
-                        auth = Some(a);
-                    }
-                }
-            }
-            if auth.is_none() {
-                log::error!("authentication is not available");
-                return;
-            }
-            if account.is_none() {
-                log::error!("authentication succeded but account is unreachable");
-                return;
-            }
-            let auth = auth.unwrap();
-            let account = account.unwrap();
-            log::debug!("authentication: {}", auth);
-            log::info!("account: {}", account);
-            // create VK client
-            let vk_api = Arc::new(APIClient::new(auth.get_access_token()));
-            // request own user info
-            let user = User::query_async(&vk_api, auth.get_user_id()).await;
-            if user.is_none() {
-                log::error!("failed to get user info");
-                return;
-            }
-            let user = user.unwrap();
-            log::debug!("user is {}", user);
-            let view_model = user.get_view_model(&storage).await;
-            log::debug!("user view is {}", &view_model);
-            if let Err(e) = tx_msg.send(Message::OwnInfo(view_model)).await {
-                log::error!("failed updating user info, {}", e);
-            }
-            let news = Arc::new(NewsProvider::new());
-            let news_sources = Arc::new(SourcesManager::new());
-
-            // start task handling rx_req
-            let vk_api_copy = vk_api.clone();
-            let news_copy = news.clone();
-            let news_sources_copy = news_sources.clone();
-            let storage_copy = storage.clone();
-            let tx_msg_copy = tx_msg.clone();
-            tokio::spawn(async move {
-                log::info!("starting UI requests handler");
+                let root_storage = Storage::new(&settings);
+                let connect: ApiFactory =
+                    Box::new(|token: &str| -> SharedApi { Arc::new(RvkApi::new(token)) });
+                let mut target = Target::Current;
                 let mut rx_req = rx_req;
-                loop {
-                    if let Some(req) = rx_req.recv().await {
-                        match req {
-                            // more news requested vy UI
-                            Request::NewsNext => {
-                                log::debug!("UI requested more news, please wait")
-                            }
-                            // older news requested by UI
-                            Request::NewsOlder => {
-                                if let Some(news_feed) = news_copy.prev_update(&vk_api_copy).await {
-                                    if let Some(items) = &news_feed.items {
-                                        log::debug!("got {} older news items", items.len());
-                                    }
-                                    let update =
-                                        NewsUpdate::new_async(&news_feed, &storage_copy).await;
-                                    if !do_send(&tx_msg_copy, Message::OlderNews(update)) {
-                                        break;
-                                    }
-                                    // prepare sources update
-                                    if let Some(new_items) = news_sources_copy.add_new_sources(
-                                        SourcesUpdate::new_async(&news_feed, &storage_copy).await,
-                                    ) {
-                                        // send sources update
-                                        if !do_send(&tx_msg_copy, Message::NewsSources(new_items)) {
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                            Request::Stop => {
-                                storage_copy.prepare_to_stop();
-                                break;
-                            }
-                        }
-                    } else {
-                        log::warn!(
-                            "request channel has closed by sender(s), there are no more requests"
-                        );
+                let mut settings = settings;
+                // serve accounts one by one as user switches them
+                while let Some((auth, vk_api, accounts)) = authenticate(
+                    &root_storage,
+                    &target,
+                    &AccessTokenProvider::new(&settings),
+                    &connect,
+                    &tx_msg,
+                )
+                .await
+                {
+                    let user_id = auth.get_user_id();
+                    if !do_send(
+                        &tx_msg,
+                        Message::Accounts {
+                            current: user_id.to_string(),
+                            list: accounts.names(),
+                        },
+                    ) {
                         break;
                     }
-                }
-                log::info!("UI requests handler has stopped");
-            });
-
-            loop {
-                // periodically query news
-                if let Some(news_feed) = news.next_update(&vk_api).await {
-                    if let Some(items) = &news_feed.items {
-                        log::debug!("got {} news items", items.len());
-                    }
-                    // prepare news update
-                    let update = NewsUpdate::new_async(&news_feed, &storage).await;
-                    // send news update
-                    if !do_send(&tx_msg, Message::News(update)) {
-                        break;
-                    }
-                    // prepare sources update
-                    if let Some(new_items) = news_sources
-                        .add_new_sources(SourcesUpdate::new_async(&news_feed, &storage).await)
-                    {
-                        // send sources update
-                        if !do_send(&tx_msg, Message::NewsSources(new_items)) {
-                            break;
+                    let storage: SharedStorage = Arc::new(root_storage.for_account(user_id));
+                    match serve(vk_api, auth, settings, storage, tx_msg.clone(), rx_req).await {
+                        Some((next, rx)) => {
+                            target = next;
+                            rx_req = rx;
+                            // might have been changed while serving the previous account
+                            settings = Settings::load();
                         }
+                        None => break,
                     }
                 }
-                if let Err(e) = storage.save_state_async().await {
-                    log::warn!("saving storage state failed: {}", e);
-                }
-
-                // pause main provider task until time to get next update from vk.com
-                sleep(Duration::from_millis(60_000)).await;
             }
         };
 
@@ -204,6 +167,428 @@ pub fn run_with_own_runtime(
     });
 }
 
+/// Tests stored access to the target vk.com account, asks UI to authenticate if it is not valid.
+/// Returns valid authentication, API client created for it and the updated list of known accounts
+async fn authenticate(
+    storage: &Storage,
+    target: &Target,
+    access: &AccessTokenProvider,
+    connect: &ApiFactory,
+    tx_msg: &MessageSender,
+) -> Option<(AuthResponse, SharedApi, Accounts)> {
+    let mut accounts = match storage.load_accounts_async().await {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            log::warn!("failed loading accounts: {}", e);
+            Accounts::default()
+        }
+    };
+    let entry = match target {
+        Target::Current => accounts.get_current(),
+        Target::Known(user_id) => accounts.find(user_id),
+        Target::New => None,
+    };
+    let stored = entry.map(|entry| entry.auth.clone());
+    // request the scopes granted before too
+    let access = access
+        .clone()
+        .with_granted(&entry.map(|entry| entry.scopes.clone()).unwrap_or_default());
+    // test stored auth
+    let mut auth: Option<AuthResponse> = None;
+    let mut account: Option<Account> = None;
+    let mut vk_api: Option<SharedApi> = None;
+    let access_token_valid = if let Some(a) = stored {
+        // create VK client
+        let api = connect(a.get_access_token());
+        account = AccountProvider::query_async(api.as_ref()).await;
+        auth = Some(a);
+        vk_api = Some(api);
+        account.is_some()
+    } else {
+        false
+    };
+    if !access_token_valid {
+        let (tx_response, rx_response) = oneshot::channel::<AuthResponse>();
+        let request = match target {
+            Target::New => Message::AddAccount(access, tx_response),
+            _ => Message::Auth(access, tx_response),
+        };
+        if let Ok(_) = tx_msg.send(request).await {
+            if let Ok(a) = rx_response.await {
+                // create VK client
+                let api = connect(a.get_access_token());
+                account = AccountProvider::query_async(api.as_ref()).await;
+                auth = Some(a);
+                vk_api = Some(api);
+            }
+        }
+    }
+    if auth.is_none() {
+        log::error!("authentication is not available");
+        return None;
+    }
+    if account.is_none() {
+        log::error!("authentication succeded but account is unreachable");
+        return None;
+    }
+    let auth = auth.unwrap();
+    let account = account.unwrap();
+    log::debug!("authentication: {}", auth);
+    log::info!("account: {}", account);
+    accounts.upsert(auth.clone());
+    accounts.set_name(
+        auth.get_user_id(),
+        format!("{} {}", account.0.first_name, account.0.last_name).as_str(),
+    );
+    if let Err(e) = storage.save_accounts_async(&accounts).await {
+        log::warn!("failed to store accounts: {}", e);
+    }
+    vk_api.map(|api| (auth, api, accounts))
+}
+
+/// Queries own user info, then periodically queries news and handles UI requests.
+/// Returns the account user has switched to and the requests channel to serve it,
+/// or None if serving has stopped
+async fn serve(
+    vk_api: SharedApi,
+    auth: AuthResponse,
+    settings: Settings,
+    storage: SharedStorage,
+    tx_msg: MessageSender,
+    rx_req: RequestReceiver,
+) -> Option<(Target, RequestReceiver)> {
+    // request own user info
+    let user = User::query_async(vk_api.as_ref(), auth.get_user_id()).await;
+    if user.is_none() {
+        log::error!("failed to get user info");
+        return None;
+    }
+    let user = user.unwrap();
+    log::debug!("user is {}", user);
+    let view_model = user.get_view_model(&storage).await;
+    log::debug!("user view is {}", &view_model);
+    // author of the messages user sends
+    let own = view_model.clone();
+    if let Err(e) = tx_msg.send(Message::OwnInfo(view_model)).await {
+        log::error!("failed updating user info, {}", e);
+    }
+    // restore news history and continue receiving news from the most recent stored item
+    let mut restored = Vec::new();
+    let news = match storage.load_news_async(MAX_RESTORED_NEWS).await {
+        Ok(mut items) if !items.is_empty() => {
+            log::info!("restored {} news items", items.len());
+            let newest = items.first().map_or(0, |item| item.timestamp);
+            let oldest = items.last().map_or(0, |item| item.timestamp);
+            restored = items
+                .iter()
+                .map(|item| {
+                    let id = item.id();
+                    (id.source_id, id.post_id, item.timestamp)
+                })
+                .collect();
+            // the source might have been muted after its news were stored
+            items.retain(|item| !storage.is_muted(item.source_id));
+            if !do_send(&tx_msg, Message::News(NewsUpdate::from_items(items))) {
+                return None;
+            }
+            Arc::new(NewsProvider::resume(
+                &settings,
+                oldest as u64,
+                newest as u64,
+            ))
+        }
+        Ok(_) => Arc::new(NewsProvider::new(&settings)),
+        Err(e) => {
+            log::warn!("failed restoring news: {}", e);
+            Arc::new(NewsProvider::new(&settings))
+        }
+    };
+    let news_sources = Arc::new(SourcesManager::new());
+    news_sources.restore_async(&storage, &restored).await;
+    // wakes up the main provider task to get news immediately
+    let refresh = Arc::new(Notify::new());
+    // the application window is inactive, so news are polled rarer
+    let background = Arc::new(AtomicBool::new(false));
+    // changed by user in settings
+    let poll_interval_sec = Arc::new(AtomicU64::new(settings.poll_interval_sec));
+    // friends are refreshed on their own schedule
+    let friends_interval_sec = Arc::new(AtomicU64::new(settings.friends_poll_interval_sec));
+    // wakes up the friends task to get friends immediately
+    let friends_refresh = Arc::new(Notify::new());
+    // user has logged out, so the account is forgotten after serving has stopped
+    let logout = Arc::new(AtomicBool::new(false));
+    // composes authorization URI to re-authenticate, might be changed by user in settings
+    let access = Arc::new(RwLock::new(AccessTokenProvider::new(&settings)));
+
+    let user_id = auth.get_user_id().to_string();
+    // asks user to grant the scopes the features require, shared with the main task
+    let granter = Arc::new(ScopeGranter::new(
+        vk_api.clone(),
+        access.clone(),
+        &user_id,
+        storage.clone(),
+        tx_msg.clone(),
+        refresh.clone(),
+    ));
+    // stops the tasks serving the long requests along with the handler
+    let (tx_stopped, stopped) = watch::channel(false);
+
+    // start task handling rx_req
+    let request_handler = Arc::new(RequestHandler {
+        vk_api: vk_api.clone(),
+        storage: storage.clone(),
+        tx_msg: tx_msg.clone(),
+        user_id: user_id.clone(),
+        own,
+        granter: granter.clone(),
+        access: access.clone(),
+        news: news.clone(),
+        news_sources: news_sources.clone(),
+        chat: ChatProvider::new(),
+        wall: WallProvider::new(&settings),
+        comments: CommentsProvider::new(),
+        uploader: PhotoUploader::new(),
+        news_order: Default::default(),
+        chat_order: Default::default(),
+        wall_order: Default::default(),
+        comments_order: Default::default(),
+        likes_order: Default::default(),
+        refresh: refresh.clone(),
+        background: background.clone(),
+        poll_interval_sec: poll_interval_sec.clone(),
+        friends_interval_sec: friends_interval_sec.clone(),
+        friends_refresh: friends_refresh.clone(),
+        logout: logout.clone(),
+        stopped,
+    });
+    let mut handler = tokio::spawn(request_handler.run(rx_req));
+
+    // real-time events of chats
+    let long_poll = tokio::spawn(long_poll::run(
+        vk_api.clone(),
+        user_id.parse::<i64>().unwrap_or(0),
+        storage.clone(),
+        tx_msg.clone(),
+    ));
+    // friends with their online status
+    let friends = tokio::spawn(friends_provider::run(
+        vk_api.clone(),
+        storage.clone(),
+        friends_interval_sec,
+        friends_refresh,
+        tx_msg.clone(),
+    ));
+
+    // to log out when the polling task has stopped
+    let logout_api = vk_api.clone();
+    let logout_storage = storage.clone();
+    let logout_tx = tx_msg.clone();
+
+    storage.apply_settings(&settings);
+    let mut schedule = PollSchedule::new(Duration::from_secs(settings.poll_interval_sec));
+    let poll = async move {
+        let mut auth = auth;
+        loop {
+            if auth.is_expired() {
+                log::info!("access token has expired");
+                let access = access.read().await.clone();
+                match reauthenticate(
+                    vk_api.as_ref(),
+                    access,
+                    auth.get_user_id(),
+                    &storage,
+                    &tx_msg,
+                )
+                .await
+                {
+                    Some(renewed) => auth = renewed,
+                    None => break,
+                }
+            }
+            // periodically query news
+            let news_feed = match news.next_update(vk_api.as_ref()).await {
+                Ok(news_feed) => {
+                    schedule.succeeded();
+                    Some(news_feed)
+                }
+                Err(e) if is_auth_error(&e) => {
+                    log::warn!("access token has been rejected");
+                    let access = access.read().await.clone();
+                    match reauthenticate(
+                        vk_api.as_ref(),
+                        access,
+                        auth.get_user_id(),
+                        &storage,
+                        &tx_msg,
+                    )
+                    .await
+                    {
+                        // get news with the new token immediately
+                        Some(renewed) => {
+                            auth = renewed;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                Err(e) if is_permission_error(&e) => {
+                    match granter.grant_on_error(&e, &SCOPES_NEWSFEED).await {
+                        // get news with the granted scopes immediately
+                        Some(Some(renewed)) => {
+                            auth = renewed;
+                            continue;
+                        }
+                        Some(None) => {
+                            schedule.failed(&e);
+                            None
+                        }
+                        None => break,
+                    }
+                }
+                Err(e) => {
+                    schedule.failed(&e);
+                    None
+                }
+            };
+            if let Some(news_feed) = news_feed {
+                if let Some(items) = &news_feed.items {
+                    log::debug!("got {} news items", items.len());
+                }
+                // prepare news update
+                let update = NewsUpdate::new_async(&news_feed, &storage).await;
+                if let Err(e) = storage.append_news_async(update.items()).await {
+                    log::warn!("failed storing news: {}", e);
+                }
+                // send news update
+                if !do_send(&tx_msg, Message::News(update)) {
+                    break;
+                }
+                // prepare sources update
+                if let Some(new_items) = news_sources.add_news(
+                    &news_feed,
+                    SourcesUpdate::new_async(&news_feed, &storage).await,
+                    &storage,
+                ) {
+                    // send sources update
+                    if !do_send(&tx_msg, Message::NewsSources(new_items)) {
+                        break;
+                    }
+                }
+            }
+            if let Err(e) = storage.save_state_async().await {
+                log::warn!("saving storage state failed: {}", e);
+            }
+            news_sources.save_async(&storage).await;
+
+            // pause main provider task until time to get next update from vk.com or until UI requests it,
+            // the latter also restarts the period
+            schedule.set_interval(Duration::from_secs(
+                poll_interval_sec.load(Ordering::SeqCst),
+            ));
+            schedule.set_background(background.load(Ordering::SeqCst));
+            let delay = schedule.delay();
+            log::debug!("next news update in {} sec", delay.as_secs());
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = refresh.notified() => log::debug!("getting news on demand"),
+            }
+        }
+    };
+
+    // serve until either UI has stopped or user has switched the account
+    let next = tokio::select! {
+        next = &mut handler => next.unwrap_or(None),
+        _ = poll => {
+            handler.abort();
+            None
+        }
+    };
+    let _ = tx_stopped.send(true);
+    long_poll.abort();
+    friends.abort();
+    if logout.load(Ordering::SeqCst)
+        && !log_out(logout_api.as_ref(), &user_id, &logout_storage, &logout_tx).await
+    {
+        return None;
+    }
+    next
+}
+
+/// Ends the session on vk.com, forgets the account with its access token and cached data,
+/// then asks UI to forget everything displayed for it.
+/// Returns false if UI has stopped
+async fn log_out(
+    vk_api: &dyn VkApi,
+    user_id: &str,
+    storage: &Storage,
+    tx_msg: &MessageSender,
+) -> bool {
+    log::info!("logging out user {}", user_id);
+    // the token might have been rejected already, the account is forgotten anyway
+    if let Err(e) = vk_api.auth_log_out().await {
+        log::warn!("failed ending the session on vk.com: {}", e);
+    }
+    if let Err(e) = storage.forget_account_async(user_id).await {
+        log::error!("failed forgetting account: {}", e);
+    }
+    storage.clear_cache();
+    do_send(tx_msg, Message::LoggedOut)
+}
+
+/// Asks UI to authenticate the same user again, then replaces access token in the shared client,
+/// so all the tasks continue with the new one.
+/// Returns None if UI has stopped
+async fn reauthenticate(
+    vk_api: &dyn VkApi,
+    access: AccessTokenProvider,
+    user_id: &str,
+    storage: &Storage,
+    tx_msg: &MessageSender,
+) -> Option<AuthResponse> {
+    // request the scopes granted before too
+    let access = match storage.load_accounts_async().await {
+        Ok(accounts) => access.with_granted(
+            &accounts
+                .find(user_id)
+                .map(|entry| entry.scopes.clone())
+                .unwrap_or_default(),
+        ),
+        Err(_) => access,
+    };
+    loop {
+        let (tx_response, rx_response) = oneshot::channel::<AuthResponse>();
+        if tx_msg
+            .send(Message::Auth(access.clone(), tx_response))
+            .await
+            .is_err()
+        {
+            return None;
+        }
+        let auth = rx_response.await.ok()?;
+        if auth.get_user_id() != user_id {
+            log::warn!(
+                "authenticated user {} instead of {}, asking again",
+                auth.get_user_id(),
+                user_id
+            );
+            continue;
+        }
+        log::info!("re-authenticated: {}", auth);
+        vk_api.set_access_token(auth.get_access_token()).await;
+        // keep the new token for the next start
+        match storage.load_accounts_async().await {
+            Ok(mut accounts) => {
+                accounts.upsert(auth.clone());
+                if let Err(e) = storage.save_accounts_async(&accounts).await {
+                    log::warn!("failed to store accounts: {}", e);
+                }
+            }
+            Err(e) => log::warn!("failed loading accounts: {}", e),
+        }
+        return Some(auth);
+    }
+}
+
 fn do_send(tx: &MessageSender, msg: Message) -> bool {
     // send sources update
     match tx.try_send(msg) {
@@ -218,3 +603,646 @@ fn do_send(tx: &MessageSender, msg: Message) -> bool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NewsItemId;
+    use crate::ui::{ChatRequest, CommentRequest, CommunityRequest};
+    use std::path::Path;
+    use tokio::sync::mpsc;
+    use tokio::task::JoinHandle;
+    use tokio::time::timeout;
+
+    // recorded news test_api answers with: the first ones, the next ones and the older ones
+    const NEWS_FIRST: &str = "2021-01-15_20-57-00.json";
+    const NEWS_NEXT: &str = "2021-01-16_12-59-00.json";
+    const NEWS_OLDER: &str = "2021-01-15_10-44-00.json";
+
+    fn test_api() -> Arc<FixtureApi> {
+        let api = FixtureApi::new().with_profile(184946538, "Test", "User");
+        for name in &[NEWS_FIRST, NEWS_NEXT] {
+            let path = Path::new("resources/tests/newsfeed").join(name);
+            api.push_next(FixtureApi::load_newsfeed(&path).unwrap());
+        }
+        let path = Path::new("resources/tests/newsfeed").join(NEWS_OLDER);
+        api.push_prev(FixtureApi::load_newsfeed(&path).unwrap());
+        Arc::new(api)
+    }
+
+    // the news are received from the recorded file: the most recent first, none of them made up
+    fn assert_replayed(update: &NewsUpdate, name: &str) {
+        let path = Path::new("resources/tests/newsfeed").join(name);
+        let recorded = FixtureApi::load_newsfeed(&path)
+            .and_then(|feed| feed.items)
+            .unwrap_or_default();
+        let items = update.items();
+        assert!(!items.is_empty());
+        assert!(items.len() <= recorded.len());
+        assert!(items
+            .windows(2)
+            .all(|pair| pair[0].timestamp >= pair[1].timestamp));
+        assert!(items.iter().all(|item| recorded
+            .iter()
+            .any(|src| src.source_id == item.source_id && src.date == item.timestamp)));
+    }
+
+    async fn recv(rx_msg: &mut Receiver<Message>) -> Message {
+        timeout(Duration::from_secs(5), rx_msg.recv())
+            .await
+            .expect("no message from provider")
+            .expect("provider has closed the channel")
+    }
+
+    // the account served for the test which talks to the provider like UI does
+    struct Served {
+        tx_req: Sender<Request>,
+        rx_msg: Receiver<Message>,
+        worker: JoinHandle<Option<(Target, RequestReceiver)>>,
+    }
+
+    impl Served {
+        fn start(
+            api: SharedApi,
+            user_id: &str,
+            settings: Settings,
+            storage: SharedStorage,
+        ) -> Self {
+            let (tx_msg, rx_msg) = mpsc::channel(100);
+            let (tx_req, rx_req) = mpsc::channel(100);
+            let worker = tokio::spawn(serve(
+                api,
+                AuthResponse::new("1234", user_id),
+                settings,
+                storage,
+                tx_msg,
+                rx_req,
+            ));
+            Served {
+                tx_req,
+                rx_msg,
+                worker,
+            }
+        }
+
+        async fn send(&self, request: Request) {
+            assert!(self.tx_req.send(request).await.is_ok());
+        }
+
+        async fn recv(&mut self) -> Message {
+            recv(&mut self.rx_msg).await
+        }
+
+        // skips news polled meanwhile
+        async fn recv_skipping_news(&mut self) -> Message {
+            loop {
+                match self.recv().await {
+                    Message::News(_) | Message::NewsSources(_) => continue,
+                    msg => return msg,
+                }
+            }
+        }
+
+        // returns the answer to the request
+        async fn request(&mut self, request: Request) -> Message {
+            self.send(request).await;
+            self.recv_skipping_news().await
+        }
+
+        // skips the sources updated meanwhile
+        async fn recv_news(&mut self) -> NewsUpdate {
+            loop {
+                match self.recv().await {
+                    Message::News(update) => return update,
+                    Message::NewsSources(_) => continue,
+                    _ => panic!("news are expected"),
+                }
+            }
+        }
+
+        async fn stop(self) {
+            self.send(Request::Stop).await;
+            self.worker.abort();
+        }
+
+        // returns the account to serve next
+        async fn stopped(self) -> Option<(Target, RequestReceiver)> {
+            timeout(Duration::from_secs(5), self.worker)
+                .await
+                .expect("serving has not stopped")
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticate_thru_ui() {
+        let storage = Arc::new(Storage::for_test("auth"));
+        let (tx_msg, mut rx_msg) = mpsc::channel(100);
+        let connect: ApiFactory = Box::new(|token: &str| -> SharedApi {
+            assert_eq!(token, "1234");
+            Arc::new(FixtureApi::new())
+        });
+        let auth_storage = storage.clone();
+        let auth = tokio::spawn(async move {
+            let access = AccessTokenProvider::new(&Settings::default());
+            authenticate(&auth_storage, &Target::Current, &access, &connect, &tx_msg).await
+        });
+        // there is no stored auth so UI is asked for it
+        match recv(&mut rx_msg).await {
+            Message::Auth(_, tx_response) => {
+                let response = "abc#access_token=1234&expires_in=0&user_id=1"
+                    .parse::<AuthResponse>()
+                    .unwrap();
+                assert!(tx_response.send(response).is_ok());
+            }
+            _ => panic!("authentication is expected"),
+        }
+        let (auth, _, accounts) = auth.await.unwrap().expect("authentication failed");
+        assert_eq!(auth.get_user_id(), "1");
+        assert_eq!(accounts.current.as_deref(), Some("1"));
+        // the account is known now
+        let stored = storage.load_accounts_async().await.ok().unwrap();
+        assert!(stored.find("1").is_some());
+    }
+
+    #[tokio::test]
+    async fn switch_account() {
+        let storage = Arc::new(Storage::for_test("switch"));
+        let mut served = Served::start(test_api(), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        served.send(Request::SwitchAccount("1".to_string())).await;
+        // serving stops and returns requests channel to serve the selected account
+        match served.stopped().await {
+            Some((Target::Known(user_id), _)) => assert_eq!(user_id, "1"),
+            _ => panic!("switching to the known account is expected"),
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_news_and_requests() {
+        let storage = Arc::new(Storage::for_test("serve"));
+        let mut served = Served::start(test_api(), "184946538", Settings::default(), storage);
+
+        match served.recv().await {
+            Message::OwnInfo(user) => assert_eq!(user.name, "Test User"),
+            _ => panic!("own info is expected first"),
+        }
+        match served.recv().await {
+            Message::News(update) => {
+                // counters of the posts are carried
+                assert!(update.items().iter().any(|item| item.likes > 0));
+                assert!(update.items().iter().any(|item| item.views > 0));
+                assert_replayed(&update, NEWS_FIRST);
+            }
+            _ => panic!("news are expected"),
+        }
+        match served.recv().await {
+            Message::NewsSources(update) => assert!(!update.items.is_empty()),
+            _ => panic!("news sources are expected"),
+        }
+
+        served.send(Request::NewsOlder).await;
+        match served.recv().await {
+            Message::OlderNews(update) => assert_replayed(&update, NEWS_OLDER),
+            _ => panic!("older news are expected"),
+        }
+        served.stop().await;
+    }
+
+    #[tokio::test]
+    async fn serve_chats() {
+        let storage = Arc::new(Storage::for_test("chats"));
+        let api = Arc::new(
+            FixtureApi::new()
+                .with_profile(4321, "Anna", "Sidorova")
+                .with_messages_from_dir("resources/tests/messages"),
+        );
+        let mut served = Served::start(api, "4321", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+
+        match served
+            .request(Request::Chat(ChatRequest::Conversations))
+            .await
+        {
+            Message::Conversations(update) => assert_eq!(update.items().len(), 3),
+            _ => panic!("conversations are expected"),
+        }
+        let peer_id = 2000000001;
+        match served
+            .request(Request::Chat(ChatRequest::History(peer_id)))
+            .await
+        {
+            Message::ChatMessages { update, older, .. } => {
+                assert!(!older);
+                assert_eq!(update.items().len(), 5);
+            }
+            _ => panic!("history is expected"),
+        }
+        // the whole history has been received
+        match served
+            .request(Request::Chat(ChatRequest::Older(peer_id)))
+            .await
+        {
+            Message::ChatMessages { update, older, .. } => {
+                assert!(older);
+                assert!(update.is_empty());
+            }
+            _ => panic!("older history is expected"),
+        }
+        let text = "see you".to_string();
+        match served
+            .request(Request::Chat(ChatRequest::Send { peer_id, text }))
+            .await
+        {
+            Message::ChatMessages {
+                peer_id: to,
+                update,
+                ..
+            } => {
+                assert_eq!(to, peer_id);
+                let sent = &update.items()[0];
+                assert_eq!(sent.id, 106);
+                assert!(sent.out);
+                assert_eq!(sent.author, "Anna Sidorova");
+                assert_eq!(sent.text, "see you");
+            }
+            _ => panic!("sent message is expected"),
+        }
+        served.stop().await;
+    }
+
+    #[tokio::test]
+    async fn serve_friends() {
+        let storage = Arc::new(Storage::for_test("friends"));
+        let api = Arc::new(
+            FixtureApi::new()
+                .with_profile(4321, "Anna", "Sidorova")
+                .with_friends_from_dir("resources/tests/friends"),
+        );
+        let mut served = Served::start(api, "4321", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        // friends are refreshed on start without being requested
+        match served.recv_skipping_news().await {
+            Message::Friends(update) => {
+                assert_eq!(update.items().len(), 3);
+                assert_eq!(update.lists().len(), 2);
+            }
+            _ => panic!("friends are expected"),
+        }
+        // and when the friends page is opened
+        match served.request(Request::Friends).await {
+            Message::Friends(update) => assert_eq!(update.items().len(), 3),
+            _ => panic!("friends are expected"),
+        }
+        served.stop().await;
+    }
+
+    #[tokio::test]
+    async fn serve_communities() {
+        let storage = Arc::new(Storage::for_test("communities"));
+        let api = FixtureApi::new()
+            .with_profile(184946538, "Test", "User")
+            .with_communities_from_dir("resources/tests/groups");
+        for name in &["2021-01-15_20-57-00.json", "2021-01-16_12-59-00.json"] {
+            let path = Path::new("resources/tests/newsfeed").join(name);
+            api.push_next(FixtureApi::load_newsfeed(&path).unwrap());
+        }
+        let mut served = Served::start(Arc::new(api), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        assert!(matches!(served.recv().await, Message::News(_)));
+        assert!(matches!(served.recv().await, Message::NewsSources(_)));
+        let list = Request::Communities(CommunityRequest::List);
+        match served.request(list).await {
+            Message::Communities(update) => assert_eq!(update.items().len(), 4),
+            _ => panic!("communities are expected"),
+        }
+        // the community left is no longer listed
+        let leave = Request::Communities(CommunityRequest::Leave(200100));
+        assert!(matches!(
+            served.request(leave).await,
+            Message::Membership {
+                group_id: 200100,
+                member: false
+            }
+        ));
+        let list = Request::Communities(CommunityRequest::List);
+        match served.request(list).await {
+            Message::Communities(update) => assert_eq!(update.items().len(), 3),
+            _ => panic!("communities are expected"),
+        }
+        let join = Request::Communities(CommunityRequest::Join(200100));
+        assert!(matches!(
+            served.request(join).await,
+            Message::Membership {
+                group_id: 200100,
+                member: true
+            }
+        ));
+        // posts of the muted community are not received
+        let mute = Request::MuteSource {
+            source_id: -40316705,
+            muted: true,
+        };
+        assert!(matches!(
+            served.request(mute).await,
+            Message::SourceMuted {
+                source_id: -40316705,
+                muted: true
+            }
+        ));
+        served.send(Request::NewsNext).await;
+        let update = served.recv_news().await;
+        assert_replayed(&update, NEWS_NEXT);
+        assert!(update
+            .items()
+            .iter()
+            .all(|item| item.source_id != -40316705));
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn serve_likes() {
+        let storage = Arc::new(Storage::for_test("likes"));
+        let api = Arc::new(
+            FixtureApi::new()
+                .with_profile(184946538, "Test", "User")
+                .with_likes(-40316705, 2194561, 21),
+        );
+        let mut served = Served::start(api.clone(), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        let post = NewsItemId {
+            source_id: -40316705,
+            post_id: 2194561,
+            itemtype: "post".to_string(),
+            content_id: 0,
+        };
+        let like = Request::Like {
+            item: post.clone(),
+            liked: true,
+        };
+        match served.request(like).await {
+            Message::Liked { item, liked, likes } => {
+                assert!(item == post && liked);
+                assert_eq!(likes, Some(22));
+            }
+            _ => panic!("like is expected"),
+        }
+        // the failed unlike is reported and taken back
+        api.deny_likes();
+        let unlike = Request::Like {
+            item: post.clone(),
+            liked: false,
+        };
+        assert!(matches!(served.request(unlike).await, Message::Error(_)));
+        match served.recv_skipping_news().await {
+            Message::Liked { item, liked, likes } => {
+                assert!(item == post && liked);
+                assert_eq!(likes, None);
+            }
+            _ => panic!("like is expected to be taken back"),
+        }
+        served.stop().await;
+    }
+
+    #[tokio::test]
+    async fn serve_comments() {
+        let storage = Arc::new(Storage::for_test("comments"));
+        let api = FixtureApi::new()
+            .with_profile(184946538, "Test", "User")
+            .with_comments_from_dir("resources/tests/comments");
+        let mut served = Served::start(Arc::new(api), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        // nothing is paged until the post is opened
+        served.send(Request::Comments(CommentRequest::More)).await;
+        let post = NewsItemId {
+            source_id: -40316705,
+            post_id: 38231954,
+            itemtype: "post".to_string(),
+            content_id: 0,
+        };
+        let open = Request::Comments(CommentRequest::Open(post.clone()));
+        match served.request(open).await {
+            Message::Comments {
+                post: opened_post,
+                thread,
+                opened,
+                update,
+            } => {
+                assert!(opened_post == post && opened);
+                assert_eq!(thread, 0);
+                assert_eq!(update.items().len(), 7);
+            }
+            _ => panic!("comments are expected"),
+        }
+        let thread = Request::Comments(CommentRequest::Thread(1001));
+        match served.request(thread).await {
+            Message::Comments {
+                thread,
+                opened,
+                update,
+                ..
+            } => {
+                assert!(!opened);
+                assert_eq!(thread, 1001);
+                assert_eq!(update.items()[0].id, 1006);
+            }
+            _ => panic!("replies are expected"),
+        }
+        // the reply is answered with the comment to display without querying it back
+        let send = Request::Comments(CommentRequest::Send {
+            text: "[id1234|Ivan], thanks".to_string(),
+            reply_to: 1002,
+            thread: 1001,
+            photos: Vec::new(),
+        });
+        match served.request(send).await {
+            Message::CommentSent {
+                post: commented,
+                reply_to,
+                comment: Some(comment),
+            } => {
+                assert!(commented == post);
+                assert_eq!(reply_to, 1002);
+                assert_eq!((comment.id, comment.thread_id), (1009, 1001));
+                assert_eq!(comment.from_id, 184946538);
+                assert_eq!(
+                    comment.text,
+                    r#"<a href="https://vk.com/id1234">Ivan</a>, thanks"#
+                );
+            }
+            _ => panic!("sent comment is expected"),
+        }
+        served.stop().await;
+    }
+
+    #[tokio::test]
+    async fn serve_source_history() {
+        let storage = Arc::new(Storage::for_test("wall"));
+        let api = FixtureApi::new()
+            .with_profile(184946538, "Test", "User")
+            .with_walls_from_dir("resources/tests/wall");
+        let mut served = Served::start(Arc::new(api), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        let mut received = 0;
+        for restart in &[true, false, false] {
+            let request = Request::SourceHistory {
+                source_id: -40316705,
+                restart: *restart,
+            };
+            match served.request(request).await {
+                Message::SourceNews { source_id, update } => {
+                    assert_eq!(source_id, -40316705);
+                    received += update.items().len();
+                }
+                _ => panic!("posts of the wall are expected"),
+            }
+        }
+        // the whole wall fits the default page, then the wall is over
+        assert_eq!(received, 82);
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn restore_stored_news() {
+        let storage = Arc::new(Storage::for_test("restore"));
+        let api = test_api();
+        let stored = NewsUpdate::new_async(
+            &api.newsfeed_get(rvk::Params::new()).await.unwrap(),
+            &storage,
+        )
+        .await;
+        assert!(storage.append_news_async(stored.items()).await.is_ok());
+
+        let mut served = Served::start(api, "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        // stored news come first
+        match served.recv().await {
+            Message::News(update) => {
+                assert_eq!(update.items().len(), stored.items().len());
+                assert!(update
+                    .items()
+                    .iter()
+                    .all(|item| stored.items().iter().any(|s| s.id() == item.id())));
+            }
+            _ => panic!("restored news are expected"),
+        }
+        // then the more recent ones
+        match served.recv().await {
+            Message::News(update) => assert_replayed(&update, NEWS_NEXT),
+            _ => panic!("news are expected"),
+        }
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn refresh_on_demand() {
+        let storage = Arc::new(Storage::for_test("refresh"));
+        let mut served = Served::start(test_api(), "184946538", Settings::default(), storage);
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        assert!(matches!(served.recv().await, Message::News(_)));
+        assert!(matches!(served.recv().await, Message::NewsSources(_)));
+        // the next news come immediately instead of in a minute
+        served.send(Request::NewsNext).await;
+        assert_replayed(&served.recv_news().await, NEWS_NEXT);
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_on_revoked_token() {
+        let storage = Arc::new(Storage::for_test("reauth"));
+        let api = test_api();
+        let mut served = Served::start(
+            api.clone(),
+            "184946538",
+            Settings::default(),
+            storage.clone(),
+        );
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        assert!(matches!(served.recv().await, Message::News(_)));
+        assert!(matches!(served.recv().await, Message::NewsSources(_)));
+
+        api.revoke_token();
+        served.send(Request::NewsNext).await;
+        match served.recv().await {
+            Message::Auth(_, tx_response) => {
+                let auth = AuthResponse::new("5678", "184946538");
+                assert!(tx_response.send(auth).is_ok());
+            }
+            _ => panic!("re-authentication is expected"),
+        }
+        // polling is resumed with the new token
+        assert_replayed(&served.recv_news().await, NEWS_NEXT);
+        // and the new token is stored
+        let accounts = storage.load_accounts_async().await.ok().unwrap();
+        let stored = accounts.find("184946538").expect("account is not stored");
+        assert_eq!(stored.auth.get_access_token(), "5678");
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn grant_scopes_on_access_denied() {
+        let storage = Arc::new(Storage::for_test("scopes"));
+        let api = test_api();
+        let mut settings = Settings::default();
+        settings.scopes = vec!["offline".to_string()];
+        let mut served = Served::start(api.clone(), "184946538", settings, storage.clone());
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        assert!(matches!(served.recv().await, Message::News(_)));
+        assert!(matches!(served.recv().await, Message::NewsSources(_)));
+
+        api.deny_access();
+        served.send(Request::NewsNext).await;
+        // user is asked to grant the scopes news require
+        match served.recv().await {
+            Message::Auth(access, tx_response) => {
+                assert_eq!(access.get_required(), ["wall", "friends"]);
+                assert!(access
+                    .get_auth_uri()
+                    .contains("scope=offline,wall,friends&"));
+                let auth = AuthResponse::new("5678", "184946538");
+                assert!(tx_response.send(auth).is_ok());
+            }
+            _ => panic!("granting access is expected"),
+        }
+        assert_replayed(&served.recv_news().await, NEWS_NEXT);
+        // the granted scopes are requested on the next logins
+        let accounts = storage.load_accounts_async().await.ok().unwrap();
+        let stored = accounts.find("184946538").expect("account is not stored");
+        assert_eq!(stored.scopes, ["wall", "friends"]);
+        served.worker.abort();
+    }
+
+    #[tokio::test]
+    async fn log_out_and_forget_account() {
+        let root = Storage::for_test("logout");
+        let storage: SharedStorage = Arc::new(root.for_account("184946538"));
+        let mut accounts = Accounts::default();
+        accounts.upsert(AuthResponse::new("1234", "184946538"));
+        assert!(storage.save_accounts_async(&accounts).await.is_ok());
+        let api = test_api();
+        let mut served = Served::start(
+            api.clone(),
+            "184946538",
+            Settings::default(),
+            storage.clone(),
+        );
+        assert!(matches!(served.recv().await, Message::OwnInfo(_)));
+        assert!(matches!(served.recv().await, Message::News(_)));
+        served.send(Request::Logout).await;
+        loop {
+            match served.recv().await {
+                Message::LoggedOut => break,
+                Message::NewsSources(_) => continue,
+                _ => panic!("logging out is expected"),
+            }
+        }
+        // serving stops to authenticate anew
+        assert!(matches!(served.stopped().await, Some((Target::Current, _))));
+        // the session has ended and nothing is left of the account
+        assert!(api.is_revoked());
+        let accounts = storage.load_accounts_async().await.ok().unwrap();
+        assert!(accounts.find("184946538").is_none());
+        assert!(accounts.current.is_none());
+        assert!(storage.load_news_async(10).await.ok().unwrap().is_empty());
+    }
+}
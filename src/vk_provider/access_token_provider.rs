@@ -1,20 +1,20 @@
+use crate::settings::Settings;
 use chrono::{DateTime, Duration, Local, TimeZone};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{fmt, str::FromStr};
 
+mod loopback;
+pub use loopback::LoopbackError;
+
 const AUTH_URI: &str = "https://oauth.vk.com/authorize";
-const AUTH_PARAMS: [(&str, &str); 6] = [
-    ("client_id", "7720259"),
+// client_id and scope are set in settings
+const AUTH_PARAMS: [(&str, &str); 4] = [
     ("display", "page"),
     ("redirect_uri", "https://oauth.vk.com/blank.html"),
-    (
-        "scope",
-        "offline,friends,groups,photos,audio,video,stories,status,notes,wall",
-    ), // "friends" is possible too
     ("response_type", "token"),
     ("v", rvk::API_VERSION),
 ];
-const IDX_REDIRECT_URI: usize = 2;
+const IDX_REDIRECT_URI: usize = 1;
 
 fn datetime_deserializer<'de, D>(de: D) -> Result<Option<DateTime<Local>>, D::Error>
 where
@@ -55,16 +55,29 @@ pub struct AuthResponse {
 }
 
 impl AuthResponse {
+    /// Creates auth with the token which does not expire
+    pub fn new(access_token: &str, user_id: &str) -> Self {
+        AuthResponse {
+            access_token: access_token.to_string(),
+            expires_on: None,
+            user_id: user_id.to_string(),
+        }
+    }
+
     pub fn get_access_token(&self) -> &str {
         &self.access_token
     }
 
-    // not decide yet how to use it
-    #[allow(dead_code)]
     pub fn get_expires_on(&self) -> Option<DateTime<Local>> {
         self.expires_on
     }
 
+    /// Returns true if the token has expired, so re-authentication is required
+    pub fn is_expired(&self) -> bool {
+        self.get_expires_on()
+            .map_or(false, |expires_on| expires_on <= Local::now())
+    }
+
     pub fn get_user_id(&self) -> &str {
         &self.user_id
     }
@@ -185,23 +198,80 @@ impl fmt::Display for AuthResponseParseError {
     }
 }
 
-pub struct AccessTokenProvider;
+/// Composes authorization URI of the application with the access scopes to request
+#[derive(Clone)]
+pub struct AccessTokenProvider {
+    client_id: String,
+    scopes: Vec<String>,
+    // scopes required by the feature user is asked to grant
+    required: Vec<String>,
+}
 
 impl AccessTokenProvider {
-    pub fn get_auth_uri() -> String {
+    pub fn new(settings: &Settings) -> Self {
+        AccessTokenProvider {
+            client_id: settings.client_id.clone(),
+            scopes: settings.scopes.clone(),
+            required: Vec::new(),
+        }
+    }
+
+    /// Returns provider requesting the scopes granted before in addition to the configured ones
+    pub fn with_granted(mut self, scopes: &[String]) -> Self {
+        for scope in scopes {
+            if !self.scopes.contains(scope) {
+                self.scopes.push(scope.clone());
+            }
+        }
+        self
+    }
+
+    /// Returns provider requesting the scopes the feature requires in addition to the current ones
+    pub fn with_required(mut self, scopes: &[&str]) -> Self {
+        for scope in scopes {
+            if !self.scopes.iter().any(|s| s == scope) {
+                self.scopes.push(scope.to_string());
+            }
+            self.required.push(scope.to_string());
+        }
+        self
+    }
+
+    /// Returns scopes the feature requires if user is asked to grant them
+    pub fn get_required(&self) -> &[String] {
+        &self.required
+    }
+
+    pub fn get_auth_uri(&self) -> String {
+        self.build_auth_uri(AUTH_PARAMS[IDX_REDIRECT_URI].1)
+    }
+
+    fn build_auth_uri(&self, redirect_uri: &str) -> String {
         let mut uri = AUTH_URI.to_string();
-        uri.push('?');
+        uri.push_str("?client_id=");
+        uri.push_str(&self.client_id);
+        uri.push_str("&scope=");
+        uri.push_str(&self.scopes.join(","));
         for (n, (name, value)) in AUTH_PARAMS.iter().enumerate() {
-            if n > 0 {
-                uri.push('&');
-            }
+            uri.push('&');
             uri.push_str(name);
             uri.push('=');
-            uri.push_str(value);
+            uri.push_str(if n == IDX_REDIRECT_URI {
+                redirect_uri
+            } else {
+                value
+            });
         }
         uri
     }
 
+    /// Returns URI to authenticate one more account:
+    /// vk.com asks to login instead of reusing the session of the account logged in the browser
+    pub fn get_add_account_uri(&self) -> String {
+        self.get_auth_uri() + "&revoke=1"
+    }
+
+    #[cfg(feature = "webkit")]
     pub fn is_auth_succeeded_uri(uri: &str) -> bool {
         if let Some(pos) = uri.find('#') {
             let (url, _) = uri.split_at(pos);
@@ -273,3 +343,24 @@ fn timestamp_conversion() {
     let ts_to = dt.timestamp();
     assert_eq!(ts_from - ts_to, 0);
 }
+
+#[test]
+fn auth_uri_scopes() {
+    let mut settings = Settings::default();
+    settings.client_id = "1234".to_string();
+    settings.scopes = vec!["offline".to_string(), "wall".to_string()];
+    let provider = AccessTokenProvider::new(&settings);
+    let uri = provider.get_auth_uri();
+    assert!(uri.contains("client_id=1234&"));
+    assert!(uri.contains("scope=offline,wall&"));
+    assert!(uri.contains("redirect_uri=https://oauth.vk.com/blank.html"));
+    assert!(provider.get_required().is_empty());
+    // granted scopes are requested again, the required one is added
+    let provider = provider
+        .with_granted(&["docs".to_string()])
+        .with_required(&["wall", "messages"]);
+    assert!(provider
+        .get_auth_uri()
+        .contains("scope=offline,wall,docs,messages&"));
+    assert_eq!(provider.get_required(), ["wall", "messages"]);
+}
@@ -0,0 +1,219 @@
+//! Login in the system browser, offered along with the embedded one if there is any:
+//! the browser is redirected to the loopback interface, or user pastes the address it ends at
+use super::{AccessTokenProvider, AuthResponse, AuthResponseParseError};
+#[cfg(test)]
+use crate::settings::Settings;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, thread, time};
+
+// any free port on the loopback interface
+const LOOPBACK_ADDR: &str = "127.0.0.1:0";
+// the token is passed in URI fragment which browser never sends to the server,
+// so the page the browser is redirected to sends the fragment back as the query
+const LOOPBACK_REDIRECT_PAGE: &str = "<html><body><script>\
+    location.replace('/token?' + location.hash.substring(1));\
+    </script></body></html>";
+const LOOPBACK_DONE_PAGE: &str =
+    "<html><body>gvk has been authorized, the page might be closed</body></html>";
+const LOOPBACK_FAILED_PAGE: &str = "<html><body>gvk has not been authorized</body></html>";
+const LOOPBACK_TOKEN_PATH: &str = "/token?";
+// period to test if waiting has been cancelled
+const LOOPBACK_POLL_MSEC: u64 = 200;
+// a maximal time to read request from the browser
+const LOOPBACK_READ_TIMEOUT_SEC: u64 = 5;
+const LOOPBACK_MAX_REQUEST: usize = 16 * 1024;
+
+#[derive(Debug)]
+pub enum LoopbackError {
+    // listening or talking to the browser failed
+    Io(String),
+    // user has not granted access
+    Denied(String),
+    // browser has passed unexpected response
+    Parse(AuthResponseParseError),
+    // waiting has been cancelled
+    Cancelled,
+}
+
+impl fmt::Display for LoopbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed receiving redirect from browser: {}", err),
+            Self::Denied(err) => write!(f, "access is not granted: {}", err),
+            Self::Parse(err) => write!(f, "failed parsing redirect from browser: {}", err),
+            Self::Cancelled => write!(f, "waiting for browser has been cancelled"),
+        }
+    }
+}
+
+impl AccessTokenProvider {
+    /// Starts listening on the loopback interface to capture the redirect of the system browser,
+    /// this way the login does not require the embedded browser
+    pub fn listen_loopback(&self) -> io::Result<LoopbackListener> {
+        let listener = TcpListener::bind(LOOPBACK_ADDR)?;
+        listener.set_nonblocking(true)?;
+        let redirect_uri = format!("http://{}/", listener.local_addr()?);
+        log::debug!("waiting for browser redirect to {}", &redirect_uri);
+        Ok(LoopbackListener {
+            listener,
+            redirect_uri,
+            provider: self.clone(),
+        })
+    }
+
+    /// Parses the address of the page the browser has been redirected to, pasted by user
+    pub fn parse_pasted_uri(uri: &str) -> Result<AuthResponse, AuthResponseParseError> {
+        uri.trim().parse::<AuthResponse>()
+    }
+}
+
+/// Captures the redirect of the system browser on the loopback interface
+pub struct LoopbackListener {
+    listener: TcpListener,
+    redirect_uri: String,
+    provider: AccessTokenProvider,
+}
+
+impl LoopbackListener {
+    /// Returns URI to open in the system browser
+    pub fn get_auth_uri(&self) -> String {
+        self.provider.build_auth_uri(&self.redirect_uri)
+    }
+
+    /// The same as get_auth_uri() but to authenticate one more account
+    pub fn get_add_account_uri(&self) -> String {
+        self.get_auth_uri() + "&revoke=1"
+    }
+
+    /// Serves the browser until it passes the auth response, blocks the calling thread.
+    /// Returns LoopbackError::Cancelled as soon as cancel is set
+    pub fn wait(&self, cancel: &AtomicBool) -> Result<AuthResponse, LoopbackError> {
+        loop {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(LoopbackError::Cancelled);
+            }
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Some(result) = self.serve_browser(stream) {
+                        return result;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(time::Duration::from_millis(LOOPBACK_POLL_MSEC));
+                }
+                Err(e) => return Err(LoopbackError::Io(format!("{}", e))),
+            }
+        }
+    }
+
+    // answers single request of the browser, returns the result as soon as the browser has passed it
+    fn serve_browser(&self, mut stream: TcpStream) -> Option<Result<AuthResponse, LoopbackError>> {
+        let path = match read_request_path(&mut stream) {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("failed reading request from browser: {}", e);
+                return None;
+            }
+        };
+        let (status, page, result) = if let Some(query) = path.strip_prefix(LOOPBACK_TOKEN_PATH) {
+            // reuse parsing of the redirect URI as if the browser has not moved the fragment to query
+            let redirected = format!("{}#{}", self.redirect_uri, query);
+            match redirected.parse::<AuthResponse>() {
+                Ok(auth) => ("200 OK", LOOPBACK_DONE_PAGE, Some(Ok(auth))),
+                Err(e) => (
+                    "200 OK",
+                    LOOPBACK_FAILED_PAGE,
+                    Some(Err(if query.contains("error=") {
+                        LoopbackError::Denied(query.to_string())
+                    } else {
+                        LoopbackError::Parse(e)
+                    })),
+                ),
+            }
+        } else if path == "/" {
+            ("200 OK", LOOPBACK_REDIRECT_PAGE, None)
+        } else if path.starts_with("/?") && path.contains("error=") {
+            (
+                "200 OK",
+                LOOPBACK_FAILED_PAGE,
+                Some(Err(LoopbackError::Denied(path[2..].to_string()))),
+            )
+        } else {
+            ("404 Not Found", "", None)
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            page.len(),
+            page
+        );
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            log::warn!("failed answering browser: {}", e);
+        }
+        result
+    }
+}
+
+// reads HTTP request head and returns the requested path
+fn read_request_path(stream: &mut TcpStream) -> io::Result<String> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(time::Duration::from_secs(LOOPBACK_READ_TIMEOUT_SEC)))?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < LOOPBACK_MAX_REQUEST {
+        let cnt = stream.read(&mut buf)?;
+        if cnt == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..cnt]);
+    }
+    // e.g. "GET /token?access_token=... HTTP/1.1"
+    String::from_utf8_lossy(&request)
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .map(|path| path.to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed request"))
+}
+
+#[test]
+fn loopback_redirect() {
+    let listener = AccessTokenProvider::new(&Settings::default())
+        .listen_loopback()
+        .unwrap();
+    assert!(listener
+        .get_auth_uri()
+        .contains("redirect_uri=http://127.0.0.1:"));
+    let addr = listener.listener.local_addr().unwrap();
+    // acts as browser
+    let browser = thread::spawn(move || {
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        // vk.com redirects to the loopback with the token in fragment
+        assert!(get("/").contains("location.hash"));
+        assert!(get("/favicon.ico").starts_with("HTTP/1.1 404"));
+        // the page passes the fragment back
+        assert!(get("/token?access_token=1234&expires_in=0&user_id=1").starts_with("HTTP/1.1 200"));
+    });
+    let auth = listener.wait(&AtomicBool::new(false)).unwrap();
+    assert_eq!(auth.get_access_token(), "1234");
+    assert_eq!(auth.get_user_id(), "1");
+    assert!(browser.join().is_ok());
+    // waiting stops on demand
+    assert!(matches!(
+        listener.wait(&AtomicBool::new(true)),
+        Err(LoopbackError::Cancelled)
+    ));
+    // pasted address is parsed the same way
+    assert!(AccessTokenProvider::parse_pasted_uri(
+        " https://oauth.vk.com/blank.html#access_token=1234&expires_in=0&user_id=1\n"
+    )
+    .is_ok());
+}
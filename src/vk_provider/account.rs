@@ -1,5 +1,6 @@
+use super::VkApi;
 use rvk::objects::account::Account as VKAccount;
-use rvk::{methods, objects, APIClient, Params};
+use rvk::Params;
 use std::boxed::Box;
 use std::fmt;
 
@@ -20,9 +21,9 @@ impl fmt::Display for Account {
 pub struct AccountProvider;
 
 impl AccountProvider {
-    pub async fn query_async(api: &APIClient) -> Option<Account> {
+    pub async fn query_async(api: &dyn VkApi) -> Option<Account> {
         let params = Params::new();
-        match methods::account::get_profile_info::<objects::account::Account>(api, params).await {
+        match api.account_get_profile_info(params).await {
             Ok(a) => Some(Account(Box::new(a))),
             Err(e) => {
                 log::error!("failed query account info: {}", e);
@@ -0,0 +1,87 @@
+//! Objects of the messages.* methods, there are no complete ones in rvk,
+//! see <https://vk.com/dev/objects/message> and <https://vk.com/dev/messages.getConversations>
+use rvk::objects::{group::Group, photo::Photo, user::User};
+use serde::Deserialize;
+
+/// Answer to messages.getConversations
+#[derive(Deserialize, Clone)]
+pub struct Conversations {
+    pub count: i64,
+    pub items: Vec<ConversationItem>,
+    pub profiles: Option<Vec<User>>,
+    pub groups: Option<Vec<Group>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ConversationItem {
+    pub conversation: Conversation,
+    pub last_message: Option<ChatMessage>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Conversation {
+    pub peer: Peer,
+    #[serde(default)]
+    pub unread_count: i64,
+    pub chat_settings: Option<ChatSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Peer {
+    // user id, -group id or 2000000000 + chat id
+    pub id: i64,
+    // user, chat, group or email
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChatSettings {
+    pub title: String,
+    pub photo: Option<ChatPhoto>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChatPhoto {
+    pub photo_50: Option<String>,
+}
+
+/// Answer to messages.getHistory
+#[derive(Deserialize, Clone)]
+pub struct History {
+    pub count: i64,
+    pub items: Vec<ChatMessage>,
+    pub profiles: Option<Vec<User>>,
+    pub groups: Option<Vec<Group>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChatMessage {
+    pub id: i64,
+    pub date: i64,
+    pub peer_id: i64,
+    pub from_id: i64,
+    #[serde(default)]
+    pub text: String,
+    // 1 for the message sent by the user
+    #[serde(default)]
+    pub out: u8,
+    pub attachments: Option<Vec<MessageAttachment>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessageAttachment {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub photo: Option<Photo>,
+}
+
+/// Answer to messages.getLongPollServer, <https://vk.com/dev/using_longpoll>
+#[derive(Deserialize, Clone)]
+pub struct LongPollServer {
+    pub key: String,
+    // the address without scheme, e.g. im.vk.com/nim123
+    pub server: String,
+    // number of the last event
+    pub ts: i64,
+}
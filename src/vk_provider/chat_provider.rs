@@ -0,0 +1,182 @@
+use super::chat_objects::{Conversations, History};
+use super::chat_update::{self, ConversationsUpdate, MessagesUpdate};
+use super::VkApi;
+use crate::models::{MessageModel, UserModel};
+use crate::storage::Storage;
+use crate::ui::{ChatRequest, Message};
+use rvk::{error::Result, Params};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// conversations to request at once, up to 200
+const CONVERSATIONS_COUNT: usize = 50;
+// messages to request per page of history, up to 200
+const HISTORY_PAGE_SIZE: usize = 30;
+
+/// <https://vk.com/dev/messages.getConversations>, <https://vk.com/dev/messages.getHistory>,
+/// <https://vk.com/dev/messages.send>
+/// Keeps the oldest received message of every conversation to page its history back
+pub struct ChatProvider {
+    // peer_id -> id of the oldest received message
+    oldest: Mutex<HashMap<i64, i64>>,
+}
+
+impl ChatProvider {
+    pub fn new() -> Self {
+        ChatProvider {
+            oldest: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the most recent conversations with profiles and groups of their peers
+    pub async fn conversations(&self, api: &dyn VkApi) -> Result<Conversations> {
+        let mut params = Params::new();
+        params.insert("count".into(), format!("{}", CONVERSATIONS_COUNT));
+        params.insert("extended".into(), "1".into());
+        params.insert("fields".into(), "photo_50".into());
+        api.messages_get_conversations(params).await
+    }
+
+    /// Returns the most recent page of the conversation history, paging starts anew
+    pub async fn history(&self, api: &dyn VkApi, peer_id: i64) -> Result<History> {
+        let history = api.messages_get_history(history_params(peer_id)).await?;
+        self.set_oldest(peer_id, &history);
+        Ok(history)
+    }
+
+    /// Returns the page of the history preceding the oldest received message,
+    /// the history is empty if there are no more messages
+    pub async fn older(&self, api: &dyn VkApi, peer_id: i64) -> Result<History> {
+        let mut params = history_params(peer_id);
+        if let Some(oldest) = self.get_oldest(peer_id) {
+            // the oldest message itself has already been received
+            params.insert("start_message_id".into(), format!("{}", oldest));
+            params.insert("offset".into(), "1".into());
+        }
+        let history = api.messages_get_history(params).await?;
+        self.set_oldest(peer_id, &history);
+        Ok(history)
+    }
+
+    /// Sends text to the conversation, returns id of the sent message
+    pub async fn send(&self, api: &dyn VkApi, peer_id: i64, text: &str) -> Result<i64> {
+        let mut params = Params::new();
+        params.insert("peer_id".into(), format!("{}", peer_id));
+        params.insert("message".into(), text.into());
+        // protects from sending the same message twice
+        params.insert("random_id".into(), format!("{}", rand::random::<i32>()));
+        api.messages_send(params).await
+    }
+
+    fn get_oldest(&self, peer_id: i64) -> Option<i64> {
+        self.oldest
+            .lock()
+            .ok()
+            .and_then(|oldest| oldest.get(&peer_id).cloned())
+    }
+
+    fn set_oldest(&self, peer_id: i64, history: &History) {
+        if let Some(id) = history.items.iter().map(|message| message.id).min() {
+            if let Ok(mut oldest) = self.oldest.lock() {
+                oldest.insert(peer_id, id);
+            }
+        }
+    }
+}
+
+/// Serves request of the chats page, returns the message to answer UI with
+pub async fn serve_request(
+    request: &ChatRequest,
+    chat: &ChatProvider,
+    vk_api: &dyn VkApi,
+    user_id: &str,
+    own: &UserModel,
+    storage: &Storage,
+) -> Result<Message> {
+    match request {
+        ChatRequest::Conversations => {
+            let conversations = chat.conversations(vk_api).await?;
+            Ok(Message::Conversations(
+                ConversationsUpdate::new_async(&conversations, storage).await,
+            ))
+        }
+        ChatRequest::History(peer_id) => {
+            let history = chat.history(vk_api, *peer_id).await?;
+            Ok(Message::ChatMessages {
+                peer_id: *peer_id,
+                update: MessagesUpdate::new_async(&history, storage).await,
+                older: false,
+            })
+        }
+        ChatRequest::Older(peer_id) => {
+            let history = chat.older(vk_api, *peer_id).await?;
+            Ok(Message::ChatMessages {
+                peer_id: *peer_id,
+                update: MessagesUpdate::new_async(&history, storage).await,
+                older: true,
+            })
+        }
+        ChatRequest::Send { peer_id, text } => {
+            let id = chat.send(vk_api, *peer_id, text).await?;
+            let timestamp = chrono::Utc::now().timestamp();
+            // the sent message is displayed without querying it back
+            let message = MessageModel {
+                id,
+                peer_id: *peer_id,
+                from_id: user_id.parse::<i64>().unwrap_or(0),
+                timestamp,
+                out: true,
+                author: own.name.clone(),
+                avatar: own.image.clone(),
+                datetime: chat_update::format_datetime(timestamp),
+                text: crate::utils::process_text(text),
+                photos: None,
+            };
+            Ok(Message::ChatMessages {
+                peer_id: *peer_id,
+                update: MessagesUpdate::from_items(vec![message]),
+                older: false,
+            })
+        }
+    }
+}
+
+fn history_params(peer_id: i64) -> Params {
+    let mut params = Params::new();
+    params.insert("peer_id".into(), format!("{}", peer_id));
+    params.insert("count".into(), format!("{}", HISTORY_PAGE_SIZE));
+    params.insert("extended".into(), "1".into());
+    params.insert("fields".into(), "photo_50".into());
+    params
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::FixtureApi;
+
+    #[tokio::test]
+    async fn page_history_back() {
+        let api = FixtureApi::new().with_messages_from_dir("resources/tests/messages");
+        let chat = ChatProvider::new();
+        let peer_id = 2000000001;
+        let mut received: Vec<i64> = chat
+            .history(&api, peer_id)
+            .await
+            .ok()
+            .unwrap()
+            .items
+            .iter()
+            .map(|message| message.id)
+            .collect();
+        loop {
+            let older = chat.older(&api, peer_id).await.ok().unwrap();
+            if older.items.is_empty() {
+                break;
+            }
+            received.extend(older.items.iter().map(|message| message.id));
+        }
+        // every message once, the most recent first
+        assert_eq!(received, vec![105, 104, 103, 102, 101]);
+    }
+}
@@ -0,0 +1,249 @@
+//! Produces chat model objects from the answers of messages.* methods the same way NewsUpdate does for news:
+//! * ConversationsUpdate - is constructed from Conversations and provides ConversationModel objects,
+//!       the title and avatar of the conversation are taken from the chat settings, the user or the group
+//! * MessagesUpdate - is constructed from History and provides MessageModel objects in the same order,
+//!       i.e. the most recent first, texts are processed like texts of news, photos are extracted from attachments
+use super::chat_objects::{ChatMessage, Conversations, History};
+use super::news_update::select_photo;
+use crate::models::{ConversationModel, MessageModel, Photo};
+use crate::storage::Storage;
+use crate::utils::{local_from_timestamp, process_text};
+use crate::vk_provider;
+use rvk::objects::{group::Group, user::User};
+use std::iter::IntoIterator;
+
+pub struct ConversationsUpdate {
+    items: Vec<ConversationModel>,
+}
+
+impl ConversationsUpdate {
+    pub async fn new_async(conversations: &Conversations, storage: &Storage) -> Self {
+        let users = conversations.profiles.as_deref().unwrap_or(&[]);
+        let groups = conversations.groups.as_deref().unwrap_or(&[]);
+        let mut items = Vec::with_capacity(conversations.items.len());
+        for src in &conversations.items {
+            let peer_id = src.conversation.peer.id;
+            let (title, avatar) = if let Some(chat) = &src.conversation.chat_settings {
+                let photo = chat
+                    .photo
+                    .as_ref()
+                    .and_then(|photo| photo.photo_50.clone())
+                    .unwrap_or_default();
+                (chat.title.clone(), get_avatar(&photo, storage).await)
+            } else {
+                find_author(peer_id, users, groups, storage).await
+            };
+            let (timestamp, last_message) = match &src.last_message {
+                Some(message) => (message.date, summary(message)),
+                None => (0, String::new()),
+            };
+            items.push(ConversationModel {
+                peer_id,
+                timestamp,
+                title,
+                avatar,
+                last_message,
+                datetime: format_datetime(timestamp),
+                unread: src.conversation.unread_count,
+            });
+        }
+        ConversationsUpdate { items }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[ConversationModel] {
+        &self.items
+    }
+}
+
+impl IntoIterator for ConversationsUpdate {
+    type Item = ConversationModel;
+    type IntoIter = std::vec::IntoIter<ConversationModel>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+pub struct MessagesUpdate {
+    items: Vec<MessageModel>,
+}
+
+impl MessagesUpdate {
+    pub async fn new_async(history: &History, storage: &Storage) -> Self {
+        let users = history.profiles.as_deref().unwrap_or(&[]);
+        let groups = history.groups.as_deref().unwrap_or(&[]);
+        let mut items = Vec::with_capacity(history.items.len());
+        for src in &history.items {
+            let (author, avatar) = find_author(src.from_id, users, groups, storage).await;
+            items.push(MessageModel {
+                id: src.id,
+                peer_id: src.peer_id,
+                from_id: src.from_id,
+                timestamp: src.date,
+                out: src.out != 0,
+                author,
+                avatar,
+                datetime: format_datetime(src.date),
+                text: process_text(&src.text),
+                photos: extract_photos(src, storage).await,
+            });
+        }
+        MessagesUpdate { items }
+    }
+
+    /// Constructs update from already prepared items, e.g. the message just sent
+    pub fn from_items(items: Vec<MessageModel>) -> Self {
+        MessagesUpdate { items }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[MessageModel] {
+        &self.items
+    }
+}
+
+impl IntoIterator for MessagesUpdate {
+    type Item = MessageModel;
+    type IntoIter = std::vec::IntoIter<MessageModel>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+pub(super) fn format_datetime(timestamp: i64) -> String {
+    format!(
+        "{}",
+        local_from_timestamp(timestamp).format("%d.%m.%Y %H:%M")
+    )
+}
+
+// returns name and avatar of the user (positive id) or the group (negative id), empty if not found
+pub(super) async fn find_author(
+    id: i64,
+    users: &[User],
+    groups: &[Group],
+    storage: &Storage,
+) -> (String, String) {
+    if id > 0 {
+        if let Some(user) = users.iter().find(|u| u.id == id) {
+            let avatar =
+                get_avatar(vk_provider::User::get_small_photo(user).as_str(), storage).await;
+            return (vk_provider::User::get_full_name(user), avatar);
+        }
+    } else if let Some(grp) = groups.iter().find(|g| g.id == -id) {
+        return (grp.name.clone(), get_avatar(&grp.photo_50, storage).await);
+    }
+    (String::new(), String::new())
+}
+
+// empty if failed getting
+async fn get_avatar(uri: &str, storage: &Storage) -> String {
+    storage.get_file(uri, "").await.unwrap_or_default()
+}
+
+// text of the message to show in the list of conversations, attachments are named if there is no text
+fn summary(message: &ChatMessage) -> String {
+    if !message.text.is_empty() {
+        return process_text(&message.text);
+    }
+    match &message.attachments {
+        Some(attachments) if !attachments.is_empty() => {
+            let types: Vec<&str> = attachments.iter().map(|a| a.type_.as_str()).collect();
+            format!("[{}]", types.join(", "))
+        }
+        _ => String::new(),
+    }
+}
+
+async fn extract_photos(message: &ChatMessage, storage: &Storage) -> Option<Vec<Photo>> {
+    let mut result = Vec::new();
+    if let Some(attachments) = &message.attachments {
+        for attachment in attachments {
+            if let Some(src_photo) = &attachment.photo {
+                if let Some(photo) = select_photo(src_photo, result.len(), storage).await {
+                    result.push(photo);
+                }
+            }
+        }
+    }
+    if !result.is_empty() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::{read_dir, read_to_string};
+
+    #[test]
+    fn deserialize_messages() {
+        for file in read_dir("resources/tests/messages").unwrap() {
+            let file_name = file.unwrap().path().to_string_lossy().to_string();
+            let json = read_to_string(&file_name).unwrap();
+            let parsed = if file_name.ends_with("conversations.json") {
+                serde_json::from_str::<Conversations>(&json).map(|c| c.items.is_empty())
+            } else {
+                serde_json::from_str::<History>(&json).map(|h| h.items.is_empty())
+            };
+            match parsed {
+                Ok(is_empty) => assert!(!is_empty),
+                Err(e) => panic!("test failed for {}: {}", file_name, e),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn convert_conversations() {
+        let json = read_to_string("resources/tests/messages/conversations.json").unwrap();
+        let conversations = serde_json::from_str::<Conversations>(&json).unwrap();
+        let update =
+            ConversationsUpdate::new_async(&conversations, &Storage::for_test("chat")).await;
+        let items = update.items();
+        assert_eq!(items.len(), 3);
+        // chat
+        assert_eq!(items[0].peer_id, 2000000001);
+        assert_eq!(items[0].title, "gvk team");
+        assert_eq!(items[0].unread, 2);
+        assert_eq!(
+            items[0].last_message,
+            r#"see <a href="https://vk.com/dev/messages.getHistory">https://vk.com/dev/messages.getHistory</a>"#
+        );
+        // user, the message has no text
+        assert_eq!(items[1].title, "Администрация ВКонтакте ");
+        assert_eq!(items[1].last_message, "[photo]");
+        // group
+        assert_eq!(items[2].peer_id, -108468);
+        assert_eq!(items[2].title, "КиноПоиск");
+        assert_eq!(items[2].last_message, "Hello &amp; welcome");
+    }
+
+    #[tokio::test]
+    async fn convert_history() {
+        let json = read_to_string("resources/tests/messages/history.json").unwrap();
+        let history = serde_json::from_str::<History>(&json).unwrap();
+        let update = MessagesUpdate::new_async(&history, &Storage::for_test("chat")).await;
+        let items = update.items();
+        assert_eq!(items.len(), 5);
+        assert_eq!(items[0].id, 105);
+        assert_eq!(items[0].author, "Ivan Petrov");
+        assert!(!items[0].out);
+        assert_eq!(items[1].author, "Anna Sidorova");
+        assert!(items[1].out);
+        assert_eq!(items[3].text, "ok &lt;3");
+        // the order is kept, the most recent first
+        assert!(items
+            .windows(2)
+            .all(|pair| pair[0].timestamp > pair[1].timestamp));
+    }
+}
@@ -0,0 +1,44 @@
+//! Objects of wall.getComments, there are no complete ones in rvk,
+//! see <https://vk.com/dev/wall.getComments> and <https://vk.com/dev/objects/comment>
+use rvk::objects::{attachment::WallAttachment, group::Group, user::User};
+use serde::Deserialize;
+
+/// Answer to wall.getComments with extended=1
+#[derive(Deserialize, Clone)]
+pub struct Comments {
+    // every comment of the post including replies
+    #[serde(default)]
+    pub count: i64,
+    pub items: Vec<Comment>,
+    pub profiles: Option<Vec<User>>,
+    pub groups: Option<Vec<Group>>,
+    // comments of the requested level: the top-level ones or replies of the thread
+    #[serde(default)]
+    pub current_level_count: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Comment {
+    pub id: i64,
+    // user id or -group id, zero if the comment is deleted
+    #[serde(default)]
+    pub from_id: i64,
+    pub date: i64,
+    #[serde(default)]
+    pub text: String,
+    // the author of the comment replied to
+    pub reply_to_user: Option<i64>,
+    pub reply_to_comment: Option<i64>,
+    pub attachments: Option<Vec<WallAttachment>>,
+    // replies to the top-level comment, at most thread_items_count of them
+    pub thread: Option<Thread>,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Thread {
+    pub count: i64,
+    #[serde(default)]
+    pub items: Vec<Comment>,
+}
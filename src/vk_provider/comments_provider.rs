@@ -0,0 +1,324 @@
+//! Comments of a single post, <https://vk.com/dev/wall.getComments>
+//!
+//! Top-level comments are paged from the oldest one with a few first replies of every thread,
+//! the rest of the thread is paged on demand of user
+use super::chat_update;
+use super::comments_update::CommentsUpdate;
+use super::photo_upload::{PhotoUploader, UploadError};
+use super::VkApi;
+use crate::models::{CommentModel, NewsItemId, Photo, UserModel};
+use crate::storage::Storage;
+use crate::ui::{CommentRequest, Message};
+use rvk::{error::Result, Params};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// top-level comments to request per page, up to 100
+const COMMENTS_PAGE_SIZE: usize = 20;
+// replies to request per page of the thread, up to 100
+const THREAD_PAGE_SIZE: usize = 20;
+// replies received along with the top-level comment, up to 10
+const THREAD_ITEMS_COUNT: usize = 2;
+
+/// Keeps the position in the comments of the post opened last time
+pub struct CommentsProvider {
+    position: Mutex<Position>,
+}
+
+#[derive(Default)]
+struct Position {
+    // the post opened, comments are paged of it only
+    post: Option<NewsItemId>,
+    // a number of top-level comments already received
+    offset: usize,
+    // a number of replies already received by id of the top-level comment
+    replies: HashMap<i64, usize>,
+}
+
+impl CommentsProvider {
+    pub fn new() -> Self {
+        CommentsProvider {
+            position: Mutex::new(Position::default()),
+        }
+    }
+
+    /// Returns the first page of comments of the post, paging starts anew
+    pub async fn open(
+        &self,
+        api: &dyn VkApi,
+        storage: &Storage,
+        post: &NewsItemId,
+    ) -> Result<CommentsUpdate> {
+        if let Ok(mut position) = self.position.lock() {
+            *position = Position {
+                post: Some(post.clone()),
+                ..Position::default()
+            };
+        }
+        self.more(api, storage).await
+    }
+
+    /// Returns the page of top-level comments following the received ones,
+    /// the update is empty if there are no more comments
+    pub async fn more(&self, api: &dyn VkApi, storage: &Storage) -> Result<CommentsUpdate> {
+        let (post, offset) = self.get_position(|position| position.offset);
+        let mut params = params(post.as_ref(), offset, COMMENTS_PAGE_SIZE);
+        params.insert("thread_items_count".into(), THREAD_ITEMS_COUNT.to_string());
+        let comments = api.wall_get_comments(params).await?;
+        let received = offset + comments.items.len();
+        self.set_position(post.as_ref(), |position| {
+            position.offset = received;
+            for comment in &comments.items {
+                let replies = comment
+                    .thread
+                    .as_ref()
+                    .map_or(0, |thread| thread.items.len());
+                position.replies.insert(comment.id, replies);
+            }
+        });
+        let more = (received as i64) < comments.current_level_count;
+        Ok(CommentsUpdate::new_async(&comments, 0, more, storage).await)
+    }
+
+    /// Returns the page of replies to the top-level comment following the received ones
+    pub async fn thread(
+        &self,
+        api: &dyn VkApi,
+        storage: &Storage,
+        comment_id: i64,
+    ) -> Result<CommentsUpdate> {
+        let (post, offset) =
+            self.get_position(|position| position.replies.get(&comment_id).cloned().unwrap_or(0));
+        let mut params = params(post.as_ref(), offset, THREAD_PAGE_SIZE);
+        params.insert("comment_id".into(), comment_id.to_string());
+        let comments = api.wall_get_comments(params).await?;
+        let received = offset + comments.items.len();
+        self.set_position(post.as_ref(), |position| {
+            position.replies.insert(comment_id, received);
+        });
+        let more = (received as i64) < comments.current_level_count;
+        Ok(CommentsUpdate::new_async(&comments, comment_id, more, storage).await)
+    }
+
+    /// Comments the opened post or replies to the comment if reply_to is not zero,
+    /// the photos are uploaded to the user's wall first, returns id of the created comment
+    pub async fn send(
+        &self,
+        api: &dyn VkApi,
+        uploader: &PhotoUploader,
+        text: &str,
+        reply_to: i64,
+        photos: &[String],
+    ) -> std::result::Result<i64, UploadError> {
+        let (owner_id, post_id) = self
+            .post()
+            .map_or((0, 0), |post| (post.source_id, post.post_id));
+        let attachments = uploader.upload(api, 0, photos).await?;
+        let mut params = Params::new();
+        params.insert("owner_id".into(), owner_id.to_string());
+        params.insert("post_id".into(), post_id.to_string());
+        params.insert("message".into(), text.to_string());
+        if reply_to != 0 {
+            params.insert("reply_to_comment".into(), reply_to.to_string());
+        }
+        if !attachments.is_empty() {
+            params.insert("attachments".into(), attachments.join(","));
+        }
+        Ok(api.wall_create_comment(params).await?)
+    }
+
+    /// Returns the opened post if any
+    pub fn post(&self) -> Option<NewsItemId> {
+        self.position
+            .lock()
+            .ok()
+            .and_then(|position| position.post.clone())
+    }
+
+    // returns the opened post with the offset to page from
+    fn get_position(&self, offset: impl FnOnce(&Position) -> usize) -> (Option<NewsItemId>, usize) {
+        self.position
+            .lock()
+            .map(|position| (position.post.clone(), offset(&position)))
+            .unwrap_or((None, 0))
+    }
+
+    // updates the position unless another post has been opened meanwhile
+    fn set_position(&self, post: Option<&NewsItemId>, update: impl FnOnce(&mut Position)) {
+        if let Ok(mut position) = self.position.lock() {
+            if position.post.as_ref() == post {
+                update(&mut position);
+            }
+        }
+    }
+}
+
+/// Serves request of the post page, returns the message to answer UI with,
+/// there is nothing to answer if no post has been opened
+pub async fn serve_request(
+    request: &CommentRequest,
+    comments: &CommentsProvider,
+    vk_api: &dyn VkApi,
+    storage: &Storage,
+) -> Result<Option<Message>> {
+    let (update, thread) = match request {
+        CommentRequest::Open(post) => (comments.open(vk_api, storage, post).await?, 0),
+        CommentRequest::More if comments.post().is_some() => {
+            (comments.more(vk_api, storage).await?, 0)
+        }
+        CommentRequest::Thread(comment_id) if comments.post().is_some() => (
+            comments.thread(vk_api, storage, *comment_id).await?,
+            *comment_id,
+        ),
+        _ => return Ok(None),
+    };
+    Ok(comments.post().map(|post| Message::Comments {
+        post,
+        thread,
+        opened: matches!(request, CommentRequest::Open(_)),
+        update,
+    }))
+}
+
+/// Returns the comment user has sent to display it without querying it back,
+/// the attached photos are shown from the local files
+pub fn sent_comment(
+    id: i64,
+    text: &str,
+    thread: i64,
+    photos: &[String],
+    user_id: &str,
+    own: &UserModel,
+) -> CommentModel {
+    let timestamp = chrono::Utc::now().timestamp();
+    let photos: Vec<Photo> = photos
+        .iter()
+        .map(|file| Photo {
+            uri: file.clone(),
+            text: String::new(),
+            source: String::new(),
+        })
+        .collect();
+    CommentModel {
+        id,
+        from_id: user_id.parse::<i64>().unwrap_or(0),
+        thread_id: thread,
+        thread_count: 0,
+        timestamp,
+        author: own.name.clone(),
+        avatar: own.image.clone(),
+        reply_to: String::new(),
+        datetime: chat_update::format_datetime(timestamp),
+        text: crate::utils::process_text(text),
+        photos: if photos.is_empty() {
+            None
+        } else {
+            Some(photos)
+        },
+    }
+}
+
+fn params(post: Option<&NewsItemId>, offset: usize, count: usize) -> Params {
+    let (owner_id, post_id) = post.map_or((0, 0), |post| (post.source_id, post.post_id));
+    let mut params = Params::new();
+    params.insert("owner_id".into(), owner_id.to_string());
+    params.insert("post_id".into(), post_id.to_string());
+    params.insert("offset".into(), offset.to_string());
+    params.insert("count".into(), count.to_string());
+    // the oldest comments first like vk.com shows them
+    params.insert("sort".into(), "asc".into());
+    params.insert("need_likes".into(), "0".into());
+    params.insert("extended".into(), "1".into());
+    params.insert("fields".into(), "photo_50".into());
+    params
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::stand_in::photo_file;
+    use crate::vk_provider::FixtureApi;
+
+    #[tokio::test]
+    async fn page_comments_and_threads() {
+        let api = FixtureApi::new().with_comments_from_dir("resources/tests/comments");
+        let storage = Storage::for_test("comments");
+        let post = NewsItemId {
+            source_id: -40316705,
+            post_id: 38231954,
+            itemtype: "post".to_string(),
+            content_id: 0,
+        };
+        let comments = CommentsProvider::new();
+        let first = comments.open(&api, &storage, &post).await.ok().unwrap();
+        // top-level comments are followed by the first replies of their threads
+        let ids: Vec<(i64, i64)> = first
+            .items()
+            .iter()
+            .map(|comment| (comment.id, comment.thread_id))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                (1001, 0),
+                (1002, 1001),
+                (1003, 1001),
+                (1004, 0),
+                (1005, 0),
+                (1007, 1005),
+                (1008, 0)
+            ]
+        );
+        assert!(!first.has_more());
+        // the rest of the thread follows the replies received
+        let thread = comments.thread(&api, &storage, 1001).await.ok().unwrap();
+        let ids: Vec<i64> = thread.items().iter().map(|comment| comment.id).collect();
+        assert_eq!(ids, vec![1006]);
+        assert_eq!(thread.items()[0].thread_id, 1001);
+        assert!(!thread.has_more());
+    }
+
+    #[tokio::test]
+    async fn send_comments_and_replies() {
+        let api = FixtureApi::new()
+            .with_profile(4321, "Anna", "Sidorova")
+            .with_comments_from_dir("resources/tests/comments");
+        let storage = Storage::for_test("comments");
+        let post = NewsItemId {
+            source_id: -40316705,
+            post_id: 38231954,
+            itemtype: "post".to_string(),
+            content_id: 0,
+        };
+        let uploader = PhotoUploader::new();
+        let comments = CommentsProvider::new();
+        comments.open(&api, &storage, &post).await.ok().unwrap();
+        // the top-level comment follows the received ones
+        let id = comments
+            .send(&api, &uploader, "first!", 0, &[])
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(id, 1009);
+        let more = comments.more(&api, &storage).await.ok().unwrap();
+        assert_eq!(more.items().len(), 1);
+        assert_eq!(more.items()[0].id, 1009);
+        assert_eq!(more.items()[0].author, "Anna Sidorova");
+        assert_eq!(more.items()[0].text, "first!");
+        // the reply to the reply goes to the thread of its top-level comment
+        let id = comments
+            .send(&api, &uploader, "me too", 1003, &[])
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(id, 1010);
+        let thread = comments.thread(&api, &storage, 1001).await.ok().unwrap();
+        let ids: Vec<i64> = thread.items().iter().map(|comment| comment.id).collect();
+        assert_eq!(ids, vec![1006, 1010]);
+        assert_eq!(thread.items()[1].reply_to, "Новости RT на русском");
+        // the photo is not uploaded without the upload server
+        let photo = photo_file("gvk_comment_photo.jpg");
+        let failed = comments.send(&api, &uploader, "look", 0, &[photo]).await;
+        assert!(matches!(failed, Err(UploadError::Api(_))));
+    }
+}
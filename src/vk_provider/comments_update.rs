@@ -0,0 +1,140 @@
+//! Produces comment model objects from the answer of wall.getComments the same way MessagesUpdate does for chats:
+//! top-level comments are followed by the replies of their threads, authors are found in profiles and groups
+//! of the answer, texts are processed like texts of news, photos are extracted from attachments like ones of reposts
+use super::chat_update::{find_author, format_datetime};
+use super::comment_objects::{Comment, Comments};
+use super::news_update::append_from_attachments;
+use crate::models::{CommentModel, Photo};
+use crate::storage::Storage;
+use crate::utils::process_text;
+use rvk::objects::{group::Group, user::User};
+use std::iter::IntoIterator;
+
+pub struct CommentsUpdate {
+    items: Vec<CommentModel>,
+    // the post or the thread has more comments to page
+    more: bool,
+}
+
+impl CommentsUpdate {
+    /// Constructs update from the comments of the level, thread_id is the top-level comment
+    /// the replies belong to or zero for top-level comments
+    pub async fn new_async(
+        comments: &Comments,
+        thread_id: i64,
+        more: bool,
+        storage: &Storage,
+    ) -> Self {
+        let users = comments.profiles.as_deref().unwrap_or(&[]);
+        let groups = comments.groups.as_deref().unwrap_or(&[]);
+        let mut items = Vec::with_capacity(comments.items.len());
+        for src in &comments.items {
+            items.push(convert(src, thread_id, users, groups, storage).await);
+            if let Some(thread) = &src.thread {
+                for reply in &thread.items {
+                    items.push(convert(reply, src.id, users, groups, storage).await);
+                }
+            }
+        }
+        CommentsUpdate { items, more }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn has_more(&self) -> bool {
+        self.more
+    }
+
+    pub fn items(&self) -> &[CommentModel] {
+        &self.items
+    }
+}
+
+impl IntoIterator for CommentsUpdate {
+    type Item = CommentModel;
+    type IntoIter = std::vec::IntoIter<CommentModel>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+async fn convert(
+    src: &Comment,
+    thread_id: i64,
+    users: &[User],
+    groups: &[Group],
+    storage: &Storage,
+) -> CommentModel {
+    let (author, avatar) = find_author(src.from_id, users, groups, storage).await;
+    let reply_to = match src.reply_to_user {
+        Some(id) if id != 0 => find_author(id, users, groups, storage).await.0,
+        _ => String::new(),
+    };
+    // the deleted comment is kept in place while it has replies
+    let text = if src.deleted {
+        "<i>deleted</i>".to_string()
+    } else {
+        process_text(&src.text)
+    };
+    CommentModel {
+        id: src.id,
+        from_id: src.from_id,
+        thread_id,
+        thread_count: src.thread.as_ref().map_or(0, |thread| thread.count),
+        timestamp: src.date,
+        author,
+        avatar,
+        reply_to,
+        datetime: format_datetime(src.date),
+        text,
+        photos: extract_photos(src, storage).await,
+    }
+}
+
+async fn extract_photos(comment: &Comment, storage: &Storage) -> Option<Vec<Photo>> {
+    let mut result = Vec::new();
+    if let Some(attachments) = &comment.attachments {
+        append_from_attachments(&mut result, attachments, storage).await;
+    }
+    if !result.is_empty() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[tokio::test]
+    async fn convert_comments() {
+        let json = read_to_string("resources/tests/comments/-40316705_38231954.json").unwrap();
+        let comments = serde_json::from_str::<Comments>(&json).unwrap();
+        let storage = Storage::for_test("comments-update");
+        let update = CommentsUpdate::new_async(&comments, 0, false, &storage).await;
+        let items = update.items();
+        assert_eq!(items.len(), 8);
+        // top-level comment with the thread
+        assert_eq!(items[0].author, "Ivan Petrov");
+        assert_eq!(items[0].thread_count, 3);
+        // the reply of the user to the user
+        assert_eq!(items[1].thread_id, 1001);
+        assert_eq!(items[1].reply_to, "Ivan Petrov");
+        assert_eq!(
+            items[1].text,
+            r#"<a href="https://vk.com/id1234">Ivan</a>, agree &amp; see <a href="https://vk.com/dev/wall.getComments">https://vk.com/dev/wall.getComments</a>"#
+        );
+        // the reply of the group to the user
+        assert_eq!(items[2].author, "Новости RT на русском");
+        assert_eq!(items[2].reply_to, "Anna Sidorova");
+        // the deleted comment is kept with its thread
+        let deleted = items.iter().find(|c| c.id == 1005).unwrap();
+        assert!(deleted.author.is_empty());
+        assert_eq!(deleted.thread_count, 1);
+    }
+}
@@ -0,0 +1,74 @@
+//! Communities of the user, <https://vk.com/dev/groups.get>, <https://vk.com/dev/groups.join>,
+//! <https://vk.com/dev/groups.leave>
+//!
+//! Unlike friends, communities are requested only when the page is opened, they rarely change
+use super::communities_update::CommunitiesUpdate;
+use super::VkApi;
+use crate::storage::Storage;
+use crate::ui::{CommunityRequest, Message};
+use rvk::{error::Result, Params};
+
+const FIELDS: &str = "members_count,description";
+// groups.get returns at most 1000 communities
+const MAX_COUNT: &str = "1000";
+
+/// Returns every community the user is a member of
+pub async fn query_async(api: &dyn VkApi, storage: &Storage) -> Result<CommunitiesUpdate> {
+    let mut params = Params::new();
+    params.insert("extended".into(), "1".into());
+    params.insert("fields".into(), FIELDS.into());
+    params.insert("count".into(), MAX_COUNT.into());
+    let communities = api.groups_get(params).await?;
+    Ok(CommunitiesUpdate::new_async(&communities, storage).await)
+}
+
+/// Returns the communities the user administers, posts are published to their walls
+pub async fn query_admin_async(api: &dyn VkApi, storage: &Storage) -> Result<CommunitiesUpdate> {
+    let mut params = Params::new();
+    params.insert("extended".into(), "1".into());
+    params.insert("filter".into(), "admin".into());
+    params.insert("count".into(), MAX_COUNT.into());
+    let communities = api.groups_get(params).await?;
+    Ok(CommunitiesUpdate::new_async(&communities, storage).await)
+}
+
+/// Subscribes the user to the community
+pub async fn join(api: &dyn VkApi, group_id: i64) -> Result<()> {
+    api.groups_join(group_params(group_id)).await.map(|_| ())
+}
+
+/// Unsubscribes the user from the community
+pub async fn leave(api: &dyn VkApi, group_id: i64) -> Result<()> {
+    api.groups_leave(group_params(group_id)).await.map(|_| ())
+}
+
+fn group_params(group_id: i64) -> Params {
+    let mut params = Params::new();
+    params.insert("group_id".into(), group_id.to_string());
+    params
+}
+
+/// Serves request of the communities page, returns the message to answer UI with
+pub async fn serve_request(
+    request: &CommunityRequest,
+    vk_api: &dyn VkApi,
+    storage: &Storage,
+) -> Result<Message> {
+    match request {
+        CommunityRequest::List => Ok(Message::Communities(query_async(vk_api, storage).await?)),
+        CommunityRequest::Join(group_id) => {
+            join(vk_api, *group_id).await?;
+            Ok(Message::Membership {
+                group_id: *group_id,
+                member: true,
+            })
+        }
+        CommunityRequest::Leave(group_id) => {
+            leave(vk_api, *group_id).await?;
+            Ok(Message::Membership {
+                group_id: *group_id,
+                member: false,
+            })
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Produces CommunityModel objects from the answer of groups.get with extended=1,
+//! deleted and banned communities are skipped
+use super::community_objects::Communities;
+use crate::models::{community_kind, format_members, CommunityModel};
+use crate::storage::Storage;
+use crate::utils::process_text;
+use std::iter::IntoIterator;
+
+pub struct CommunitiesUpdate {
+    items: Vec<CommunityModel>,
+}
+
+impl CommunitiesUpdate {
+    pub async fn new_async(communities: &Communities, storage: &Storage) -> Self {
+        let mut items = Vec::with_capacity(communities.items.len());
+        for src in communities.items.iter().filter(|c| c.deactivated.is_none()) {
+            let avatar = match &src.photo_50 {
+                Some(uri) => storage.get_file(uri, "").await.unwrap_or_default(),
+                None => String::new(),
+            };
+            let screen_name = src
+                .screen_name
+                .clone()
+                .unwrap_or_else(|| format!("club{}", src.id));
+            items.push(CommunityModel {
+                id: src.id,
+                name: src.name.clone(),
+                avatar,
+                uri: format!("https://vk.com/{}", screen_name),
+                kind: community_kind(&src.type_, src.is_closed),
+                members: src.members_count.map(format_members).unwrap_or_default(),
+                description: src
+                    .description
+                    .as_deref()
+                    .map(process_text)
+                    .unwrap_or_default(),
+                is_member: src.is_member != 0,
+                muted: storage.is_muted(-src.id),
+            });
+        }
+        CommunitiesUpdate { items }
+    }
+
+    pub fn items(&self) -> &[CommunityModel] {
+        &self.items
+    }
+}
+
+impl IntoIterator for CommunitiesUpdate {
+    type Item = CommunityModel;
+    type IntoIter = std::vec::IntoIter<CommunityModel>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::{FixtureApi, VkApi};
+    use rvk::Params;
+
+    #[tokio::test]
+    async fn convert_communities() {
+        let api = FixtureApi::new().with_communities_from_dir("resources/tests/groups");
+        let communities = api.groups_get(Params::new()).await.ok().unwrap();
+        assert_eq!(communities.items.len(), 4);
+        let storage = Storage::for_test("communities");
+        assert!(storage.set_muted_async(-24199209, true).await.is_ok());
+        let update = CommunitiesUpdate::new_async(&communities, &storage).await;
+        assert_eq!(update.items().len(), 4);
+        let rt = &update.items()[0];
+        assert_eq!(rt.uri, "https://vk.com/rt_russian");
+        assert_eq!(rt.kind, "public page");
+        assert_eq!(rt.members, "1 266 277 members");
+        assert!(rt.is_member);
+        assert!(!rt.muted);
+        let life = &update.items()[1];
+        assert_eq!(life.description, "Новости &amp; события");
+        assert!(life.muted);
+        assert_eq!(update.items()[2].kind, "closed group");
+        assert_eq!(update.items()[3].kind, "event");
+    }
+}
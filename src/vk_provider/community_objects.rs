@@ -0,0 +1,34 @@
+//! Objects of the groups.* methods, rvk groups lack members_count and is_member,
+//! see <https://vk.com/dev/groups.get> and <https://vk.com/dev/objects/group>
+use serde::Deserialize;
+
+/// Answer to groups.get with extended=1
+#[derive(Deserialize, Clone)]
+pub struct Communities {
+    pub count: i64,
+    pub items: Vec<Community>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Community {
+    // positive, source_id of its posts is negative
+    pub id: i64,
+    pub name: String,
+    pub screen_name: Option<String>,
+    // group, page or event
+    #[serde(rename = "type", default)]
+    pub type_: String,
+    // 0 open, 1 closed, 2 private
+    #[serde(default)]
+    pub is_closed: u8,
+    #[serde(default)]
+    pub is_member: u8,
+    // the user manages the community and publishes to its wall
+    #[serde(default)]
+    pub is_admin: u8,
+    pub photo_50: Option<String>,
+    pub members_count: Option<i64>,
+    pub description: Option<String>,
+    // deleted or banned
+    pub deactivated: Option<String>,
+}
@@ -55,3 +55,29 @@ pub const NEWS_TYPE_FRIEND: &str = "friend";
 // pub const ATTACH_TYPE_PRETTY_CARDS: &str = "pretty_cards";
 // 18. Встреча (type = event)
 // pub const ATTACH_TYPE_EVENTS: &str = "event";
+
+// API error codes, https://vk.com/dev/errors
+
+// авторизация пользователя не удалась
+pub const API_ERROR_AUTH_FAILED: u64 = 5;
+// слишком много запросов в секунду
+pub const API_ERROR_TOO_MANY_REQUESTS: u64 = 6;
+// нет прав для выполнения этого действия
+pub const API_ERROR_PERMISSION_DENIED: u64 = 7;
+// в доступе отказано
+pub const API_ERROR_ACCESS_DENIED: u64 = 15;
+
+// access scopes required by the features, https://vk.com/dev/permissions
+
+// newsfeed.get
+pub const SCOPES_NEWSFEED: [&str; 2] = ["wall", "friends"];
+// messages.getConversations, messages.getHistory, messages.send
+pub const SCOPES_MESSAGES: [&str; 1] = ["messages"];
+// friends.get, friends.getLists
+pub const SCOPES_FRIENDS: [&str; 1] = ["friends"];
+// groups.get, groups.join, groups.leave
+pub const SCOPES_GROUPS: [&str; 1] = ["groups"];
+// wall.createComment, wall.post, photos.getWallUploadServer, photos.saveWallPhoto
+pub const SCOPES_PUBLISH: [&str; 2] = ["wall", "photos"];
+// likes.add, likes.delete
+pub const SCOPES_LIKES: [&str; 1] = ["wall"];
@@ -0,0 +1,1088 @@
+//! In-memory implementation of VkApi answering from recorded data instead of querying vk.com.
+//!
+//! Used for the offline replay of the recorded newsfeed (like one in `resources/tests/newsfeed`),
+//! which allows to demo and debug the UI without an access token, and by the tests.
+//! Replayed files are ordered by their names, which are the timestamps of the recording,
+//! e.g. `2021-01-15_10-44-00.json`. Replay starts from the middle of the set:
+//! the next updates go to the more recent files, older news are taken from the preceding ones.
+//! Chats are replayed from `conversations.json` and `history.json` (like ones in `resources/tests/messages`),
+//! friends from `friends.json` and `lists.json` (like ones in `resources/tests/friends`),
+//! communities from `groups.json` (like one in `resources/tests/groups`),
+//! walls from files named by their owner_id, e.g. `-40316705.json` (like ones in `resources/tests/wall`),
+//! comments from files named by owner_id and id of the post, e.g. `-40316705_38231954.json`
+//! (like ones in `resources/tests/comments`).
+//! Comments sent and posts published are added to the recorded ones,
+//! photos are uploaded to the server set by with_upload_server.
+use super::chat_objects::{Conversations, History, LongPollServer};
+use super::comment_objects::Comments;
+use super::community_objects::Communities;
+use super::constants::{
+    API_ERROR_ACCESS_DENIED, API_ERROR_AUTH_FAILED, API_ERROR_PERMISSION_DENIED,
+};
+use super::friend_objects::{FriendLists, Friends};
+use super::upload_objects::{SavedPhoto, UploadServer};
+use super::wall_objects;
+use super::VkApi;
+use async_trait::async_trait;
+use rvk::{
+    error::{APIError, Error, Result},
+    objects::{account::Account, newsfeed::NewsFeed, user::User},
+    Params,
+};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+/// Environment variable to set the directory with recorded newsfeed files and to turn replay mode on
+pub const REPLAY_DIR_ENV: &str = "GVK_REPLAY";
+/// Environment variable to set the directory with files to resolve image URIs against in replay mode,
+/// images are not shown while replaying without it since the recordings do not carry them
+pub const REPLAY_FILES_ENV: &str = "GVK_REPLAY_FILES";
+/// Environment variable to set the directory with recorded conversations and history in replay mode
+pub const REPLAY_MESSAGES_ENV: &str = "GVK_REPLAY_MESSAGES";
+// default directory with recorded chats for replay mode
+const REPLAY_MESSAGES_DEFAULT: &str = "resources/tests/messages";
+/// Environment variable to set the directory with recorded friends in replay mode
+pub const REPLAY_FRIENDS_ENV: &str = "GVK_REPLAY_FRIENDS";
+// default directory with recorded friends for replay mode
+const REPLAY_FRIENDS_DEFAULT: &str = "resources/tests/friends";
+/// Environment variable to set the directory with recorded communities in replay mode
+pub const REPLAY_GROUPS_ENV: &str = "GVK_REPLAY_GROUPS";
+// default directory with recorded communities for replay mode
+const REPLAY_GROUPS_DEFAULT: &str = "resources/tests/groups";
+/// Environment variable to set the directory with recorded walls in replay mode
+pub const REPLAY_WALLS_ENV: &str = "GVK_REPLAY_WALLS";
+// default directory with recorded walls for replay mode
+const REPLAY_WALLS_DEFAULT: &str = "resources/tests/wall";
+/// Environment variable to set the directory with recorded comments in replay mode
+pub const REPLAY_COMMENTS_ENV: &str = "GVK_REPLAY_COMMENTS";
+// default directory with recorded comments for replay mode
+const REPLAY_COMMENTS_DEFAULT: &str = "resources/tests/comments";
+
+pub struct FixtureApi {
+    // answers to newsfeed.get for the more recent news
+    news_next: Mutex<VecDeque<NewsFeed>>,
+    // answers to newsfeed.get for the older news, i.e. having end_time set, the most recent first
+    news_prev: Mutex<VecDeque<NewsFeed>>,
+    // answer to account.getProfileInfo
+    account: Value,
+    // the only user users.get knows about
+    user: Value,
+    // if set, newsfeed.get is answered from this pool of news, the most recent first
+    feed: Option<Value>,
+    // maximal number of news items in answer to newsfeed.get
+    page_size: usize,
+    // answer to messages.getConversations
+    conversations: Value,
+    // messages of all the conversations the most recent first, with profiles and groups of their authors,
+    // messages.getHistory is answered from it and messages.send adds to it
+    history: Mutex<Value>,
+    // answer to messages.getLongPollServer, there is no long poll server if not set
+    long_poll: Option<Value>,
+    // a number of times messages.getLongPollServer has been answered
+    long_poll_issued: AtomicUsize,
+    // answer to friends.get, access to friends is denied if not set
+    friends: Option<Value>,
+    // answer to friends.getLists
+    friend_lists: Value,
+    // communities user has been a member of, groups.get answers with the current ones,
+    // groups.join and groups.leave change is_member of them
+    communities: Mutex<Value>,
+    // answers to wall.get by owner_id, the most recent posts first, unknown walls are empty,
+    // wall.post adds to them, postponed posts are answered with filter=postponed only
+    walls: Mutex<HashMap<i64, Value>>,
+    // every comment of the post by (owner_id, post_id), top-level ones with complete threads of replies,
+    // unknown posts have no comments, wall.createComment adds to them
+    comments: Mutex<HashMap<(i64, i64), Value>>,
+    // answer to photos.getWallUploadServer, there is no upload server if not set
+    upload_url: Option<String>,
+    // the photo field of every photos.saveWallPhoto, ids of the saved photos follow the order
+    saved_photos: Mutex<Vec<String>>,
+    // count of likes and whether user likes it by (owner_id, item_id), changed by likes.add and likes.delete
+    likes: Mutex<HashMap<(i64, i64), (i64, bool)>>,
+    // likes.add and likes.delete fail with access error
+    likes_denied: AtomicBool,
+    // access token has been revoked, every method fails until the token is replaced
+    revoked: AtomicBool,
+    // access token lacks the scope newsfeed.get requires until the token is replaced
+    denied: AtomicBool,
+}
+
+impl FixtureApi {
+    pub fn new() -> Self {
+        FixtureApi {
+            news_next: Mutex::new(VecDeque::new()),
+            news_prev: Mutex::new(VecDeque::new()),
+            account: json!({
+                "first_name": "offline",
+                "last_name": "replay",
+            }),
+            user: json!({
+                "id": 0,
+                "first_name": "offline",
+                "last_name": "replay",
+            }),
+            feed: None,
+            page_size: 100,
+            conversations: json!({ "count": 0, "items": [] }),
+            history: Mutex::new(json!({ "count": 0, "items": [] })),
+            long_poll: None,
+            long_poll_issued: AtomicUsize::new(0),
+            friends: None,
+            friend_lists: json!({ "count": 0, "items": [] }),
+            communities: Mutex::new(json!({ "count": 0, "items": [] })),
+            walls: Mutex::new(HashMap::new()),
+            comments: Mutex::new(HashMap::new()),
+            upload_url: None,
+            saved_photos: Mutex::new(Vec::new()),
+            likes: Mutex::new(HashMap::new()),
+            likes_denied: AtomicBool::new(false),
+            revoked: AtomicBool::new(false),
+            denied: AtomicBool::new(false),
+        }
+    }
+
+    /// Answers messages.getConversations with the conversations, messages.getHistory from the pool of messages
+    pub fn with_messages(mut self, conversations: Value, mut history: Value) -> Self {
+        if let Some(items) = history["items"].as_array_mut() {
+            items.sort_by_key(|item| -item["id"].as_i64().unwrap_or(0));
+        }
+        self.conversations = conversations;
+        self.history = Mutex::new(history);
+        self
+    }
+
+    /// Loads recorded conversations and history from the directory
+    pub fn with_messages_from_dir(self, dir: &str) -> Self {
+        match (
+            load_json(dir, "conversations.json"),
+            load_json(dir, "history.json"),
+        ) {
+            (Some(conversations), Some(history)) => self.with_messages(conversations, history),
+            _ => self,
+        }
+    }
+
+    /// Answers friends.get with the friends and friends.getLists with the lists
+    pub fn with_friends(mut self, friends: Value, lists: Value) -> Self {
+        self.friends = Some(friends);
+        self.friend_lists = lists;
+        self
+    }
+
+    /// Loads recorded friends and their lists from the directory
+    pub fn with_friends_from_dir(self, dir: &str) -> Self {
+        match (load_json(dir, "friends.json"), load_json(dir, "lists.json")) {
+            (Some(friends), Some(lists)) => self.with_friends(friends, lists),
+            _ => self,
+        }
+    }
+
+    /// Answers groups.get with the communities
+    pub fn with_communities(mut self, communities: Value) -> Self {
+        self.communities = Mutex::new(communities);
+        self
+    }
+
+    /// Loads recorded communities from the directory
+    pub fn with_communities_from_dir(self, dir: &str) -> Self {
+        match load_json(dir, "groups.json") {
+            Some(communities) => self.with_communities(communities),
+            None => self,
+        }
+    }
+
+    /// Answers wall.get for owner_id with the posts of the wall
+    pub fn with_wall(self, owner_id: i64, wall: Value) -> Self {
+        if let Ok(mut walls) = self.walls.lock() {
+            walls.insert(owner_id, wall);
+        }
+        self
+    }
+
+    /// Loads recorded walls from the directory, every file is named by owner_id of the wall
+    pub fn with_walls_from_dir(self, dir: &str) -> Self {
+        let owners: Vec<i64> = match read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .path()
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(|stem| stem.parse::<i64>().ok())
+                })
+                .collect(),
+            Err(e) => {
+                log::warn!("failed reading {}: {}", dir, e);
+                Vec::new()
+            }
+        };
+        owners.into_iter().fold(self, |api, owner_id| {
+            match load_json(dir, &format!("{}.json", owner_id)) {
+                Some(wall) => api.with_wall(owner_id, wall),
+                None => api,
+            }
+        })
+    }
+
+    /// Answers wall.getComments for the post with its comments
+    pub fn with_comments(self, owner_id: i64, post_id: i64, comments: Value) -> Self {
+        if let Ok(mut all) = self.comments.lock() {
+            all.insert((owner_id, post_id), comments);
+        }
+        self
+    }
+
+    /// Loads recorded comments from the directory, every file is named by owner_id and id of the post
+    pub fn with_comments_from_dir(self, dir: &str) -> Self {
+        let posts: Vec<(i64, i64)> = match read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let stem = path.file_stem().and_then(|stem| stem.to_str())?;
+                    let (owner_id, post_id) = stem.split_at(stem.rfind('_')?);
+                    Some((
+                        owner_id.parse::<i64>().ok()?,
+                        post_id[1..].parse::<i64>().ok()?,
+                    ))
+                })
+                .collect(),
+            Err(e) => {
+                log::warn!("failed reading {}: {}", dir, e);
+                Vec::new()
+            }
+        };
+        posts.into_iter().fold(self, |api, (owner_id, post_id)| {
+            match load_json(dir, &format!("{}_{}.json", owner_id, post_id)) {
+                Some(comments) => api.with_comments(owner_id, post_id, comments),
+                None => api,
+            }
+        })
+    }
+
+    /// Appends the answer to the next query of the more recent news
+    pub fn push_next(&self, feed: NewsFeed) {
+        if let Ok(mut news) = self.news_next.lock() {
+            news.push_back(feed);
+        }
+    }
+
+    /// Appends the answer to the next query of the older news
+    pub fn push_prev(&self, feed: NewsFeed) {
+        if let Ok(mut news) = self.news_prev.lock() {
+            news.push_back(feed);
+        }
+    }
+
+    // likes or unlikes the item, returns the count of its likes
+    fn set_liked(&self, params: &Params, liked: bool) -> Result<i64> {
+        self.check_token()?;
+        if self.likes_denied.load(Ordering::SeqCst) {
+            return api_error(
+                API_ERROR_ACCESS_DENIED,
+                "Access denied: no access to call this method",
+            );
+        }
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let key = (
+            param("owner_id").unwrap_or(0),
+            param("item_id").unwrap_or(0),
+        );
+        let mut likes = match self.likes.lock() {
+            Ok(likes) => likes,
+            Err(_) => return Ok(0),
+        };
+        let (count, user_likes) = likes.entry(key).or_insert((0, false));
+        if *user_likes != liked {
+            *user_likes = liked;
+            *count += if liked { 1 } else { -1 };
+        }
+        Ok(*count)
+    }
+
+    fn check_token(&self) -> Result<()> {
+        if self.revoked.load(Ordering::SeqCst) {
+            api_error(
+                API_ERROR_AUTH_FAILED,
+                "User authorization failed: invalid access_token (4).",
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_access(&self) -> Result<()> {
+        if self.denied.load(Ordering::SeqCst) {
+            api_error(
+                API_ERROR_PERMISSION_DENIED,
+                "Permission to perform this action is denied",
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    // joins or leaves the known community with group_id
+    fn set_member(&self, params: &Params, is_member: bool) {
+        let group_id = params
+            .get("group_id")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(0);
+        if let Ok(mut communities) = self.communities.lock() {
+            if let Some(items) = communities["items"].as_array_mut() {
+                for item in items
+                    .iter_mut()
+                    .filter(|item| item["id"].as_i64() == Some(group_id))
+                {
+                    item["is_member"] = json!(if is_member { 1 } else { 0 });
+                }
+            }
+        }
+    }
+
+    /// Loads recorded newsfeed files from the directory
+    pub fn from_dir(dir: &str) -> Option<Self> {
+        let mut files: Vec<PathBuf> = match read_dir(dir) {
+            Ok(list) => list
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+                .collect(),
+            Err(e) => {
+                log::error!("failed reading replay directory {}: {}", dir, e);
+                return None;
+            }
+        };
+        if files.is_empty() {
+            log::error!("there are no newsfeed files to replay in {}", dir);
+            return None;
+        }
+        files.sort();
+        let start = files.len() / 2;
+        log::info!(
+            "replaying {} newsfeed files from {}, starting with {}",
+            files.len(),
+            dir,
+            files[start].to_string_lossy()
+        );
+        let api = FixtureApi::new();
+        for (i, path) in files.iter().enumerate() {
+            if let Some(feed) = FixtureApi::load_newsfeed(path) {
+                if i < start {
+                    api.push_prev(feed);
+                } else {
+                    api.push_next(feed);
+                }
+            }
+        }
+        // older news are answered starting from the most recent ones
+        if let Ok(mut news) = api.news_prev.lock() {
+            news.make_contiguous().reverse();
+        }
+        Some(api)
+    }
+
+    /// Returns replay if it is requested by environment
+    pub fn from_env() -> Option<Self> {
+        let messages_dir = std::env::var(REPLAY_MESSAGES_ENV)
+            .unwrap_or_else(|_| REPLAY_MESSAGES_DEFAULT.to_string());
+        let friends_dir = std::env::var(REPLAY_FRIENDS_ENV)
+            .unwrap_or_else(|_| REPLAY_FRIENDS_DEFAULT.to_string());
+        let groups_dir =
+            std::env::var(REPLAY_GROUPS_ENV).unwrap_or_else(|_| REPLAY_GROUPS_DEFAULT.to_string());
+        let walls_dir =
+            std::env::var(REPLAY_WALLS_ENV).unwrap_or_else(|_| REPLAY_WALLS_DEFAULT.to_string());
+        let comments_dir = std::env::var(REPLAY_COMMENTS_ENV)
+            .unwrap_or_else(|_| REPLAY_COMMENTS_DEFAULT.to_string());
+        std::env::var(REPLAY_DIR_ENV)
+            .ok()
+            .and_then(|dir| FixtureApi::from_dir(dir.as_str()))
+            .map(|api| {
+                api.with_messages_from_dir(messages_dir.as_str())
+                    .with_friends_from_dir(friends_dir.as_str())
+                    .with_communities_from_dir(groups_dir.as_str())
+                    .with_walls_from_dir(walls_dir.as_str())
+                    .with_comments_from_dir(comments_dir.as_str())
+            })
+    }
+
+    /// Returns directory to resolve image URIs against while replaying,
+    /// the empty one resolves nothing but still keeps replay from downloading
+    pub fn files_dir() -> String {
+        std::env::var(REPLAY_FILES_ENV).unwrap_or_else(|_| {
+            log::warn!(
+                "images are not shown, set {} to the directory with the image files",
+                REPLAY_FILES_ENV
+            );
+            String::new()
+        })
+    }
+
+    pub fn load_newsfeed(path: &Path) -> Option<NewsFeed> {
+        match read_to_string(path) {
+            Ok(json) => match serde_json::from_str::<NewsFeed>(&json) {
+                Ok(feed) => Some(feed),
+                Err(e) => {
+                    log::error!("failed parsing {}: {}", path.to_string_lossy(), e);
+                    None
+                }
+            },
+            Err(e) => {
+                log::error!("failed reading {}: {}", path.to_string_lossy(), e);
+                None
+            }
+        }
+    }
+
+    fn get_page(&self, feed: &Value, params: &Params) -> Result<NewsFeed> {
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let start_time = param("start_time").unwrap_or(0);
+        let end_time = param("end_time").unwrap_or(i64::MAX);
+        let count = param("count").map_or(self.page_size, |c| (c as usize).min(self.page_size));
+        let offset = param("start_from").unwrap_or(0) as usize;
+        let empty = Vec::new();
+        let items: Vec<&Value> = feed["items"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .filter(|item| {
+                let date = item["date"].as_i64().unwrap_or(0);
+                date >= start_time && date <= end_time
+            })
+            .collect();
+        let page: Vec<Value> = items
+            .iter()
+            .skip(offset)
+            .take(count)
+            .cloned()
+            .cloned()
+            .collect();
+        let next = offset + page.len();
+        let next_from = if next < items.len() {
+            Some(format!("{}", next))
+        } else {
+            None
+        };
+        serde_json::from_value::<NewsFeed>(json!({
+            "items": page,
+            "profiles": feed["profiles"],
+            "groups": feed["groups"],
+            "next_from": next_from,
+        }))
+        .map_err(Error::Serde)
+    }
+}
+
+#[cfg(test)]
+impl FixtureApi {
+    /// Sets own account and user info
+    pub fn with_profile(mut self, user_id: i64, first_name: &str, last_name: &str) -> Self {
+        self.account = json!({
+            "first_name": first_name,
+            "last_name": last_name,
+        });
+        self.user = json!({
+            "id": user_id,
+            "first_name": first_name,
+            "last_name": last_name,
+        });
+        self
+    }
+
+    /// Makes every method fail with authorization error until the access token is replaced
+    pub fn revoke_token(&self) {
+        self.revoked.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the access token is not valid anymore
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.load(Ordering::SeqCst)
+    }
+
+    /// Makes newsfeed.get fail as if the access token lacked the scope until it is replaced
+    pub fn deny_access(&self) {
+        self.denied.store(true, Ordering::SeqCst);
+    }
+
+    /// Answers messages.getLongPollServer with the server, e.g. the local stand-in like `http://127.0.0.1:8080/lp`,
+    /// every answer has a new key
+    pub fn with_long_poll(mut self, server: &str, ts: i64) -> Self {
+        self.long_poll = Some(json!({ "server": server, "ts": ts }));
+        self
+    }
+
+    /// Returns a number of times the long poll server has been requested
+    pub fn long_poll_issued(&self) -> usize {
+        self.long_poll_issued.load(Ordering::SeqCst)
+    }
+
+    /// Sets the count of likes of the item user does not like yet
+    pub fn with_likes(self, owner_id: i64, item_id: i64, count: i64) -> Self {
+        if let Ok(mut likes) = self.likes.lock() {
+            likes.insert((owner_id, item_id), (count, false));
+        }
+        self
+    }
+
+    /// Makes likes.add and likes.delete fail with access error
+    pub fn deny_likes(&self) {
+        self.likes_denied.store(true, Ordering::SeqCst);
+    }
+
+    /// Answers photos.getWallUploadServer with the address, e.g. the local stand-in like `http://127.0.0.1:8080/upload`
+    pub fn with_upload_server(mut self, upload_url: &str) -> Self {
+        self.upload_url = Some(upload_url.to_string());
+        self
+    }
+
+    /// Returns the photos saved to the wall as the upload server has answered them
+    pub fn saved_photos(&self) -> Vec<String> {
+        self.saved_photos
+            .lock()
+            .map(|photos| photos.clone())
+            .unwrap_or_default()
+    }
+
+    /// Answers newsfeed.get from the pool of news like vk.com does: honoring start_time, end_time (both inclusive),
+    /// count and start_from, each answer contains at most page_size items and next_from if there are more of them
+    pub fn with_feed(mut self, mut feed: Value, page_size: usize) -> Self {
+        if let Some(items) = feed["items"].as_array_mut() {
+            items.sort_by_key(|item| -item["date"].as_i64().unwrap_or(0));
+        }
+        self.feed = Some(feed);
+        self.page_size = page_size;
+        self
+    }
+}
+
+#[async_trait]
+impl VkApi for FixtureApi {
+    async fn newsfeed_get(&self, params: Params) -> Result<NewsFeed> {
+        self.check_token()?;
+        self.check_access()?;
+        if let Some(feed) = &self.feed {
+            return self.get_page(feed, &params);
+        }
+        let queue = if params.contains_key("end_time") {
+            &self.news_prev
+        } else {
+            &self.news_next
+        };
+        // recorded answers have no continuation pages
+        let feed = if params.contains_key("start_from") {
+            None
+        } else if let Ok(mut news) = queue.lock() {
+            news.pop_front()
+        } else {
+            None
+        };
+        match feed {
+            Some(feed) => Ok(feed),
+            // the recorded news are over, nothing new happens
+            None => serde_json::from_str::<NewsFeed>("{}").map_err(Error::Serde),
+        }
+    }
+
+    async fn account_get_profile_info(&self, _params: Params) -> Result<Account> {
+        self.check_token()?;
+        serde_json::from_value::<Account>(self.account.clone()).map_err(Error::Serde)
+    }
+
+    async fn users_get(&self, _params: Params) -> Result<Vec<User>> {
+        self.check_token()?;
+        serde_json::from_value::<User>(self.user.clone())
+            .map(|user| vec![user])
+            .map_err(Error::Serde)
+    }
+
+    async fn messages_get_conversations(&self, _params: Params) -> Result<Conversations> {
+        self.check_token()?;
+        serde_json::from_value::<Conversations>(self.conversations.clone()).map_err(Error::Serde)
+    }
+
+    /// Honors peer_id, count, start_message_id and offset which is relative to start_message_id
+    async fn messages_get_history(&self, params: Params) -> Result<History> {
+        self.check_token()?;
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let peer_id = param("peer_id").unwrap_or(0);
+        let count = param("count").unwrap_or(20).max(0) as usize;
+        let offset = param("offset").unwrap_or(0);
+        let history = match self.history.lock() {
+            Ok(history) => history.clone(),
+            Err(_) => return serde_json::from_str::<History>("{}").map_err(Error::Serde),
+        };
+        let empty = Vec::new();
+        let items: Vec<&Value> = history["items"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .filter(|item| item["peer_id"].as_i64() == Some(peer_id))
+            .collect();
+        let start = match param("start_message_id") {
+            Some(id) => items
+                .iter()
+                .position(|item| item["id"].as_i64().unwrap_or(0) <= id)
+                .unwrap_or_else(|| items.len()),
+            None => 0,
+        };
+        let page: Vec<Value> = items
+            .iter()
+            .skip((start as i64 + offset).max(0) as usize)
+            .take(count)
+            .cloned()
+            .cloned()
+            .collect();
+        serde_json::from_value::<History>(json!({
+            "count": items.len(),
+            "items": page,
+            "profiles": history["profiles"],
+            "groups": history["groups"],
+        }))
+        .map_err(Error::Serde)
+    }
+
+    async fn messages_send(&self, params: Params) -> Result<i64> {
+        self.check_token()?;
+        let mut history = match self.history.lock() {
+            Ok(history) => history,
+            Err(_) => return Ok(0),
+        };
+        let id = history["items"]
+            .as_array()
+            .and_then(|items| items.iter().filter_map(|item| item["id"].as_i64()).max())
+            .unwrap_or(0)
+            + 1;
+        let message = json!({
+            "id": id,
+            "date": chrono::Utc::now().timestamp(),
+            "peer_id": params.get("peer_id").and_then(|v| v.parse::<i64>().ok()).unwrap_or(0),
+            "from_id": self.user["id"],
+            "out": 1,
+            "text": params.get("message").cloned().unwrap_or_default(),
+        });
+        if let Some(items) = history["items"].as_array_mut() {
+            items.insert(0, message);
+        }
+        Ok(id)
+    }
+
+    async fn messages_get_long_poll_server(&self, _params: Params) -> Result<LongPollServer> {
+        self.check_token()?;
+        match &self.long_poll {
+            Some(long_poll) => {
+                let issued = self.long_poll_issued.fetch_add(1, Ordering::SeqCst) + 1;
+                let mut answer = long_poll.clone();
+                answer["key"] = json!(format!("key{}", issued));
+                serde_json::from_value::<LongPollServer>(answer).map_err(Error::Serde)
+            }
+            // nothing is recorded, so there are no real-time events
+            None => api_error(
+                API_ERROR_PERMISSION_DENIED,
+                "Permission to perform this action is denied: no long poll server to replay",
+            ),
+        }
+    }
+
+    async fn friends_get(&self, _params: Params) -> Result<Friends> {
+        self.check_token()?;
+        match &self.friends {
+            Some(friends) => {
+                serde_json::from_value::<Friends>(friends.clone()).map_err(Error::Serde)
+            }
+            // nothing is recorded, so there are no friends to refresh
+            None => api_error(
+                API_ERROR_PERMISSION_DENIED,
+                "Permission to perform this action is denied: no friends to replay",
+            ),
+        }
+    }
+
+    async fn friends_get_lists(&self, _params: Params) -> Result<FriendLists> {
+        self.check_token()?;
+        serde_json::from_value::<FriendLists>(self.friend_lists.clone()).map_err(Error::Serde)
+    }
+
+    /// Honors filter=admin, the communities user administers have is_admin set
+    async fn groups_get(&self, params: Params) -> Result<Communities> {
+        self.check_token()?;
+        let admin = params
+            .get("filter")
+            .map_or(false, |filter| filter == "admin");
+        let communities = match self.communities.lock() {
+            Ok(communities) => communities.clone(),
+            Err(_) => return serde_json::from_str::<Communities>("{}").map_err(Error::Serde),
+        };
+        let empty = Vec::new();
+        let items: Vec<&Value> = communities["items"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .filter(|item| item["is_member"].as_i64() == Some(1))
+            .filter(|item| !admin || item["is_admin"].as_i64() == Some(1))
+            .collect();
+        serde_json::from_value::<Communities>(json!({
+            "count": items.len(),
+            "items": items,
+        }))
+        .map_err(Error::Serde)
+    }
+
+    async fn groups_join(&self, params: Params) -> Result<i64> {
+        self.check_token()?;
+        self.set_member(&params, true);
+        Ok(1)
+    }
+
+    async fn groups_leave(&self, params: Params) -> Result<i64> {
+        self.check_token()?;
+        self.set_member(&params, false);
+        Ok(1)
+    }
+
+    async fn wall_get(&self, params: Params) -> Result<NewsFeed> {
+        self.check_token()?;
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let offset = param("offset").unwrap_or(0) as usize;
+        let count = param("count").unwrap_or(20) as usize;
+        let postponed = params
+            .get("filter")
+            .map_or(false, |filter| filter == "postponed");
+        let wall = match param("owner_id").and_then(|owner_id| {
+            self.walls
+                .lock()
+                .ok()
+                .and_then(|walls| walls.get(&owner_id).cloned())
+        }) {
+            Some(wall) => wall,
+            None => return wall_objects::into_newsfeed(json!({})).map_err(Error::Serde),
+        };
+        let empty = Vec::new();
+        let page: Vec<&Value> = wall["items"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .filter(|item| (item["post_type"] == "postpone") == postponed)
+            .skip(offset)
+            .take(count)
+            .collect();
+        wall_objects::into_newsfeed(json!({
+            "count": wall["count"],
+            "items": page,
+            "profiles": wall["profiles"],
+            "groups": wall["groups"],
+        }))
+        .map_err(Error::Serde)
+    }
+
+    /// The post goes to the wall of owner_id or to the user's one, it is postponed if publish_date is set
+    async fn wall_post(&self, params: Params) -> Result<i64> {
+        self.check_token()?;
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let user_id = self.user["id"].as_i64().unwrap_or(0);
+        let owner_id = param("owner_id").unwrap_or(user_id);
+        let from_id = if owner_id < 0 && param("from_group") == Some(1) {
+            owner_id
+        } else {
+            user_id
+        };
+        let now = chrono::Utc::now().timestamp();
+        let publish_date = param("publish_date").filter(|date| *date > now);
+        let mut walls = match self.walls.lock() {
+            Ok(walls) => walls,
+            Err(_) => return Ok(0),
+        };
+        let wall = walls
+            .entry(owner_id)
+            .or_insert_with(|| json!({ "count": 0, "items": [] }));
+        let id = wall["items"]
+            .as_array()
+            .and_then(|items| items.iter().filter_map(|item| item["id"].as_i64()).max())
+            .unwrap_or(0)
+            + 1;
+        let post = json!({
+            "id": id,
+            "owner_id": owner_id,
+            "from_id": from_id,
+            "date": publish_date.unwrap_or(now),
+            "post_type": if publish_date.is_some() { "postpone" } else { "post" },
+            "text": params.get("message").cloned().unwrap_or_default(),
+        });
+        if let Some(items) = wall["items"].as_array_mut() {
+            items.insert(0, post);
+        }
+        if publish_date.is_none() {
+            wall["count"] = json!(wall["count"].as_i64().unwrap_or(0) + 1);
+        }
+        Ok(id)
+    }
+
+    async fn wall_get_comments(&self, params: Params) -> Result<Comments> {
+        self.check_token()?;
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let offset = param("offset").unwrap_or(0) as usize;
+        let count = param("count").unwrap_or(10) as usize;
+        let thread_items = param("thread_items_count").unwrap_or(0) as usize;
+        let post = (
+            param("owner_id").unwrap_or(0),
+            param("post_id").unwrap_or(0),
+        );
+        let all = match self
+            .comments
+            .lock()
+            .ok()
+            .and_then(|all| all.get(&post).cloned())
+        {
+            Some(all) => all,
+            None => {
+                return serde_json::from_value::<Comments>(json!({ "count": 0, "items": [] }))
+                    .map_err(Error::Serde)
+            }
+        };
+        let empty = Vec::new();
+        let top_level = all["items"].as_array().unwrap_or(&empty);
+        // replies of the thread are paged the same way as top-level comments, they have no threads
+        let level: Vec<Value> = match param("comment_id") {
+            Some(comment_id) => top_level
+                .iter()
+                .find(|comment| comment["id"].as_i64() == Some(comment_id))
+                .and_then(|comment| comment["thread"]["items"].as_array())
+                .unwrap_or(&empty)
+                .clone(),
+            None => top_level
+                .iter()
+                .map(|comment| {
+                    let mut comment = comment.clone();
+                    if let Some(replies) = comment["thread"]["items"].as_array_mut() {
+                        replies.truncate(thread_items);
+                    }
+                    comment
+                })
+                .collect(),
+        };
+        let page: Vec<&Value> = level.iter().skip(offset).take(count).collect();
+        serde_json::from_value::<Comments>(json!({
+            "count": all["count"],
+            "current_level_count": level.len(),
+            "items": page,
+            "profiles": all["profiles"],
+            "groups": all["groups"],
+        }))
+        .map_err(Error::Serde)
+    }
+
+    /// The reply is added to the thread of the top-level comment it replies to directly or thru another reply
+    async fn wall_create_comment(&self, params: Params) -> Result<i64> {
+        self.check_token()?;
+        let param = |name: &str| params.get(name).and_then(|v| v.parse::<i64>().ok());
+        let post = (
+            param("owner_id").unwrap_or(0),
+            param("post_id").unwrap_or(0),
+        );
+        let mut all = match self.comments.lock() {
+            Ok(all) => all,
+            Err(_) => return Ok(0),
+        };
+        let comments = all
+            .entry(post)
+            .or_insert_with(|| json!({ "count": 0, "items": [] }));
+        let top_level = comments["items"].as_array().cloned().unwrap_or_default();
+        // every comment with the position of its top-level one
+        let flat: Vec<(usize, &Value)> = top_level
+            .iter()
+            .enumerate()
+            .flat_map(|(pos, root)| {
+                let replies = root["thread"]["items"].as_array().into_iter().flatten();
+                std::iter::once((pos, root)).chain(replies.map(move |reply| (pos, reply)))
+            })
+            .collect();
+        let id = flat
+            .iter()
+            .filter_map(|(_, comment)| comment["id"].as_i64())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let mut comment = json!({
+            "id": id,
+            "from_id": self.user["id"],
+            "post_id": post.1,
+            "owner_id": post.0,
+            "date": chrono::Utc::now().timestamp(),
+            "text": params.get("message").cloned().unwrap_or_default(),
+        });
+        // the thread and the author replied to
+        let replied = param("reply_to_comment").and_then(|reply_to| {
+            flat.iter()
+                .find(|(_, comment)| comment["id"].as_i64() == Some(reply_to))
+                .map(|(pos, comment)| (*pos, reply_to, comment["from_id"].clone()))
+        });
+        match replied {
+            Some((pos, reply_to, from_id)) => {
+                comment["reply_to_comment"] = json!(reply_to);
+                comment["reply_to_user"] = from_id;
+                let thread = &mut comments["items"][pos]["thread"];
+                if !thread["items"].is_array() {
+                    *thread = json!({ "count": 0, "items": [] });
+                }
+                thread["count"] = json!(thread["count"].as_i64().unwrap_or(0) + 1);
+                if let Some(replies) = thread["items"].as_array_mut() {
+                    replies.push(comment);
+                }
+            }
+            None => {
+                if let Some(items) = comments["items"].as_array_mut() {
+                    items.push(comment);
+                }
+            }
+        }
+        comments["count"] = json!(comments["count"].as_i64().unwrap_or(0) + 1);
+        Ok(id)
+    }
+
+    /// The address carries the user and the community like ones of vk.com do
+    async fn photos_get_wall_upload_server(&self, params: Params) -> Result<UploadServer> {
+        self.check_token()?;
+        match &self.upload_url {
+            Some(upload_url) => Ok(UploadServer {
+                upload_url: format!(
+                    "{}?act=do_add&mid={}&gid={}",
+                    upload_url,
+                    self.user["id"],
+                    params.get("group_id").map_or("0", |v| v.as_str())
+                ),
+            }),
+            // nothing is uploaded while replaying
+            None => api_error(
+                API_ERROR_ACCESS_DENIED,
+                "Access denied: no upload server to replay",
+            ),
+        }
+    }
+
+    /// The photo is saved to the wall of the community if group_id is set, to the user's one otherwise
+    async fn photos_save_wall_photo(&self, params: Params) -> Result<Vec<SavedPhoto>> {
+        self.check_token()?;
+        let owner_id = match params.get("group_id").and_then(|v| v.parse::<i64>().ok()) {
+            Some(group_id) => -group_id,
+            None => self.user["id"].as_i64().unwrap_or(0),
+        };
+        let mut saved = match self.saved_photos.lock() {
+            Ok(saved) => saved,
+            Err(_) => return Ok(Vec::new()),
+        };
+        saved.push(params.get("photo").cloned().unwrap_or_default());
+        Ok(vec![SavedPhoto {
+            id: saved.len() as i64,
+            owner_id,
+        }])
+    }
+
+    async fn likes_add(&self, params: Params) -> Result<i64> {
+        self.set_liked(&params, true)
+    }
+
+    async fn likes_delete(&self, params: Params) -> Result<i64> {
+        self.set_liked(&params, false)
+    }
+
+    async fn auth_log_out(&self) -> Result<()> {
+        self.check_token()?;
+        self.revoked.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn set_access_token(&self, _access_token: &str) {
+        self.revoked.store(false, Ordering::SeqCst);
+        self.denied.store(false, Ordering::SeqCst);
+    }
+}
+
+// None if failed reading or parsing the file
+fn load_json(dir: &str, name: &str) -> Option<Value> {
+    let path = Path::new(dir).join(name);
+    read_to_string(&path)
+        .map_err(|e| format!("{}", e))
+        .and_then(|json| serde_json::from_str::<Value>(&json).map_err(|e| format!("{}", e)))
+        .map_err(|e| log::warn!("failed loading {}: {}", path.to_string_lossy(), e))
+        .ok()
+}
+
+fn api_error<T>(code: u64, msg: &str) -> Result<T> {
+    serde_json::from_value::<APIError>(json!({
+        "error_code": code,
+        "error_msg": msg,
+        "request_params": [],
+    }))
+    .map_err(Error::Serde)
+    .and_then(|e| Err(Error::API(e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn replay_recorded_newsfeed() {
+        let total = read_dir("resources/tests/newsfeed").unwrap().count();
+        let api = FixtureApi::from_dir("resources/tests/newsfeed").unwrap();
+        let mut older = Params::new();
+        older.insert("end_time".into(), "0".into());
+        let mut cnt = 0;
+        while let Ok(feed) = api.newsfeed_get(older.clone()).await {
+            if feed.items.is_none() {
+                break;
+            }
+            cnt += 1;
+        }
+        while let Ok(feed) = api.newsfeed_get(Params::new()).await {
+            if feed.items.is_none() {
+                break;
+            }
+            cnt += 1;
+        }
+        assert_eq!(cnt, total);
+    }
+
+    #[tokio::test]
+    async fn replay_recorded_messages() {
+        let api = FixtureApi::new()
+            .with_profile(4321, "Anna", "Sidorova")
+            .with_messages_from_dir("resources/tests/messages");
+        let conversations = api
+            .messages_get_conversations(Params::new())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(conversations.items.len(), 3);
+        let mut params = Params::new();
+        params.insert("peer_id".into(), "2000000001".into());
+        params.insert("count".into(), "2".into());
+        let recent = api.messages_get_history(params.clone()).await.ok().unwrap();
+        let ids: Vec<i64> = recent.items.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![105, 104]);
+        // the page preceding the oldest received message
+        params.insert("start_message_id".into(), "104".into());
+        params.insert("offset".into(), "1".into());
+        params.insert("count".into(), "20".into());
+        let older = api.messages_get_history(params.clone()).await.ok().unwrap();
+        let ids: Vec<i64> = older.items.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![103, 102, 101]);
+        // sent message is the most recent one
+        let mut send = Params::new();
+        send.insert("peer_id".into(), "2000000001".into());
+        send.insert("message".into(), "hi".into());
+        assert_eq!(api.messages_send(send).await.ok().unwrap(), 106);
+        params.remove("start_message_id");
+        params.remove("offset");
+        let recent = api.messages_get_history(params).await.ok().unwrap();
+        assert_eq!(recent.items[0].text, "hi");
+        assert_eq!(recent.items[0].from_id, 4321);
+        assert_eq!(recent.items[0].out, 1);
+    }
+}
@@ -0,0 +1,50 @@
+//! Objects of the friends.* methods, rvk users lack online, last_seen and lists,
+//! see <https://vk.com/dev/friends.get> and <https://vk.com/dev/friends.getLists>
+use serde::Deserialize;
+
+/// Answer to friends.get with fields
+#[derive(Deserialize, Clone)]
+pub struct Friends {
+    pub count: i64,
+    pub items: Vec<Friend>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Friend {
+    pub id: i64,
+    pub first_name: String,
+    #[serde(default)]
+    pub last_name: String,
+    // short address of the page, id{user_id} if it is not set
+    pub domain: Option<String>,
+    pub photo_50: Option<String>,
+    #[serde(default)]
+    pub online: u8,
+    // is missing for the deleted and banned users and if user hides it
+    pub last_seen: Option<LastSeen>,
+    // ids of the friend lists the friend is in
+    pub lists: Option<Vec<i64>>,
+    // deleted or banned
+    pub deactivated: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LastSeen {
+    // unix time
+    pub time: i64,
+    // 1 mobile, 2 iPhone, 3 iPad, 4 Android, 5 Windows Phone, 6 Windows 10, 7 web
+    pub platform: Option<i64>,
+}
+
+/// Answer to friends.getLists
+#[derive(Deserialize, Clone)]
+pub struct FriendLists {
+    pub count: i64,
+    pub items: Vec<FriendList>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FriendList {
+    pub id: i64,
+    pub name: String,
+}
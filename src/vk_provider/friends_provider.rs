@@ -0,0 +1,65 @@
+//! Friends of the user, <https://vk.com/dev/friends.get>, <https://vk.com/dev/friends.getLists>
+//!
+//! * query_async() - requests friends with their online status, last_seen and friend lists
+//! * run() - the provider task refreshing friends on its own schedule, independent of the newsfeed polling,
+//!   the online statuses change in between with the chat events of the long poll
+use super::friends_update::FriendsUpdate;
+use super::{do_send, is_permission_error, MessageSender, SharedApi, VkApi};
+use crate::storage::{SharedStorage, Storage};
+use crate::ui::Message;
+use rvk::{error::Result, Params};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::Notify;
+use tokio::time::{sleep, Duration};
+
+const FIELDS: &str = "domain,photo_50,online,last_seen,lists";
+
+/// Returns friends of the user with the names of their friend lists
+pub async fn query_async(api: &dyn VkApi, storage: &Storage) -> Result<FriendsUpdate> {
+    let mut params = Params::new();
+    // the most important friends first, like vk.com lists them
+    params.insert("order".into(), "hints".into());
+    params.insert("fields".into(), FIELDS.into());
+    let friends = api.friends_get(params).await?;
+    let lists = api.friends_get_lists(Params::new()).await?;
+    Ok(FriendsUpdate::new_async(&friends, &lists, storage).await)
+}
+
+/// Sends friends to UI every interval_sec or when refresh is notified
+pub async fn run(
+    api: SharedApi,
+    storage: SharedStorage,
+    interval_sec: Arc<AtomicU64>,
+    refresh: Arc<Notify>,
+    tx_msg: MessageSender,
+) {
+    log::info!("starting friends refresh");
+    // logged once, not on every refresh
+    let mut denied = false;
+    loop {
+        match query_async(api.as_ref(), &storage).await {
+            Ok(update) => {
+                denied = false;
+                log::debug!("got {} friends", update.items().len());
+                if !do_send(&tx_msg, Message::Friends(update)) {
+                    return;
+                }
+            }
+            Err(e) if is_permission_error(&e) => {
+                if !denied {
+                    log::info!("there is no access to friends, waiting for it: {}", e);
+                    denied = true;
+                }
+            }
+            Err(e) => log::warn!("failed getting friends: {}", e),
+        }
+        let delay = Duration::from_secs(interval_sec.load(Ordering::SeqCst));
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = refresh.notified() => log::debug!("getting friends on demand"),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Produces FriendModel objects from the answers of friends.get and friends.getLists
+//! the same way ConversationsUpdate does for conversations, ids of the friend lists are replaced with their names,
+//! deleted and banned users are skipped
+use super::friend_objects::{FriendLists, Friends};
+use crate::models::FriendModel;
+use crate::storage::Storage;
+use std::iter::IntoIterator;
+
+pub struct FriendsUpdate {
+    items: Vec<FriendModel>,
+    // names of all the friend lists
+    lists: Vec<String>,
+}
+
+impl FriendsUpdate {
+    pub async fn new_async(friends: &Friends, lists: &FriendLists, storage: &Storage) -> Self {
+        let mut items = Vec::with_capacity(friends.items.len());
+        for src in friends.items.iter().filter(|f| f.deactivated.is_none()) {
+            let avatar = match &src.photo_50 {
+                Some(uri) => storage.get_file(uri, "").await.unwrap_or_default(),
+                None => String::new(),
+            };
+            let domain = src
+                .domain
+                .clone()
+                .unwrap_or_else(|| format!("id{}", src.id));
+            let in_lists = src.lists.as_deref().unwrap_or(&[]);
+            items.push(FriendModel {
+                id: src.id,
+                name: format!("{} {}", src.first_name, src.last_name)
+                    .trim()
+                    .to_string(),
+                avatar,
+                uri: format!("https://vk.com/{}", domain),
+                online: src.online != 0,
+                last_seen: src.last_seen.as_ref().map_or(0, |seen| seen.time),
+                lists: lists
+                    .items
+                    .iter()
+                    .filter(|list| in_lists.contains(&list.id))
+                    .map(|list| list.name.clone())
+                    .collect(),
+            });
+        }
+        FriendsUpdate {
+            items,
+            lists: lists.items.iter().map(|list| list.name.clone()).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[FriendModel] {
+        &self.items
+    }
+
+    pub fn lists(&self) -> &[String] {
+        &self.lists
+    }
+}
+
+impl IntoIterator for FriendsUpdate {
+    type Item = FriendModel;
+    type IntoIter = std::vec::IntoIter<FriendModel>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::{FixtureApi, VkApi};
+    use rvk::Params;
+
+    #[tokio::test]
+    async fn convert_friends() {
+        let api = FixtureApi::new().with_friends_from_dir("resources/tests/friends");
+        let friends = api.friends_get(Params::new()).await.ok().unwrap();
+        let lists = api.friends_get_lists(Params::new()).await.ok().unwrap();
+        assert_eq!(friends.items.len(), 4);
+        let storage = Storage::for_test("friends");
+        let update = FriendsUpdate::new_async(&friends, &lists, &storage).await;
+        // the deleted one is skipped
+        assert_eq!(update.items().len(), 3);
+        assert_eq!(update.lists(), ["Family", "Colleagues"]);
+        let ivan = &update.items()[0];
+        assert_eq!(ivan.name, "Ivan Petrov");
+        assert_eq!(ivan.uri, "https://vk.com/ivan.petrov");
+        assert!(ivan.online);
+        assert_eq!(ivan.lists, ["Family"]);
+        let olga = &update.items()[1];
+        assert!(!olga.online);
+        assert_eq!(olga.last_seen, 1610640000);
+        assert_eq!(olga.lists, ["Family", "Colleagues"]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use tokio::sync::oneshot::{self, error::TryRecvError, Receiver};
+
+/// Runs the tasks of the same key one by one in the order they have been queued,
+/// the tasks of distinct keys run concurrently. Queueing does not wait,
+/// so the tasks are queued by the requests loop and served by tasks of their own
+pub struct InOrder<K: Eq + Hash> {
+    // finishes along with the last queued task of the key
+    last: Mutex<HashMap<K, Receiver<()>>>,
+}
+
+impl<K: Eq + Hash> Default for InOrder<K> {
+    fn default() -> Self {
+        InOrder {
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash> InOrder<K> {
+    /// Returns the future which runs the task after the ones queued before it with the same key
+    pub fn queue<F: Future>(&self, key: K, task: F) -> impl Future<Output = F::Output> {
+        let (done, turn) = oneshot::channel::<()>();
+        let previous = {
+            let mut last = self.last.lock().unwrap();
+            // forget the finished ones
+            last.retain(|_, turn| matches!(turn.try_recv(), Err(TryRecvError::Empty)));
+            last.insert(key, turn)
+        };
+        async move {
+            if let Some(previous) = previous {
+                // the previous task has finished or has been dropped
+                let _ = previous.await;
+            }
+            let output = task.await;
+            drop(done);
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn queued_in_order() {
+        let in_order = InOrder::default();
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for &(key, n, delay) in &[(1, 1, 50), (2, 2, 0), (1, 3, 0), (1, 4, 10)] {
+            let served = served.clone();
+            let task = in_order.queue(key, async move {
+                sleep(Duration::from_millis(delay)).await;
+                served.lock().unwrap().push(n);
+            });
+            tasks.push(tokio::spawn(task));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // the other key does not wait
+        assert_eq!(*served.lock().unwrap(), vec![2, 1, 3, 4]);
+        // the finished ones are forgotten
+        let _ = in_order.queue(3, async {});
+        assert_eq!(in_order.last.lock().unwrap().len(), 1);
+    }
+}
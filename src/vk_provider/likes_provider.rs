@@ -0,0 +1,43 @@
+//! Likes of the posts, <https://vk.com/dev/likes.add>, <https://vk.com/dev/likes.delete>
+use super::VkApi;
+use crate::models::NewsItemId;
+use rvk::{error::Result, Params};
+
+// type of the liked objects, news items are liked as posts of their source walls
+const LIKE_TYPE_POST: &str = "post";
+
+/// Likes or unlikes the post, returns the count of its likes
+pub async fn set_liked(api: &dyn VkApi, item: &NewsItemId, liked: bool) -> Result<i64> {
+    let mut params = Params::new();
+    params.insert("type".into(), LIKE_TYPE_POST.into());
+    params.insert("owner_id".into(), item.source_id.to_string());
+    params.insert("item_id".into(), item.post_id.to_string());
+    if liked {
+        api.likes_add(params).await
+    } else {
+        api.likes_delete(params).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::FixtureApi;
+
+    #[tokio::test]
+    async fn like_and_unlike() {
+        let api = FixtureApi::new().with_likes(-40316705, 2194561, 21);
+        let item = NewsItemId {
+            source_id: -40316705,
+            post_id: 2194561,
+            itemtype: "post".to_string(),
+            content_id: 0,
+        };
+        assert_eq!(set_liked(&api, &item, true).await.ok(), Some(22));
+        // liking twice counts once
+        assert_eq!(set_liked(&api, &item, true).await.ok(), Some(22));
+        assert_eq!(set_liked(&api, &item, false).await.ok(), Some(21));
+        api.deny_likes();
+        assert!(set_liked(&api, &item, true).await.is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Real-time events of the user's chats from the Long Poll server, <https://vk.com/dev/using_longpoll>
+//!
+//! * LongPollClient - asks vk.com for the server with messages.getLongPollServer, then waits for events
+//!   following the ts of the last one, the server is requested anew if it reports failure
+//! * run() - the provider task which converts events to ChatEvent and sends them to UI
+use super::chat_objects::LongPollServer;
+use super::chat_provider::ChatProvider;
+use super::chat_update::{format_datetime, ConversationsUpdate};
+use super::{do_send, is_permission_error, MessageSender, SharedApi, VkApi};
+use crate::models::{ChatEvent, MessageModel};
+use crate::storage::{SharedStorage, Storage};
+use crate::ui::Message;
+use crate::utils::process_text;
+use rvk::Params;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use tokio::time::{sleep, Duration};
+
+// the server holds the request up to the time if there are no events
+const WAIT_SEC: u64 = 25;
+// attachments (2) and the extended set of events (8)
+const MODE: u32 = 2 | 8;
+const VERSION: u32 = 3;
+// pause after the failed request not to flood
+const RETRY_DELAY_SEC: u64 = 10;
+// pause while there is no access to messages, user might grant it meanwhile
+const DENIED_DELAY_SEC: u64 = 60;
+
+// event codes
+const EVENT_NEW_MESSAGE: i64 = 4;
+const EVENT_READ_INCOMING: i64 = 6;
+const EVENT_FRIEND_ONLINE: i64 = 8;
+const EVENT_FRIEND_OFFLINE: i64 = 9;
+const EVENT_UNREAD_COUNT: i64 = 80;
+// flag of the new message sent by the user
+const FLAG_OUTBOX: i64 = 2;
+// peer_id of a chat is the chat id plus this
+const CHAT_PEER_START: i64 = 2_000_000_000;
+
+pub enum LongPollError {
+    // failed requesting the server from vk.com
+    Api(rvk::error::Error),
+    // failed waiting for events
+    Http(String),
+    // unexpected answer of the server
+    Malformed(String),
+}
+
+impl fmt::Display for LongPollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LongPollError::Api(e) => write!(f, "failed getting long poll server: {}", e),
+            LongPollError::Http(e) => write!(f, "failed waiting for events: {}", e),
+            LongPollError::Malformed(e) => write!(f, "malformed long poll answer: {}", e),
+        }
+    }
+}
+
+/// Event as the server reports it
+pub enum LongPollEvent {
+    NewMessage {
+        id: i64,
+        peer_id: i64,
+        // the author in a chat, the same as peer_id otherwise
+        from_id: Option<i64>,
+        timestamp: i64,
+        text: String,
+        out: bool,
+    },
+    Read(i64),
+    Unread(i64),
+    Online {
+        user_id: i64,
+        online: bool,
+    },
+    /// Events might have been lost, everything is to be requested anew
+    Outdated,
+}
+
+pub struct LongPollClient {
+    http: reqwest::Client,
+    server: Option<LongPollServer>,
+}
+
+impl LongPollClient {
+    pub fn new() -> Self {
+        LongPollClient {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(WAIT_SEC + 10))
+                .build()
+                .unwrap_or_default(),
+            server: None,
+        }
+    }
+
+    /// Waits for the next events, they are empty if nothing has happened for a while
+    pub async fn next_events(
+        &mut self,
+        api: &dyn VkApi,
+    ) -> Result<Vec<LongPollEvent>, LongPollError> {
+        let server = match self.server.take() {
+            Some(server) => server,
+            None => {
+                let mut params = Params::new();
+                params.insert("lp_version".into(), format!("{}", VERSION));
+                api.messages_get_long_poll_server(params)
+                    .await
+                    .map_err(LongPollError::Api)?
+            }
+        };
+        let answer = match self.check(&server).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                // the same server is asked again
+                self.server = Some(server);
+                return Err(e);
+            }
+        };
+        let ts = answer["ts"].as_i64();
+        match answer["failed"].as_i64() {
+            None => {
+                self.server = Some(LongPollServer {
+                    ts: ts.ok_or_else(|| LongPollError::Malformed("no ts".into()))?,
+                    ..server
+                });
+                Ok(parse_updates(&answer["updates"]))
+            }
+            // the history is outdated, continue with the new ts
+            Some(1) => {
+                log::info!("long poll history is outdated");
+                self.server = Some(LongPollServer {
+                    ts: ts.ok_or_else(|| LongPollError::Malformed("no ts".into()))?,
+                    ..server
+                });
+                Ok(vec![LongPollEvent::Outdated])
+            }
+            // the key has expired, a new key is to continue with the same ts
+            Some(2) => {
+                log::info!("long poll key has expired");
+                let mut params = Params::new();
+                params.insert("lp_version".into(), format!("{}", VERSION));
+                let renewed = api
+                    .messages_get_long_poll_server(params)
+                    .await
+                    .map_err(LongPollError::Api)?;
+                self.server = Some(LongPollServer {
+                    ts: server.ts,
+                    ..renewed
+                });
+                Ok(Vec::new())
+            }
+            // the information is lost, start anew with a new key and ts
+            Some(3) => {
+                log::info!("long poll information is lost");
+                Ok(vec![LongPollEvent::Outdated])
+            }
+            Some(code) => Err(LongPollError::Malformed(format!("failed {}", code))),
+        }
+    }
+
+    async fn check(&self, server: &LongPollServer) -> Result<Value, LongPollError> {
+        // vk.com reports the address without scheme
+        let address = if server.server.starts_with("http") {
+            server.server.clone()
+        } else {
+            format!("https://{}", server.server)
+        };
+        let uri = format!(
+            "{}?act=a_check&key={}&ts={}&wait={}&mode={}&version={}",
+            address, server.key, server.ts, WAIT_SEC, MODE, VERSION
+        );
+        let response = self
+            .http
+            .get(uri.as_str())
+            .send()
+            .await
+            .map_err(|e| LongPollError::Http(format!("{}", e)))?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| LongPollError::Http(format!("{}", e)))?;
+        serde_json::from_str::<Value>(&body).map_err(|e| LongPollError::Malformed(format!("{}", e)))
+    }
+}
+
+// unknown events are skipped
+fn parse_updates(updates: &Value) -> Vec<LongPollEvent> {
+    let mut events = Vec::new();
+    for update in updates.as_array().map(|u| u.as_slice()).unwrap_or(&[]) {
+        let field = |i: usize| update[i].as_i64().unwrap_or(0);
+        match update[0].as_i64() {
+            Some(EVENT_NEW_MESSAGE) => events.push(LongPollEvent::NewMessage {
+                id: field(1),
+                peer_id: field(3),
+                from_id: update[6]["from"]
+                    .as_str()
+                    .and_then(|from| from.parse::<i64>().ok()),
+                timestamp: field(4),
+                text: update[5].as_str().unwrap_or("").to_string(),
+                out: field(2) & FLAG_OUTBOX != 0,
+            }),
+            Some(EVENT_READ_INCOMING) => events.push(LongPollEvent::Read(field(1))),
+            Some(EVENT_FRIEND_ONLINE) => events.push(LongPollEvent::Online {
+                user_id: -field(1),
+                online: true,
+            }),
+            Some(EVENT_FRIEND_OFFLINE) => events.push(LongPollEvent::Online {
+                user_id: -field(1),
+                online: false,
+            }),
+            Some(EVENT_UNREAD_COUNT) => events.push(LongPollEvent::Unread(field(1))),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Names and avatars of the message authors, users are requested once
+struct Authors {
+    known: HashMap<i64, (String, String)>,
+}
+
+impl Authors {
+    fn new() -> Self {
+        Authors {
+            known: HashMap::new(),
+        }
+    }
+
+    async fn get(&mut self, api: &dyn VkApi, user_id: i64, storage: &Storage) -> (String, String) {
+        if let Some(author) = self.known.get(&user_id) {
+            return author.clone();
+        }
+        // groups are not looked up
+        if user_id <= 0 {
+            return (String::new(), String::new());
+        }
+        let mut params = Params::new();
+        params.insert("user_ids".into(), format!("{}", user_id));
+        params.insert("fields".into(), "photo_50".into());
+        match api.users_get(params).await {
+            Ok(users) => {
+                if let Some(user) = users.iter().find(|u| u.id == user_id) {
+                    let avatar = storage
+                        .get_file(super::User::get_small_photo(user).as_str(), "")
+                        .await
+                        .unwrap_or_default();
+                    let author = (super::User::get_full_name(user), avatar);
+                    self.known.insert(user_id, author.clone());
+                    return author;
+                }
+                (String::new(), String::new())
+            }
+            Err(e) => {
+                log::warn!("failed getting author of the message: {}", e);
+                (String::new(), String::new())
+            }
+        }
+    }
+}
+
+/// Sends events of the user's chats to UI until it has stopped,
+/// waits while the access token lacks the scope to read messages
+pub async fn run(api: SharedApi, user_id: i64, storage: SharedStorage, tx_msg: MessageSender) {
+    log::info!("starting long poll");
+    let mut client = LongPollClient::new();
+    let mut authors = Authors::new();
+    // logged once, not on every retry
+    let mut denied = false;
+    loop {
+        let events = match client.next_events(api.as_ref()).await {
+            Ok(events) => {
+                denied = false;
+                events
+            }
+            Err(LongPollError::Api(e)) if is_permission_error(&e) => {
+                if !denied {
+                    log::info!("there is no access to messages, waiting for it: {}", e);
+                    denied = true;
+                }
+                sleep(Duration::from_secs(DENIED_DELAY_SEC)).await;
+                continue;
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                sleep(Duration::from_secs(RETRY_DELAY_SEC)).await;
+                continue;
+            }
+        };
+        let mut chat_events = Vec::with_capacity(events.len());
+        for event in events {
+            match event {
+                LongPollEvent::NewMessage {
+                    id,
+                    peer_id,
+                    from_id,
+                    timestamp,
+                    text,
+                    out,
+                } => {
+                    let from_id = if out {
+                        user_id
+                    } else if peer_id > CHAT_PEER_START {
+                        from_id.unwrap_or(0)
+                    } else {
+                        peer_id
+                    };
+                    let (author, avatar) = authors.get(api.as_ref(), from_id, &storage).await;
+                    chat_events.push(ChatEvent::NewMessage(MessageModel {
+                        id,
+                        peer_id,
+                        from_id,
+                        timestamp,
+                        out,
+                        author,
+                        avatar,
+                        datetime: format_datetime(timestamp),
+                        text: process_text(&text),
+                        photos: None,
+                    }));
+                }
+                LongPollEvent::Read(peer_id) => chat_events.push(ChatEvent::Read(peer_id)),
+                LongPollEvent::Unread(count) => chat_events.push(ChatEvent::Unread(count)),
+                LongPollEvent::Online { user_id, online } => {
+                    chat_events.push(ChatEvent::Online { user_id, online })
+                }
+                LongPollEvent::Outdated => {
+                    // the lost events are covered by the fresh list of conversations
+                    match ChatProvider::new().conversations(api.as_ref()).await {
+                        Ok(conversations) => {
+                            let update =
+                                ConversationsUpdate::new_async(&conversations, &storage).await;
+                            if !do_send(&tx_msg, Message::Conversations(update)) {
+                                return;
+                            }
+                        }
+                        Err(e) => log::warn!("failed getting conversations: {}", e),
+                    }
+                }
+            }
+        }
+        if !chat_events.is_empty() && !do_send(&tx_msg, Message::ChatEvents(chat_events)) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::stand_in::stand_in;
+    use crate::vk_provider::FixtureApi;
+
+    // the value of the query parameter of the request like GET /lp?act=a_check&... HTTP/1.1
+    fn param(request: &str, name: &str) -> String {
+        request
+            .split_whitespace()
+            .nth(1)
+            .and_then(|path| path.split('?').nth(1))
+            .unwrap_or("")
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(&format!("{}=", name)))
+            .unwrap_or("")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn follow_ts_and_failures() {
+        let (address, rx_request) = stand_in(
+            "/lp",
+            vec![
+                r#"{"ts": 11, "updates": [
+                [4, 106, 1, 2000000001, 1610701000, "hello", {"from": "1234"}],
+                [4, 107, 3, 100, 1610701001, "hi", {}],
+                [6, 2000000001, 106],
+                [80, 3, 0],
+                [8, -1234, 7, 1610701000],
+                [9, -100, 0, 1610701000],
+                [61, 1234, 1]
+            ]}"#,
+                r#"{"failed": 2}"#,
+                r#"{"failed": 1, "ts": 20}"#,
+                r#"{"ts": 21, "updates": []}"#,
+                r#"{"failed": 3}"#,
+                r#"{"ts": 5, "updates": []}"#,
+            ],
+        );
+        let api = FixtureApi::new().with_long_poll(&address, 10);
+        let mut client = LongPollClient::new();
+
+        let events = client.next_events(&api).await.ok().unwrap();
+        assert_eq!(param(&rx_request.recv().unwrap(), "ts"), "10");
+        assert_eq!(events.len(), 6);
+        match &events[0] {
+            LongPollEvent::NewMessage {
+                id,
+                peer_id,
+                from_id,
+                text,
+                out,
+                ..
+            } => {
+                assert_eq!(*id, 106);
+                assert_eq!(*peer_id, 2000000001);
+                assert_eq!(*from_id, Some(1234));
+                assert_eq!(text, "hello");
+                assert!(!out);
+            }
+            _ => panic!("new message is expected"),
+        }
+        assert!(matches!(
+            events[1],
+            LongPollEvent::NewMessage { out: true, .. }
+        ));
+        assert!(matches!(events[2], LongPollEvent::Read(2000000001)));
+        assert!(matches!(events[3], LongPollEvent::Unread(3)));
+        assert!(matches!(
+            events[4],
+            LongPollEvent::Online {
+                user_id: 1234,
+                online: true
+            }
+        ));
+        assert!(matches!(
+            events[5],
+            LongPollEvent::Online {
+                user_id: 100,
+                online: false
+            }
+        ));
+
+        // the key has expired: a new one continues with the same ts
+        assert!(client.next_events(&api).await.ok().unwrap().is_empty());
+        let request = rx_request.recv().unwrap();
+        assert_eq!(param(&request, "ts"), "11");
+        assert_eq!(param(&request, "key"), "key1");
+        assert_eq!(api.long_poll_issued(), 2);
+
+        // the history is outdated: continue with the new ts
+        let events = client.next_events(&api).await.ok().unwrap();
+        assert!(matches!(events[..], [LongPollEvent::Outdated]));
+        let request = rx_request.recv().unwrap();
+        assert_eq!(param(&request, "ts"), "11");
+        assert_eq!(param(&request, "key"), "key2");
+        assert!(client.next_events(&api).await.ok().unwrap().is_empty());
+        assert_eq!(param(&rx_request.recv().unwrap(), "ts"), "20");
+
+        // the information is lost: start anew
+        let events = client.next_events(&api).await.ok().unwrap();
+        assert!(matches!(events[..], [LongPollEvent::Outdated]));
+        rx_request.recv().unwrap();
+        assert!(client.next_events(&api).await.ok().unwrap().is_empty());
+        let request = rx_request.recv().unwrap();
+        assert_eq!(param(&request, "ts"), "10");
+        assert_eq!(param(&request, "key"), "key3");
+        assert_eq!(api.long_poll_issued(), 3);
+    }
+}
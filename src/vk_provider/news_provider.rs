@@ -1,92 +1,202 @@
+use super::VkApi;
+use crate::settings::Settings;
 use crate::utils::local_from_timestamp;
 use chrono::Utc;
-use rvk::{methods::newsfeed, objects::newsfeed::NewsFeed, APIClient, Params};
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Mutex,
-};
+use rvk::{error::Result, objects::newsfeed::NewsFeed, Params};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-// a maximal time interval to limit news updates
-const MAX_UPDATE_DELTA_SEC: u64 = 3_600; // 60 minutes
+// limits of the time window to query older news in, 10 minutes and 24 hours
+const MIN_WINDOW_SEC: u64 = 600;
+const MAX_WINDOW_SEC: u64 = 86_400;
+// desired number of news items in the window, the window is adapted to the feed density to get it
+const WINDOW_ITEMS: usize = 50;
+// protects from endless following of the next_from cursor
+const MAX_PAGES: usize = 20;
 
 /// <https://vk.com/dev/newsfeed.get>
 /// Multi-threaded, callef from a couple of tasks
 pub struct NewsProvider {
     received_from: AtomicU64,
     received_to: AtomicU64,
-    last_next_from: Mutex<String>,
+    // if not zero, the news from received_to till gap_to (inclusive) are still to be received,
+    // the more recent ones have been received till head_to
+    gap_to: AtomicU64,
+    head_to: AtomicU64,
+    // current size of the time window to query older news in
+    window_sec: AtomicU64,
+    // a number of news items to request per page
+    page_size: AtomicU64,
 }
 
 impl NewsProvider {
-    pub fn new() -> Self {
-        // on start get news for the last hour:
-        let received_from = Utc::now().timestamp() as u64 - MAX_UPDATE_DELTA_SEC;
-        let received_to = received_from; // for the last hour
-        NewsProvider {
+    pub fn new(settings: &Settings) -> Self {
+        // on start get news for the configured interval, e.g. the last hour:
+        let received_from = Utc::now().timestamp() as u64 - settings.initial_news_sec;
+        let received_to = received_from;
+        NewsProvider::resume(settings, received_from, received_to)
+    }
+
+    /// Continues receiving news already received from received_from till received_to,
+    /// e.g. restored from storage on start
+    pub fn resume(settings: &Settings, received_from: u64, received_to: u64) -> Self {
+        let provider = NewsProvider {
             received_from: AtomicU64::new(received_from),
             received_to: AtomicU64::new(received_to),
-            last_next_from: Mutex::new(String::new()),
-        }
+            gap_to: AtomicU64::new(0),
+            head_to: AtomicU64::new(received_to),
+            window_sec: AtomicU64::new(0),
+            page_size: AtomicU64::new(0),
+        };
+        provider.set_window(settings.initial_news_sec);
+        provider.apply_settings(settings);
+        provider
     }
 
-    // returns pack of the news preceeding the current the most old one, i.e. start_sec - MAX_UPDATE_DELTA_SEC
-    pub async fn prev_update(&self, api: &APIClient) -> Option<NewsFeed> {
-        let end_time = self.received_from.load(Ordering::SeqCst);
-        let start_time = end_time - MAX_UPDATE_DELTA_SEC;
-        let mut params = Params::new();
-        params.insert("start_time".into(), format!("{}", start_time).into());
-        params.insert("end_time".into(), format!("{}", end_time).into());
-        params.insert("count".into(), "100".into());
-        self.received_from.store(start_time, Ordering::SeqCst);
-        self.do_update(api, params)
-            .await
-            .map(|upd| {
-                // if success update next_from
-                let next_from = if let Some(val) = upd.next_from.as_ref() {
-                    val.clone()
-                } else {
-                    String::new()
-                };
-                if let Ok(mut s) = self.last_next_from.lock() {
-                    *s = next_from;
-                }
-                upd
-            })
-            .or_else(|| {
-                // if failed log warning
-                log::warn!(
-                    "drop news from {} to {} due to error",
-                    format!(
-                        "{}",
-                        local_from_timestamp(start_time as i64).format("%d.%m.%Y %H:%M")
-                    )
-                    .as_str(),
-                    format!(
-                        "{}",
+    /// Applies changed settings
+    pub fn apply_settings(&self, settings: &Settings) {
+        self.page_size
+            .store(settings.news_page_size as u64, Ordering::SeqCst);
+    }
+
+    fn set_window(&self, window_sec: u64) {
+        self.window_sec.store(
+            window_sec.max(MIN_WINDOW_SEC).min(MAX_WINDOW_SEC),
+            Ordering::SeqCst,
+        );
+    }
+
+    // returns pack of the news preceeding the current the most old one,
+    // the window is widened until it contains some news or reaches MAX_WINDOW_SEC
+    pub async fn prev_update(&self, api: &dyn VkApi) -> Result<NewsFeed> {
+        loop {
+            let end_time = self.received_from.load(Ordering::SeqCst);
+            let window = self.window_sec.load(Ordering::SeqCst);
+            let start_time = end_time.saturating_sub(window);
+            // both bounds are inclusive, so the upper one is excluded as it has already been received
+            let (feed, exhausted) = match self
+                .get_window(api, start_time, Some(end_time.saturating_sub(1)))
+                .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    // nothing is dropped, the same window will be requested next time
+                    log::warn!(
+                        "failed getting news from {} to {}, will retry",
+                        local_from_timestamp(start_time as i64).format("%d.%m.%Y %H:%M"),
                         local_from_timestamp(end_time as i64).format("%d.%m.%Y %H:%M")
-                    )
-                    .as_str()
-                );
-                None
-            })
+                    );
+                    return Err(e);
+                }
+            };
+            let cnt = feed.items.as_ref().map_or(0, |items| items.len());
+            if exhausted {
+                self.received_from.store(start_time, Ordering::SeqCst);
+            } else if let Some(oldest) = oldest_date(&feed) {
+                // the rest of the window is to be received next time
+                self.received_from
+                    .store(oldest as u64 + 1, Ordering::SeqCst);
+            }
+            // adapt the window to the feed density
+            let next_window = if cnt > 2 * WINDOW_ITEMS || !exhausted {
+                (window / 2).max(MIN_WINDOW_SEC)
+            } else if cnt < WINDOW_ITEMS / 2 {
+                (window * 2).min(MAX_WINDOW_SEC)
+            } else {
+                window
+            };
+            self.window_sec.store(next_window, Ordering::SeqCst);
+            if cnt > 0 || window == MAX_WINDOW_SEC || start_time == 0 {
+                return Ok(feed);
+            }
+            log::debug!(
+                "there are no news in the window, widen it to {} sec",
+                next_window
+            );
+        }
     }
 
-    // returrns next protion of the news, i.e. subsequent to the most recent ones
-    pub async fn next_update(&self, api: &APIClient) -> Option<NewsFeed> {
-        let mut params = Params::new();
+    // returrns next protion of the news, i.e. subsequent to the most recent ones,
+    // if there are too many of them the rest is received next time before the more recent ones
+    pub async fn next_update(&self, api: &dyn VkApi) -> Result<NewsFeed> {
         let start_time = self.received_to.load(Ordering::SeqCst);
-        params.insert("start_time".into(), format!("{}", start_time));
-        params.insert("count".into(), "100".into());
-        self.received_to
-            .store(Utc::now().timestamp() as u64, Ordering::SeqCst);
-        self.do_update(api, params).await
+        let gap_to = self.gap_to.load(Ordering::SeqCst);
+        let (end_time, head_to) = if gap_to != 0 {
+            (Some(gap_to), self.head_to.load(Ordering::SeqCst))
+        } else {
+            (None, Utc::now().timestamp() as u64 + 1)
+        };
+        // received_to is kept on failure, so nothing is dropped
+        let (feed, exhausted) = self.get_window(api, start_time, end_time).await?;
+        if exhausted {
+            self.gap_to.store(0, Ordering::SeqCst);
+            self.received_to.store(head_to, Ordering::SeqCst);
+        } else if let Some(oldest) = oldest_date(&feed) {
+            log::info!(
+                "more than {} pages of news since {}, the older ones are received next time",
+                MAX_PAGES,
+                local_from_timestamp(start_time as i64).format("%d.%m.%Y %H:%M")
+            );
+            // the news of the oldest second might have been received partly, so it is queried again,
+            // unless the whole window has been filled with that second
+            let oldest = oldest as u64;
+            let gap_to = match end_time {
+                Some(end_time) if oldest >= end_time => end_time.saturating_sub(1),
+                _ => oldest,
+            };
+            self.gap_to.store(gap_to.max(1), Ordering::SeqCst);
+            self.head_to.store(head_to, Ordering::SeqCst);
+        }
+        Ok(feed)
+    }
+
+    // follows the next_from cursor until the window is exhausted,
+    // returns all the news of the window and a flag the window has been exhausted
+    async fn get_window(
+        &self,
+        api: &dyn VkApi,
+        start_time: u64,
+        end_time: Option<u64>,
+    ) -> Result<(NewsFeed, bool)> {
+        let mut result: Option<NewsFeed> = None;
+        let mut next_from = String::new();
+        let mut exhausted = false;
+        for _ in 0..MAX_PAGES {
+            let mut params = Params::new();
+            params.insert("start_time".into(), format!("{}", start_time));
+            if let Some(end_time) = end_time {
+                params.insert("end_time".into(), format!("{}", end_time));
+            }
+            params.insert(
+                "count".into(),
+                format!("{}", self.page_size.load(Ordering::SeqCst)),
+            );
+            if !next_from.is_empty() {
+                params.insert("start_from".into(), next_from.clone());
+            }
+            let page = self.do_update(api, params).await?;
+            let is_empty = page.items.as_ref().map_or(true, |items| items.is_empty());
+            next_from = page.next_from.clone().unwrap_or_default();
+            result = Some(match result {
+                None => page,
+                Some(mut acc) => {
+                    merge(&mut acc, page);
+                    acc
+                }
+            });
+            if is_empty || next_from.is_empty() {
+                exhausted = true;
+                break;
+            }
+        }
+        // MAX_PAGES is not 0, so there is at least one page
+        Ok((result.unwrap(), exhausted))
     }
 
-    async fn do_update(&self, api: &APIClient, params: Params) -> Option<NewsFeed> {
-        match newsfeed::get::<NewsFeed>(api, params).await {
-            Ok(upd) => Some(upd),
+    async fn do_update(&self, api: &dyn VkApi, params: Params) -> Result<NewsFeed> {
+        match api.newsfeed_get(params).await {
+            Ok(upd) => Ok(upd),
             Err(e) => {
-                match e {
+                match &e {
                     rvk::error::Error::API(e) => {
                         log::error!(
                             "failed requesting news update: {}, extra {:?}",
@@ -96,18 +206,174 @@ impl NewsProvider {
                     }
                     _ => log::error!("failed requesting news update: {}", e),
                 }
-                None
+                Err(e)
+            }
+        }
+    }
+}
+
+// appends the next page to the accumulated news, profiles and groups are not duplicated
+fn merge(acc: &mut NewsFeed, page: NewsFeed) {
+    if let Some(items) = page.items {
+        acc.items.get_or_insert_with(Vec::new).extend(items);
+    }
+    if let Some(profiles) = page.profiles {
+        let acc_profiles = acc.profiles.get_or_insert_with(Vec::new);
+        for profile in profiles {
+            if !acc_profiles.iter().any(|p| p.id == profile.id) {
+                acc_profiles.push(profile);
+            }
+        }
+    }
+    if let Some(groups) = page.groups {
+        let acc_groups = acc.groups.get_or_insert_with(Vec::new);
+        for group in groups {
+            if !acc_groups.iter().any(|g| g.id == group.id) {
+                acc_groups.push(group);
             }
         }
     }
+    acc.next_from = page.next_from;
+}
+
+fn oldest_date(feed: &NewsFeed) -> Option<i64> {
+    feed.items
+        .as_ref()
+        .and_then(|items| items.iter().map(|item| item.date).min())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::vk_provider::FixtureApi;
+    use serde_json::{json, Value};
+    use std::collections::HashSet;
     use std::fs::{read_dir, read_to_string};
     use std::path::Path;
 
+    type ItemKey = (String, i64, i64, i64);
+
+    // all the recorded news without duplicates
+    fn recorded_news() -> (Value, HashSet<ItemKey>) {
+        let mut items: Vec<Value> = Vec::new();
+        let mut profiles: Vec<Value> = Vec::new();
+        let mut groups: Vec<Value> = Vec::new();
+        let mut keys = HashSet::new();
+        for file in read_dir("resources/tests/newsfeed").unwrap() {
+            let json = read_to_string(file.unwrap().path()).unwrap();
+            let feed: Value = serde_json::from_str(&json).unwrap();
+            for item in feed["items"].as_array().unwrap() {
+                let key = (
+                    item["type"].as_str().unwrap().to_string(),
+                    item["source_id"].as_i64().unwrap(),
+                    item["post_id"].as_i64().unwrap_or(0),
+                    item["date"].as_i64().unwrap(),
+                );
+                if keys.insert(key) {
+                    items.push(item.clone());
+                }
+            }
+            append_unique(&mut profiles, &feed["profiles"]);
+            append_unique(&mut groups, &feed["groups"]);
+        }
+        let feed = json!({ "items": items, "profiles": profiles, "groups": groups });
+        (feed, keys)
+    }
+
+    fn append_unique(dst: &mut Vec<Value>, src: &Value) {
+        for v in src.as_array().unwrap() {
+            if !dst.iter().any(|d| d["id"] == v["id"]) {
+                dst.push(v.clone());
+            }
+        }
+    }
+
+    fn item_keys(feed: &NewsFeed) -> Vec<ItemKey> {
+        feed.items.as_ref().map_or(Vec::new(), |items| {
+            items
+                .iter()
+                .map(|item| {
+                    (
+                        item.type_.clone(),
+                        item.source_id,
+                        item.post_id.unwrap_or(0),
+                        item.date,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn resume_applies_settings() {
+        let settings = Settings {
+            initial_news_sec: 7_200,
+            news_page_size: 20,
+            ..Settings::default()
+        };
+        let news = NewsProvider::resume(&settings, 1000, 2000);
+        assert_eq!(news.window_sec.load(Ordering::SeqCst), 7_200);
+        assert_eq!(news.page_size.load(Ordering::SeqCst), 20);
+    }
+
+    #[tokio::test]
+    async fn prev_update_follows_cursor() {
+        let (feed, expected) = recorded_news();
+        let newest = expected.iter().map(|k| k.3).max().unwrap() as u64;
+        let oldest = expected.iter().map(|k| k.3).min().unwrap() as u64;
+        // small pages to force following the cursor
+        let api = FixtureApi::new().with_feed(feed, 10);
+        let news = NewsProvider::resume(&Settings::default(), newest + 1, newest + 1);
+        let mut received = Vec::new();
+        while news.received_from.load(Ordering::SeqCst) > oldest {
+            let update = news.prev_update(&api).await.ok().unwrap();
+            received.extend(item_keys(&update));
+        }
+        let unique: HashSet<ItemKey> = received.iter().cloned().collect();
+        // no duplicates
+        assert_eq!(unique.len(), received.len());
+        // nothing dropped
+        assert_eq!(unique, expected);
+    }
+
+    #[tokio::test]
+    async fn next_update_follows_cursor() {
+        let (feed, expected) = recorded_news();
+        let oldest = expected.iter().map(|k| k.3).min().unwrap() as u64;
+        let api = FixtureApi::new().with_feed(feed, 50);
+        let news = NewsProvider::resume(&Settings::default(), oldest, oldest);
+        let received = item_keys(&news.next_update(&api).await.ok().unwrap());
+        let unique: HashSet<ItemKey> = received.iter().cloned().collect();
+        assert_eq!(unique.len(), received.len());
+        assert_eq!(unique, expected);
+        // nothing new since then
+        assert!(item_keys(&news.next_update(&api).await.ok().unwrap()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn next_update_continues_burst() {
+        let (feed, expected) = recorded_news();
+        let oldest = expected.iter().map(|k| k.3).min().unwrap() as u64;
+        // more news than MAX_PAGES pages
+        let page_size = 5;
+        assert!(expected.len() > MAX_PAGES * page_size);
+        let api = FixtureApi::new().with_feed(feed, page_size);
+        let news = NewsProvider::resume(&Settings::default(), oldest, oldest);
+        let first = item_keys(&news.next_update(&api).await.ok().unwrap());
+        assert_eq!(first.len(), MAX_PAGES * page_size);
+        // the rest of the burst comes on the next ticks, the older the later
+        let mut received: HashSet<ItemKey> = first.into_iter().collect();
+        for _ in 0..expected.len() {
+            let update = item_keys(&news.next_update(&api).await.ok().unwrap());
+            if update.is_empty() {
+                break;
+            }
+            received.extend(update);
+        }
+        // nothing dropped
+        assert_eq!(received, expected);
+    }
+
     #[test]
     fn deserialize_news_update() {
         if let Ok(mut file_list) = read_dir("resources/tests/newsfeed") {
@@ -13,7 +13,7 @@ use crate::utils::{local_from_timestamp, process_text};
 use crate::vk_provider;
 use crate::vk_provider::constants::*;
 use rvk::objects::{
-    attachment::PostedPhoto,
+    attachment::{PostedPhoto, WallAttachment},
     link::Link as NewsLink,
     newsfeed::{Item as NewsItem, NewsFeed},
     photo::{Photo as NewsPhoto, Size as PhotoSize},
@@ -28,6 +28,15 @@ pub struct NewsUpdate {
 impl NewsUpdate {
     // Perform all desired transformations while constructing NewsItemModel collecton
     pub async fn new_async(newsfeed: &NewsFeed, storage: &Storage) -> Self {
+        NewsUpdate::convert_async(newsfeed, storage, true).await
+    }
+
+    /// Constructs update from posts of the source wall, they are displayed even if the source is muted in news
+    pub async fn from_wall_async(wall: &NewsFeed, storage: &Storage) -> Self {
+        NewsUpdate::convert_async(wall, storage, false).await
+    }
+
+    async fn convert_async(newsfeed: &NewsFeed, storage: &Storage, skip_muted: bool) -> Self {
         // prepare users
         let users_stub = Vec::new();
         let users = if let Some(ref src_users) = newsfeed.profiles {
@@ -46,6 +55,10 @@ impl NewsUpdate {
         let items = if let Some(ref src_items) = newsfeed.items {
             let mut items = Vec::with_capacity(src_items.len());
             for src in src_items {
+                // user does not want to see news of the source
+                if skip_muted && storage.is_muted(src.source_id) {
+                    continue;
+                }
                 let mut friends_photos = None;
                 // some items to ignore
                 match src.type_.as_str() {
@@ -58,15 +71,13 @@ impl NewsUpdate {
                                     let mut photos = Vec::new();
                                     for profile in ids {
                                         let mut friend_pic = String::new();
+                                        let mut friend_source = String::new();
                                         let friend_name = if let Some(user) =
                                             users.iter().find(|u| u.id == profile.user_id)
                                         {
-                                            if let Ok(filename) = storage
-                                                .get_file(
-                                                    vk_provider::User::get_max_photo(user).as_str(),
-                                                    "",
-                                                )
-                                                .await
+                                            friend_source = vk_provider::User::get_max_photo(user);
+                                            if let Ok(filename) =
+                                                storage.get_file(friend_source.as_str(), "").await
                                             {
                                                 friend_pic = filename;
                                             }
@@ -77,6 +88,7 @@ impl NewsUpdate {
                                         photos.push(Photo {
                                             uri: friend_pic,
                                             text: friend_name,
+                                            source: friend_source,
                                         });
                                     }
                                     friends_photos = Some(photos);
@@ -134,6 +146,9 @@ impl NewsUpdate {
                 let links = extract_links(&src).await;
                 // compose and return model
                 items.push(NewsItemModel {
+                    source_id: src.source_id,
+                    post_id: src.post_id.unwrap_or(0),
+                    timestamp: src.date,
                     author,
                     avatar,
                     itemtype: src.type_.clone(),
@@ -148,6 +163,15 @@ impl NewsUpdate {
                     },
                     photos,
                     links,
+                    likes: src.likes.as_ref().map_or(0, |likes| likes.count),
+                    comments: src.comments.as_ref().map_or(0, |comments| comments.count),
+                    reposts: src.reposts.as_ref().map_or(0, |reposts| reposts.count),
+                    views: src.views.as_ref().map_or(0, |views| views.count),
+                    liked: src
+                        .likes
+                        .as_ref()
+                        .map_or(false, |likes| likes.user_likes != 0),
+                    content_id: content_id(&src),
                 })
             }
             //
@@ -158,9 +182,38 @@ impl NewsUpdate {
         NewsUpdate { items }
     }
 
+    /// Constructs update from already prepared items, e.g. restored from storage
+    pub fn from_items(items: Vec<NewsItemModel>) -> Self {
+        NewsUpdate { items }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
+
+    pub fn items(&self) -> &[NewsItemModel] {
+        &self.items
+    }
+}
+
+// the first photo or friend the item is about, the items which are not posts have no id of their own
+fn content_id(item: &NewsItem) -> i64 {
+    if item.post_id.is_some() {
+        return 0;
+    }
+    let photo = item
+        .photos
+        .as_ref()
+        .and_then(|photoset| photoset.items.as_ref())
+        .and_then(|photos| photos.first())
+        .map(|photo| photo.id);
+    let friend = item
+        .friends
+        .as_ref()
+        .and_then(|friends| friends.items.as_ref())
+        .and_then(|friends| friends.first())
+        .map(|friend| friend.user_id);
+    photo.or(friend).unwrap_or(0)
 }
 
 async fn extract_photos(item: &NewsItem, storage: &Storage) -> Option<Vec<Photo>> {
@@ -185,29 +238,7 @@ async fn extract_photos(item: &NewsItem, storage: &Storage) -> Option<Vec<Photo>
                 for history_item in copy_history {
                     // for any type continue searching in attachments (WallAttachment)
                     if let Some(attachments) = &history_item.attachments {
-                        // different attachment types might contain photos
-                        for attachment in attachments {
-                            // photo itself
-                            if let Some(src_photo) = &attachment.photo {
-                                if let Some(res_photo) =
-                                    select_photo(src_photo, result.len(), storage).await
-                                {
-                                    result.push(res_photo);
-                                }
-                            }
-                            // also, link might hold a photo
-                            if let Some(link) = &attachment.link {
-                                append_from_link(&mut result, link, storage).await;
-                            }
-                            // video
-                            if let Some(video) = &attachment.video {
-                                append_from_video(&mut result, video, storage).await;
-                            }
-                            // posted photo
-                            if let Some(posted_photo) = &attachment.posted_photo {
-                                append_from_posted_photo(&mut result, posted_photo, storage).await;
-                            }
-                        }
+                        append_from_attachments(&mut result, attachments, storage).await;
                     }
                 }
             }
@@ -240,13 +271,14 @@ async fn extract_photos(item: &NewsItem, storage: &Storage) -> Option<Vec<Photo>
             if let Some(doc) = &attachment.doc {
                 if let Some(preview) = &doc.preview {
                     if let Some(photo) = &preview.photo {
-                        if let Some(uri) =
+                        if let Some((uri, source)) =
                             select_photo_uri(&photo.sizes, result.len(), storage).await
                         {
                             let text = doc.title.clone();
                             result.push(Photo {
                                 uri,
                                 text: process_text(&text),
+                                source,
                             })
                         }
                     }
@@ -262,6 +294,35 @@ async fn extract_photos(item: &NewsItem, storage: &Storage) -> Option<Vec<Photo>
     }
 }
 
+/// Appends photos of the wall attachments, the ones of reposts and comments
+pub(super) async fn append_from_attachments(
+    cont: &mut Vec<Photo>,
+    attachments: &[WallAttachment],
+    storage: &Storage,
+) {
+    // different attachment types might contain photos
+    for attachment in attachments {
+        // photo itself
+        if let Some(src_photo) = &attachment.photo {
+            if let Some(res_photo) = select_photo(src_photo, cont.len(), storage).await {
+                cont.push(res_photo);
+            }
+        }
+        // also, link might hold a photo
+        if let Some(link) = &attachment.link {
+            append_from_link(cont, link, storage).await;
+        }
+        // video
+        if let Some(video) = &attachment.video {
+            append_from_video(cont, video, storage).await;
+        }
+        // posted photo
+        if let Some(posted_photo) = &attachment.posted_photo {
+            append_from_posted_photo(cont, posted_photo, storage).await;
+        }
+    }
+}
+
 async fn extract_links(item: &NewsItem) -> Option<Vec<Link>> {
     let mut result = Vec::new();
     match item.type_.as_str() {
@@ -344,6 +405,7 @@ async fn append_from_video(cont: &mut Vec<Photo>, video: &Video, storage: &Stora
                 cont.push(Photo {
                     text: String::new(),
                     uri,
+                    source: src_uri.clone(),
                 });
             }
         }
@@ -354,6 +416,7 @@ async fn append_from_video(cont: &mut Vec<Photo>, video: &Video, storage: &Stora
                 cont.push(Photo {
                     text: String::new(),
                     uri,
+                    source: src_uri.clone(),
                 });
             }
         }
@@ -375,6 +438,7 @@ async fn append_from_video(cont: &mut Vec<Photo>, video: &Video, storage: &Stora
                     cont.push(Photo {
                         text: String::new(),
                         uri,
+                        source: images[idx_best].url.clone(),
                     });
                 }
             }
@@ -395,6 +459,7 @@ async fn append_from_posted_photo(
             cont.push(Photo {
                 text: String::new(),
                 uri,
+                source: posted_photo.photo_604.clone(),
             });
         }
     } else {
@@ -405,21 +470,21 @@ async fn append_from_posted_photo(
             cont.push(Photo {
                 text: String::new(),
                 uri,
+                source: posted_photo.photo_130.clone(),
             });
         }
     }
 }
 
-static PRIO_0: [&str; 8] = ["y", "x", "r", "q", "p", "o", "m", "s"];
-//static PRIO_N: [&str; 2] = ["o", "m"];
-
-async fn select_photo_uri(sizes: &Vec<PhotoSize>, idx: usize, storage: &Storage) -> Option<String> {
-    let prio = match idx {
-        //0 | 1 => &PRIO_0[..],
-        _ => &PRIO_0[..],
-    };
-    for p in prio {
-        if let Some(size) = sizes.iter().find(|s| s.type_.as_str() == *p) {
+async fn select_photo_uri(
+    sizes: &Vec<PhotoSize>,
+    _idx: usize,
+    storage: &Storage,
+) -> Option<(String, String)> {
+    // preferred sizes are set by user
+    let prio = storage.get_photo_sizes();
+    for p in prio.iter() {
+        if let Some(size) = sizes.iter().find(|s| s.type_ == *p) {
             let uri = if let Some(url) = &size.url {
                 url
             } else if let Some(src) = &size.src {
@@ -427,23 +492,27 @@ async fn select_photo_uri(sizes: &Vec<PhotoSize>, idx: usize, storage: &Storage)
             } else {
                 continue;
             };
-            if let Ok(uri) = storage.get_temp_file(uri, *p).await {
-                return Some(uri);
+            if let Ok(file) = storage.get_temp_file(uri, p).await {
+                return Some((file, uri.clone()));
             }
         }
     }
     None
 }
 
-async fn select_photo(src_photo: &NewsPhoto, idx: usize, storage: &Storage) -> Option<Photo> {
+pub(super) async fn select_photo(
+    src_photo: &NewsPhoto,
+    idx: usize,
+    storage: &Storage,
+) -> Option<Photo> {
     if let Some(sizes) = &src_photo.sizes {
-        if let Some(uri) = select_photo_uri(sizes, idx, storage).await {
+        if let Some((uri, source)) = select_photo_uri(sizes, idx, storage).await {
             let text = if let Some(val) = &src_photo.text {
                 val.clone()
             } else {
                 String::new()
             };
-            return Some(Photo { uri, text });
+            return Some(Photo { uri, text, source });
         }
     }
     None
@@ -0,0 +1,206 @@
+//! Photos attached to comments and posts, <https://vk.com/dev/upload_files?f=2.%20Uploading%20Photos%20on%20User%20or%20Community%20Wall>
+//!
+//! Every photo takes three steps: the upload address is requested with photos.getWallUploadServer,
+//! the file is posted there as multipart/form-data, then the answer of the server is passed
+//! to photos.saveWallPhoto which returns the photo to attach
+use super::upload_objects::UploadedPhoto;
+use super::VkApi;
+use reqwest::multipart::{Form, Part};
+use rvk::{error::Error, Params};
+use std::fmt;
+use std::path::Path;
+use tokio::time::Duration;
+
+// uploading a large photo over a slow connection takes a while
+const UPLOAD_TIMEOUT_SEC: u64 = 120;
+
+pub enum UploadError {
+    // failed calling vk.com API
+    Api(Error),
+    // failed reading the file to upload
+    File(String),
+    // failed posting the file or unexpected answer of the upload server
+    Server(String),
+}
+
+impl UploadError {
+    /// Returns the error of vk.com API if it is the one
+    pub fn api_error(&self) -> Option<&Error> {
+        match self {
+            UploadError::Api(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadError::Api(e) => write!(f, "{}", e),
+            UploadError::File(e) => write!(f, "failed reading the photo: {}", e),
+            UploadError::Server(e) => write!(f, "failed uploading the photo: {}", e),
+        }
+    }
+}
+
+impl From<Error> for UploadError {
+    fn from(e: Error) -> Self {
+        UploadError::Api(e)
+    }
+}
+
+pub struct PhotoUploader {
+    http: reqwest::Client,
+}
+
+impl PhotoUploader {
+    pub fn new() -> Self {
+        PhotoUploader {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(UPLOAD_TIMEOUT_SEC))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Uploads the files to the wall of the user or of the community if group_id is not zero,
+    /// returns the attachments like `photo123_456` in the order of the files
+    pub async fn upload(
+        &self,
+        api: &dyn VkApi,
+        group_id: i64,
+        files: &[String],
+    ) -> Result<Vec<String>, UploadError> {
+        let mut attachments = Vec::with_capacity(files.len());
+        for file in files {
+            let mut params = Params::new();
+            if group_id != 0 {
+                params.insert("group_id".into(), group_id.to_string());
+            }
+            let server = api.photos_get_wall_upload_server(params.clone()).await?;
+            let uploaded = self.post(&server.upload_url, file).await?;
+            params.insert("server".into(), uploaded.server.to_string());
+            params.insert("photo".into(), uploaded.photo);
+            params.insert("hash".into(), uploaded.hash);
+            let saved = api.photos_save_wall_photo(params).await?;
+            match saved.first() {
+                Some(photo) => attachments.push(photo.attachment()),
+                None => return Err(UploadError::Server("the photo has not been saved".into())),
+            }
+        }
+        Ok(attachments)
+    }
+
+    async fn post(&self, upload_url: &str, file: &str) -> Result<UploadedPhoto, UploadError> {
+        let content = tokio::fs::read(file)
+            .await
+            .map_err(|e| UploadError::File(format!("{}: {}", file, e)))?;
+        let name = Path::new(file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "photo.jpg".to_string());
+        let form = Form::new().part("photo", Part::bytes(content).file_name(name));
+        let body = self
+            .http
+            .post(upload_url)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| UploadError::Server(format!("{}", e)))?
+            .text()
+            .await
+            .map_err(|e| UploadError::Server(format!("{}", e)))?;
+        let uploaded = serde_json::from_str::<UploadedPhoto>(&body)
+            .map_err(|e| UploadError::Server(format!("{}: {}", e, body)))?;
+        // the server answers with the empty list if it has not accepted the file
+        if uploaded.photo.is_empty() || uploaded.photo == "[]" {
+            return Err(UploadError::Server(format!("rejected: {}", body)));
+        }
+        Ok(uploaded)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::stand_in::{photo_file, stand_in};
+    use crate::vk_provider::FixtureApi;
+
+    #[tokio::test]
+    async fn upload_to_user_wall() {
+        let (address, rx_request) = stand_in(
+            "/upload",
+            vec![
+                r#"{"server": 837, "photo": "[{\"photo\":\"a1\"}]", "hash": "h1"}"#,
+                r#"{"server": 837, "photo": "[{\"photo\":\"a2\"}]", "hash": "h2"}"#,
+            ],
+        );
+        let api = FixtureApi::new()
+            .with_profile(4321, "Anna", "Sidorova")
+            .with_upload_server(&address);
+        let files = vec![
+            photo_file("gvk_upload_1.jpg"),
+            photo_file("gvk_upload_2.jpg"),
+        ];
+        let attachments = PhotoUploader::new()
+            .upload(&api, 0, &files)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(attachments, vec!["photo4321_1", "photo4321_2"]);
+        // the file is posted as the photo field
+        let request = rx_request.recv().unwrap();
+        assert!(request.starts_with("POST /upload?act=do_add&mid=4321&gid=0 "));
+        assert!(request.contains("multipart/form-data; boundary="));
+        assert!(request.contains(r#"name="photo"; filename="gvk_upload_1.jpg""#));
+        assert!(request.contains("not really a jpeg"));
+        // the answer of the server is saved as is
+        assert_eq!(
+            api.saved_photos(),
+            vec![r#"[{"photo":"a1"}]"#, r#"[{"photo":"a2"}]"#]
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_to_community_wall() {
+        let (address, rx_request) = stand_in(
+            "/upload",
+            vec![r#"{"server": 625, "photo": "[{\"photo\":\"g1\"}]", "hash": "h1"}"#],
+        );
+        let api = FixtureApi::new()
+            .with_profile(4321, "Anna", "Sidorova")
+            .with_upload_server(&address);
+        let attachments = PhotoUploader::new()
+            .upload(&api, 200100, &[photo_file("gvk_upload_group.jpg")])
+            .await
+            .ok()
+            .unwrap();
+        // the photo belongs to the community
+        assert_eq!(attachments, vec!["photo-200100_1"]);
+        let request = rx_request.recv().unwrap();
+        assert!(request.starts_with("POST /upload?act=do_add&mid=4321&gid=200100 "));
+    }
+
+    #[tokio::test]
+    async fn fail_rejected_and_missing_files() {
+        let (address, _rx_request) = stand_in(
+            "/upload",
+            vec![r#"{"server": 837, "photo": "[]", "hash": ""}"#],
+        );
+        let api = FixtureApi::new().with_upload_server(&address);
+        let uploader = PhotoUploader::new();
+        let rejected = uploader
+            .upload(&api, 0, &[photo_file("gvk_upload_3.jpg")])
+            .await;
+        assert!(matches!(rejected, Err(UploadError::Server(_))));
+        let missing = uploader
+            .upload(&api, 0, &["/nonexistent/photo.jpg".to_string()])
+            .await;
+        assert!(matches!(missing, Err(UploadError::File(_))));
+        // there is no upload server without access to photos
+        let denied = uploader
+            .upload(&FixtureApi::new(), 0, &[photo_file("gvk_upload_4.jpg")])
+            .await;
+        assert!(matches!(denied, Err(UploadError::Api(_))));
+    }
+}
@@ -0,0 +1,102 @@
+//! Schedule of polling vk.com for the news.
+//!
+//! The news are polled every interval set by user while requests succeed. Every failed request doubles the delay
+//! until the next one up to MAX_BACKOFF_SEC, "too many requests" (error 6) makes the delay at least
+//! RATE_LIMIT_BACKOFF_SEC. While the application is in background (its window is not active)
+//! the news are polled BACKGROUND_FACTOR times rarer.
+use super::vk_api::is_rate_limit;
+use rvk::error::Error;
+use tokio::time::Duration;
+
+// the upper limit of the delay after the failed requests, 30 minutes
+const MAX_BACKOFF_SEC: u64 = 1_800;
+// the least delay after vk.com has reported too many requests, 5 minutes
+const RATE_LIMIT_BACKOFF_SEC: u64 = 300;
+// slow down factor while application is in background
+const BACKGROUND_FACTOR: u32 = 5;
+
+pub struct PollSchedule {
+    interval: Duration,
+    // a number of failed requests in a row
+    failures: u32,
+    // vk.com has reported too many requests
+    rate_limited: bool,
+    background: bool,
+}
+
+impl PollSchedule {
+    pub fn new(interval: Duration) -> Self {
+        PollSchedule {
+            interval,
+            failures: 0,
+            rate_limited: false,
+            background: false,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn set_background(&mut self, background: bool) {
+        self.background = background;
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+        self.rate_limited = false;
+    }
+
+    pub fn failed(&mut self, error: &Error) {
+        self.failures = self.failures.saturating_add(1);
+        self.rate_limited = is_rate_limit(error);
+    }
+
+    /// Returns delay until the next poll
+    pub fn delay(&self) -> Duration {
+        let max_backoff = Duration::from_secs(MAX_BACKOFF_SEC);
+        let mut delay = if self.failures == 0 {
+            self.interval
+        } else {
+            // 2^failures, the shift is limited to not to overflow
+            let factor = 1u32 << self.failures.min(16);
+            self.interval
+                .checked_mul(factor)
+                .unwrap_or(max_backoff)
+                .min(max_backoff)
+                .max(self.interval)
+        };
+        if self.rate_limited {
+            delay = delay.max(Duration::from_secs(RATE_LIMIT_BACKOFF_SEC));
+        }
+        if self.background {
+            delay = delay * BACKGROUND_FACTOR;
+        }
+        delay
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_and_recover() {
+        let interval = Duration::from_secs(60);
+        let mut schedule = PollSchedule::new(interval);
+        assert_eq!(schedule.delay(), interval);
+        let error = Error::Other("network is unreachable".to_string());
+        schedule.failed(&error);
+        assert_eq!(schedule.delay(), interval * 2);
+        schedule.failed(&error);
+        assert_eq!(schedule.delay(), interval * 4);
+        for _ in 0..100 {
+            schedule.failed(&error);
+        }
+        assert_eq!(schedule.delay(), Duration::from_secs(MAX_BACKOFF_SEC));
+        schedule.succeeded();
+        assert_eq!(schedule.delay(), interval);
+        schedule.set_background(true);
+        assert_eq!(schedule.delay(), interval * BACKGROUND_FACTOR);
+    }
+}
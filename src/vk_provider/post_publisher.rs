@@ -0,0 +1,131 @@
+//! Posts the user publishes to the own wall or to the wall of the community they administer,
+//! <https://vk.com/dev/wall.post>
+//!
+//! Photos are uploaded to the wall the post goes to, so the community post carries the community photos
+use super::photo_upload::{PhotoUploader, UploadError};
+use super::VkApi;
+use crate::models::PostDraft;
+use rvk::Params;
+
+/// Publishes the post or schedules it if the draft has publish_date, returns id of the post
+pub async fn publish(
+    api: &dyn VkApi,
+    uploader: &PhotoUploader,
+    draft: &PostDraft,
+) -> Result<i64, UploadError> {
+    let attachments = uploader.upload(api, draft.group_id, &draft.photos).await?;
+    let mut params = Params::new();
+    // the user's own wall is the default one
+    if draft.group_id != 0 {
+        params.insert("owner_id".into(), (-draft.group_id).to_string());
+        if draft.from_group {
+            params.insert("from_group".into(), "1".into());
+        }
+    }
+    params.insert("message".into(), draft.text.clone());
+    if !attachments.is_empty() {
+        params.insert("attachments".into(), attachments.join(","));
+    }
+    if let Some(publish_date) = draft.publish_date {
+        params.insert("publish_date".into(), publish_date.to_string());
+    }
+    Ok(api.wall_post(params).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::stand_in::{photo_file, stand_in};
+    use crate::vk_provider::FixtureApi;
+
+    fn wall_params(owner_id: i64, postponed: bool) -> Params {
+        let mut params = Params::new();
+        params.insert("owner_id".into(), owner_id.to_string());
+        if postponed {
+            params.insert("filter".into(), "postponed".into());
+        }
+        params
+    }
+
+    #[tokio::test]
+    async fn publish_to_own_wall() {
+        let api = FixtureApi::new().with_profile(4321, "Anna", "Sidorova");
+        let draft = PostDraft {
+            group_id: 0,
+            text: "hello".to_string(),
+            photos: Vec::new(),
+            // ignored for the own wall
+            from_group: true,
+            publish_date: None,
+        };
+        let id = publish(&api, &PhotoUploader::new(), &draft)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(id, 1);
+        let wall = api.wall_get(wall_params(4321, false)).await.ok().unwrap();
+        let items = wall.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].post_id, Some(1));
+        assert_eq!(items[0].source_id, 4321);
+    }
+
+    #[tokio::test]
+    async fn publish_to_community_with_photo_and_schedule() {
+        let (address, rx_request) = stand_in(
+            "/upload",
+            vec![r#"{"server": 625, "photo": "[{\"photo\":\"g1\"}]", "hash": "h1"}"#],
+        );
+        let api = FixtureApi::new()
+            .with_profile(4321, "Anna", "Sidorova")
+            .with_upload_server(&address);
+        let draft = PostDraft {
+            group_id: 200100,
+            text: "meetup tomorrow".to_string(),
+            photos: vec![photo_file("gvk_post_photo.jpg")],
+            from_group: true,
+            publish_date: Some(chrono::Utc::now().timestamp() + 3600),
+        };
+        let id = publish(&api, &PhotoUploader::new(), &draft)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(id, 1);
+        // the photo is uploaded to the community wall
+        let request = rx_request.recv().unwrap();
+        assert!(request.contains("gid=200100"));
+        assert_eq!(api.saved_photos().len(), 1);
+        // the scheduled post is not on the wall yet
+        let wall = api
+            .wall_get(wall_params(-200100, false))
+            .await
+            .ok()
+            .unwrap();
+        assert!(wall.items.unwrap_or_default().is_empty());
+        let postponed = api.wall_get(wall_params(-200100, true)).await.ok().unwrap();
+        let items = postponed.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source_id, -200100);
+    }
+
+    #[tokio::test]
+    async fn fail_without_upload_server() {
+        let api = FixtureApi::new();
+        let draft = PostDraft {
+            group_id: 200100,
+            text: String::new(),
+            photos: vec![photo_file("gvk_post_denied.jpg")],
+            from_group: false,
+            publish_date: None,
+        };
+        let result = publish(&api, &PhotoUploader::new(), &draft).await;
+        assert!(matches!(result, Err(UploadError::Api(_))));
+        // nothing is published without the photo
+        let wall = api
+            .wall_get(wall_params(-200100, false))
+            .await
+            .ok()
+            .unwrap();
+        assert!(wall.items.unwrap_or_default().is_empty());
+    }
+}
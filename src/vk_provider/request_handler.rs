@@ -0,0 +1,494 @@
+//! Requests of UI for the account being served
+//!
+//! Every request which calls the API is served by a task of its own, so a slow page, uploading photos
+//! or asking user to grant scopes does not hold back the rest of the requests, e.g. switching the account.
+//! The pages of a feature are served one by one in the order they are requested, so they follow
+//! each other, and so are the likes of a post
+use super::chat_provider::{self, ChatProvider};
+use super::comments_provider::{self, CommentsProvider};
+use super::communities_provider;
+use super::constants::{
+    SCOPES_FRIENDS, SCOPES_GROUPS, SCOPES_LIKES, SCOPES_MESSAGES, SCOPES_NEWSFEED, SCOPES_PUBLISH,
+};
+use super::friends_provider;
+use super::in_order::InOrder;
+use super::likes_provider;
+use super::photo_upload::PhotoUploader;
+use super::post_publisher;
+use super::scope_granter::{call_with_scopes, ScopeGranter};
+use super::wall_provider::WallProvider;
+use super::{do_send, is_auth_error, MessageSender, RequestReceiver, SharedApi, Target};
+use super::{AccessTokenProvider, NewsProvider, NewsUpdate, SourcesManager, SourcesUpdate};
+use crate::models::{NewsItemId, PostDraft, UserModel};
+use crate::settings::Settings;
+use crate::storage::SharedStorage;
+use crate::ui::{ChatRequest, CommentRequest, CommunityRequest, Message, PublishRequest, Request};
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::{watch, Notify, RwLock};
+
+/// The account the requests are served for, shared with the tasks serving the long requests
+pub struct RequestHandler {
+    pub vk_api: SharedApi,
+    pub storage: SharedStorage,
+    pub tx_msg: MessageSender,
+    pub user_id: String,
+    // author of the messages and comments user sends
+    pub own: UserModel,
+    pub granter: Arc<ScopeGranter>,
+    // composes authorization URI to re-authenticate, replaced when settings change
+    pub access: Arc<RwLock<AccessTokenProvider>>,
+    pub news: Arc<NewsProvider>,
+    pub news_sources: Arc<SourcesManager>,
+    pub chat: ChatProvider,
+    // pages back the wall of the source selected in UI
+    pub wall: WallProvider,
+    // pages comments of the post opened in UI
+    pub comments: CommentsProvider,
+    // uploads photos attached to comments and posts
+    pub uploader: PhotoUploader,
+    // serve the pages of each feature one by one
+    pub news_order: InOrder<()>,
+    pub chat_order: InOrder<()>,
+    pub wall_order: InOrder<()>,
+    pub comments_order: InOrder<()>,
+    // likes and unlikes of each post are served in the order user has made them
+    pub likes_order: InOrder<NewsItemId>,
+    // wakes up the main provider task to get news immediately
+    pub refresh: Arc<Notify>,
+    pub background: Arc<AtomicBool>,
+    pub poll_interval_sec: Arc<AtomicU64>,
+    pub friends_interval_sec: Arc<AtomicU64>,
+    pub friends_refresh: Arc<Notify>,
+    pub logout: Arc<AtomicBool>,
+    // changes when serving the account has stopped, the requests being served are dropped then
+    pub stopped: watch::Receiver<bool>,
+}
+
+impl RequestHandler {
+    /// Serves the requests until UI has stopped or user has switched the account,
+    /// returns the account to serve next along with the requests
+    pub(super) async fn run(
+        self: Arc<Self>,
+        rx_req: RequestReceiver,
+    ) -> Option<(Target, RequestReceiver)> {
+        log::info!("starting UI requests handler");
+        let mut rx_req = rx_req;
+        let next = loop {
+            let req = match rx_req.recv().await {
+                Some(req) => req,
+                None => {
+                    log::warn!(
+                        "request channel has closed by sender(s), there are no more requests"
+                    );
+                    break None;
+                }
+            };
+            let served = match req {
+                // more news requested vy UI
+                Request::NewsNext => {
+                    log::debug!("UI requested more news");
+                    self.refresh.notify_one();
+                    Some(())
+                }
+                // older news requested by UI
+                Request::NewsOlder => {
+                    self.spawn(self.news_order.queue((), self.clone().older_news()))
+                }
+                // chats page
+                Request::Chat(request) => {
+                    self.spawn(self.chat_order.queue((), self.clone().chat(request)))
+                }
+                // friends page has been opened
+                Request::Friends => self.spawn(self.clone().friends()),
+                // communities page
+                Request::Communities(request) => self.spawn(self.clone().communities(request)),
+                // the source history has been opened or scrolled back
+                Request::SourceHistory { source_id, restart } => self.spawn(
+                    self.wall_order
+                        .queue((), self.clone().source_history(source_id, restart)),
+                ),
+                // user has commented the opened post, the composer waits for the answer
+                Request::Comments(CommentRequest::Send {
+                    text,
+                    reply_to,
+                    thread,
+                    photos,
+                }) => self.spawn(self.clone().send_comment(text, reply_to, thread, photos)),
+                // the post has been opened or its comments have been scrolled
+                Request::Comments(request) => self.spawn(
+                    self.comments_order
+                        .queue((), self.clone().comments(request)),
+                ),
+                // the post composer has been opened
+                Request::Publish(PublishRequest::Targets) => {
+                    self.spawn(self.clone().post_targets())
+                }
+                // user has composed the post, the composer waits for the answer
+                Request::Publish(PublishRequest::Post(draft)) => {
+                    self.spawn(self.clone().publish(draft))
+                }
+                // user has liked or unliked the post, UI has already shown the change
+                Request::Like { item, liked } => self.spawn(
+                    self.likes_order
+                        .queue(item.clone(), self.clone().like(item, liked)),
+                ),
+                // user has muted or unmuted news of the source
+                Request::MuteSource { source_id, muted } => {
+                    self.mute_source(source_id, muted).await
+                }
+                // user has marked the source as favourite or unmarked it
+                Request::FavouriteSource {
+                    source_id,
+                    favourite,
+                } => self.favourite_source(source_id, favourite).await,
+                // application window has been activated or deactivated
+                Request::Background(is_background) => {
+                    log::debug!("application is in background: {}", is_background);
+                    let was_background = self.background.swap(is_background, Ordering::SeqCst);
+                    if was_background && !is_background {
+                        // user is back, show the fresh news
+                        self.refresh.notify_one();
+                    }
+                    Some(())
+                }
+                // user has changed settings
+                Request::Settings(settings) => {
+                    self.apply_settings(&settings).await;
+                    Some(())
+                }
+                // user has selected another account
+                Request::SwitchAccount(user_id) => {
+                    log::info!("switching to account {}", user_id);
+                    self.storage.prepare_to_stop();
+                    break Some((Target::Known(user_id), rx_req));
+                }
+                Request::AddAccount => {
+                    log::info!("adding account");
+                    self.storage.prepare_to_stop();
+                    break Some((Target::New, rx_req));
+                }
+                // then any account is authenticated anew
+                Request::Logout => {
+                    self.storage.prepare_to_stop();
+                    self.logout.store(true, Ordering::SeqCst);
+                    break Some((Target::Current, rx_req));
+                }
+                Request::Stop => {
+                    self.storage.prepare_to_stop();
+                    break None;
+                }
+            };
+            if served.is_none() {
+                break None;
+            }
+        };
+        log::info!("UI requests handler has stopped");
+        next
+    }
+
+    // serves the request by a task of its own until serving the account has stopped
+    fn spawn(&self, serve: impl Future<Output = Option<()>> + Send + 'static) -> Option<()> {
+        let mut stopped = self.stopped.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = serve => {}
+                _ = stopped.changed() => log::debug!("serving has stopped, the request is dropped"),
+            }
+        });
+        Some(())
+    }
+
+    // returns None if UI has stopped
+    fn send(&self, msg: Message) -> Option<()> {
+        if do_send(&self.tx_msg, msg) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    async fn older_news(self: Arc<Self>) -> Option<()> {
+        let news_feed = match self.news.prev_update(self.vk_api.as_ref()).await {
+            Ok(news_feed) => news_feed,
+            Err(e) => {
+                if is_auth_error(&e) {
+                    // the main task re-authenticates on its next attempt
+                    self.refresh.notify_one();
+                }
+                return Some(());
+            }
+        };
+        if let Some(items) = &news_feed.items {
+            log::debug!("got {} older news items", items.len());
+        }
+        let update = NewsUpdate::new_async(&news_feed, &self.storage).await;
+        if let Err(e) = self.storage.append_news_async(update.items()).await {
+            log::warn!("failed storing older news: {}", e);
+        }
+        self.send(Message::OlderNews(update))?;
+        // prepare sources update
+        if let Some(new_items) = self.news_sources.add_news(
+            &news_feed,
+            SourcesUpdate::new_async(&news_feed, &self.storage).await,
+            &self.storage,
+        ) {
+            // send sources update
+            self.send(Message::NewsSources(new_items))?;
+        }
+        Some(())
+    }
+
+    async fn chat(self: Arc<Self>, request: ChatRequest) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_MESSAGES, || {
+            chat_provider::serve_request(
+                &request,
+                &self.chat,
+                api,
+                &self.user_id,
+                &self.own,
+                &self.storage,
+            )
+        })
+        .await?;
+        match result {
+            Ok(msg) => self.send(msg),
+            Err(e) => {
+                log::error!("failed serving chats: {}", e);
+                Some(())
+            }
+        }
+    }
+
+    async fn friends(self: Arc<Self>) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_FRIENDS, || {
+            friends_provider::query_async(api, &self.storage)
+        })
+        .await?;
+        match result {
+            Ok(update) => self.send(Message::Friends(update)),
+            Err(e) => {
+                log::error!("failed getting friends: {}", e);
+                Some(())
+            }
+        }
+    }
+
+    async fn communities(self: Arc<Self>, request: CommunityRequest) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_GROUPS, || {
+            communities_provider::serve_request(&request, api, &self.storage)
+        })
+        .await?;
+        let msg = match result {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::error!("failed serving communities: {}", e);
+                // UI has already shown the change, so take it back
+                match request {
+                    CommunityRequest::Join(group_id) => Message::Membership {
+                        group_id,
+                        member: false,
+                    },
+                    CommunityRequest::Leave(group_id) => Message::Membership {
+                        group_id,
+                        member: true,
+                    },
+                    CommunityRequest::List => return Some(()),
+                }
+            }
+        };
+        self.send(msg)
+    }
+
+    async fn source_history(self: Arc<Self>, source_id: i64, restart: bool) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_NEWSFEED, || {
+            self.wall.next_page(api, &self.storage, source_id, restart)
+        })
+        .await?;
+        match result {
+            Ok(update) => self.send(Message::SourceNews { source_id, update }),
+            Err(e) => {
+                log::error!("failed getting wall of {}: {}", source_id, e);
+                Some(())
+            }
+        }
+    }
+
+    async fn send_comment(
+        self: Arc<Self>,
+        text: String,
+        reply_to: i64,
+        thread: i64,
+        photos: Vec<String>,
+    ) -> Option<()> {
+        let post = match self.comments.post() {
+            Some(post) => post,
+            None => return Some(()),
+        };
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_PUBLISH, || {
+            self.comments
+                .send(api, &self.uploader, &text, reply_to, &photos)
+        })
+        .await?;
+        let comment = match result {
+            Ok(id) => Some(comments_provider::sent_comment(
+                id,
+                &text,
+                thread,
+                &photos,
+                &self.user_id,
+                &self.own,
+            )),
+            Err(e) => {
+                log::error!("failed sending the comment: {}", e);
+                let error = format!("Failed to send the comment: {}", e);
+                self.send(Message::Error(error))?;
+                None
+            }
+        };
+        self.send(Message::CommentSent {
+            post,
+            reply_to,
+            comment,
+        })
+    }
+
+    async fn comments(self: Arc<Self>, request: CommentRequest) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_NEWSFEED, || {
+            comments_provider::serve_request(&request, &self.comments, api, &self.storage)
+        })
+        .await?;
+        let msg = match result {
+            Ok(Some(msg)) => msg,
+            Ok(None) => return Some(()),
+            Err(e) => {
+                log::error!("failed getting comments: {}", e);
+                Message::Error(format!("Failed to load the comments: {}", e))
+            }
+        };
+        self.send(msg)
+    }
+
+    async fn post_targets(self: Arc<Self>) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_GROUPS, || {
+            communities_provider::query_admin_async(api, &self.storage)
+        })
+        .await?;
+        match result {
+            Ok(update) => self.send(Message::PostTargets(update)),
+            Err(e) => {
+                // the own wall is still there to publish to
+                log::error!("failed getting administered communities: {}", e);
+                Some(())
+            }
+        }
+    }
+
+    async fn publish(self: Arc<Self>, draft: PostDraft) -> Option<()> {
+        log::info!("publishing {}", draft);
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_PUBLISH, || {
+            post_publisher::publish(api, &self.uploader, &draft)
+        })
+        .await?;
+        let post_id = match result {
+            Ok(post_id) => {
+                // the post might be in news already
+                if draft.publish_date.is_none() {
+                    self.refresh.notify_one();
+                }
+                Some(post_id)
+            }
+            Err(e) => {
+                log::error!("failed publishing the post: {}", e);
+                let error = format!("Failed to publish the post: {}", e);
+                self.send(Message::Error(error))?;
+                None
+            }
+        };
+        self.send(Message::Published(post_id))
+    }
+
+    async fn like(self: Arc<Self>, item: NewsItemId, liked: bool) -> Option<()> {
+        let api = self.vk_api.as_ref();
+        let result = call_with_scopes(&self.granter, &SCOPES_LIKES, || {
+            likes_provider::set_liked(api, &item, liked)
+        })
+        .await?;
+        let msg = match result {
+            Ok(likes) => Message::Liked {
+                item,
+                liked,
+                likes: Some(likes),
+            },
+            Err(e) => {
+                log::error!("failed liking {}: {}", item, e);
+                let action = if liked { "like" } else { "unlike" };
+                let error = format!("Failed to {} the post: {}", action, e);
+                self.send(Message::Error(error))?;
+                // so take it back
+                Message::Liked {
+                    item,
+                    liked: !liked,
+                    likes: None,
+                }
+            }
+        };
+        self.send(msg)
+    }
+
+    async fn mute_source(&self, source_id: i64, muted: bool) -> Option<()> {
+        log::info!("muting news of {}: {}", source_id, muted);
+        let muted = match self.storage.set_muted_async(source_id, muted).await {
+            Ok(_) => muted,
+            Err(e) => {
+                log::error!("failed muting news source: {}", e);
+                self.storage.is_muted(source_id)
+            }
+        };
+        self.send(Message::SourceMuted { source_id, muted })
+    }
+
+    async fn favourite_source(&self, source_id: i64, favourite: bool) -> Option<()> {
+        log::info!("marking {} as favourite: {}", source_id, favourite);
+        let source = self.news_sources.set_favourite(source_id, favourite);
+        self.news_sources.save_async(&self.storage).await;
+        match source {
+            Some(source) => self.send(Message::NewsSources(SourcesUpdate {
+                items: vec![source],
+            })),
+            None => Some(()),
+        }
+    }
+
+    async fn apply_settings(&self, settings: &Settings) {
+        log::info!("applying changed settings");
+        self.news.apply_settings(settings);
+        self.storage.apply_settings(settings);
+        self.wall.apply_settings(settings);
+        *self.access.write().await = AccessTokenProvider::new(settings);
+        let was_interval = self
+            .poll_interval_sec
+            .swap(settings.poll_interval_sec, Ordering::SeqCst);
+        if was_interval != settings.poll_interval_sec {
+            // restart the period
+            self.refresh.notify_one();
+        }
+        let was_friends_interval = self
+            .friends_interval_sec
+            .swap(settings.friends_poll_interval_sec, Ordering::SeqCst);
+        if was_friends_interval != settings.friends_poll_interval_sec {
+            self.friends_refresh.notify_one();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Access scopes granted on demand, <https://vk.com/dev/permissions>
+//!
+//! The features ask user to grant the scopes they require when vk.com denies access for the lack of them,
+//! the request is repeated with the new access token then
+use super::photo_upload::UploadError;
+use super::{is_auth_error, is_permission_error, reauthenticate};
+use super::{AccessTokenProvider, AuthResponse, MessageSender, SharedApi, VkApi};
+use crate::storage::{SharedStorage, Storage};
+use rvk::error::Error;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex as SyncMutex};
+use tokio::sync::{Mutex, Notify, RwLock};
+
+/// Errors of the calls which might fail for the lack of the scopes
+pub trait ApiFailure {
+    /// Returns the error of vk.com API if it is the one
+    fn api_error(&self) -> Option<&Error>;
+}
+
+impl ApiFailure for Error {
+    fn api_error(&self) -> Option<&Error> {
+        Some(self)
+    }
+}
+
+impl ApiFailure for UploadError {
+    fn api_error(&self) -> Option<&Error> {
+        UploadError::api_error(self)
+    }
+}
+
+/// Asks user to grant the scopes a feature requires when vk.com denies access to it.
+/// Every scope is asked for once while the account is served, not to ask again if access is still denied,
+/// the tasks serving the account share the granter
+pub struct ScopeGranter {
+    vk_api: SharedApi,
+    access: Arc<RwLock<AccessTokenProvider>>,
+    user_id: String,
+    storage: SharedStorage,
+    tx_msg: MessageSender,
+    // wakes up the main task which re-authenticates if the access token has been rejected
+    refresh: Arc<Notify>,
+    // scopes user has been asked for
+    requested: SyncMutex<HashSet<String>>,
+    // user is asked for one feature at a time
+    asking: Mutex<()>,
+}
+
+impl ScopeGranter {
+    pub fn new(
+        vk_api: SharedApi,
+        access: Arc<RwLock<AccessTokenProvider>>,
+        user_id: &str,
+        storage: SharedStorage,
+        tx_msg: MessageSender,
+        refresh: Arc<Notify>,
+    ) -> Self {
+        ScopeGranter {
+            vk_api,
+            access,
+            user_id: user_id.to_string(),
+            storage,
+            tx_msg,
+            refresh,
+            requested: SyncMutex::new(HashSet::new()),
+            asking: Mutex::new(()),
+        }
+    }
+
+    /// Asks user to grant the scopes if vk.com has denied access for the lack of them and they have not
+    /// been asked for yet. Returns the new auth if they have been granted, None if UI has stopped
+    pub async fn grant_on_error(
+        &self,
+        error: &Error,
+        scopes: &[&str],
+    ) -> Option<Option<AuthResponse>> {
+        if is_auth_error(error) {
+            // the main task re-authenticates on its next attempt
+            self.refresh.notify_one();
+            return Some(None);
+        }
+        if !is_permission_error(error) {
+            return Some(None);
+        }
+        let _asking = self.asking.lock().await;
+        if !self.set_requested(scopes) {
+            return Some(None);
+        }
+        log::warn!("access to {} is denied: {}", scopes.join(", "), error);
+        let access = self.access.read().await.clone();
+        grant_scopes(
+            self.vk_api.as_ref(),
+            access,
+            scopes,
+            &self.user_id,
+            &self.storage,
+            &self.tx_msg,
+        )
+        .await
+        .map(Some)
+    }
+
+    // returns false if user has been asked for all the scopes already
+    fn set_requested(&self, scopes: &[&str]) -> bool {
+        match self.requested.lock() {
+            Ok(mut requested) if scopes.iter().any(|scope| !requested.contains(*scope)) => {
+                requested.extend(scopes.iter().map(|scope| scope.to_string()));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Calls vk.com, if the access token lacks the scopes the call requires asks user to grant them
+/// and calls once more. Returns None if UI has stopped
+pub async fn call_with_scopes<T, E, F, Fut>(
+    granter: &ScopeGranter,
+    scopes: &[&str],
+    call: F,
+) -> Option<Result<T, E>>
+where
+    E: ApiFailure,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let result = call().await;
+    let granted = match result.as_ref().err().and_then(ApiFailure::api_error) {
+        Some(e) => granter.grant_on_error(e, scopes).await?,
+        None => None,
+    };
+    Some(match granted {
+        Some(_) => call().await,
+        None => result,
+    })
+}
+
+/// Asks UI to authenticate the same user again granting the scopes the feature requires,
+/// the granted scopes are kept with the account to be requested on the next logins too.
+/// Returns None if UI has stopped
+async fn grant_scopes(
+    vk_api: &dyn VkApi,
+    access: AccessTokenProvider,
+    scopes: &[&str],
+    user_id: &str,
+    storage: &Storage,
+    tx_msg: &MessageSender,
+) -> Option<AuthResponse> {
+    log::info!("asking user to grant access to {}", scopes.join(", "));
+    let auth = reauthenticate(
+        vk_api,
+        access.with_required(scopes),
+        user_id,
+        storage,
+        tx_msg,
+    )
+    .await?;
+    match storage.load_accounts_async().await {
+        Ok(mut accounts) => {
+            let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+            accounts.grant(user_id, &scopes);
+            if let Err(e) = storage.save_accounts_async(&accounts).await {
+                log::warn!("failed to store accounts: {}", e);
+            }
+        }
+        Err(e) => log::warn!("failed loading accounts: {}", e),
+    }
+    Some(auth)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::constants::API_ERROR_PERMISSION_DENIED;
+    use super::*;
+    use crate::settings::Settings;
+    use crate::ui::Message;
+    use crate::vk_provider::FixtureApi;
+    use rvk::error::APIError;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn ask_for_scopes_once() {
+        let storage = Arc::new(Storage::for_test("granter"));
+        let (tx_msg, mut rx_msg) = mpsc::channel(10);
+        let granter = ScopeGranter::new(
+            Arc::new(FixtureApi::new()),
+            Arc::new(RwLock::new(AccessTokenProvider::new(&Settings::default()))),
+            "184946538",
+            storage,
+            tx_msg,
+            Arc::new(Notify::new()),
+        );
+        let ui = tokio::spawn(async move {
+            let mut asked = Vec::new();
+            while let Some(msg) = rx_msg.recv().await {
+                if let Message::Auth(access, tx_response) = msg {
+                    asked.push(access.get_required().to_vec());
+                    let _ = tx_response.send(AuthResponse::new("5678", "184946538"));
+                }
+            }
+            asked
+        });
+        let denied = serde_json::from_value::<APIError>(serde_json::json!({
+            "error_code": API_ERROR_PERMISSION_DENIED,
+            "error_msg": "",
+            "request_params": [],
+        }))
+        .map(Error::API)
+        .unwrap();
+        let granted = granter.grant_on_error(&denied, &["wall", "friends"]).await;
+        assert!(matches!(granted, Some(Some(_))));
+        // the poll loop is denied news again, the scopes have been asked for already
+        let granted = granter.grant_on_error(&denied, &["wall", "friends"]).await;
+        assert!(matches!(granted, Some(None)));
+        // the feature lacking one more scope asks for it
+        let granted = granter.grant_on_error(&denied, &["wall", "groups"]).await;
+        assert!(matches!(granted, Some(Some(_))));
+        drop(granter);
+        let asked = ui.await.unwrap();
+        assert_eq!(asked, [vec!["wall", "friends"], vec!["wall", "groups"]]);
+    }
+}
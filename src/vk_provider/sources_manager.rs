@@ -1,39 +1,225 @@
+//! Keeps the news sources met in news with their statistics and favourite marks,
+//! the statistics and marks are persisted in Storage while the sources are known from news only
 use super::SourcesUpdate;
 use crate::models::NewsSourceModel;
-use std::collections::HashMap;
+use crate::storage::{SourceStats, Storage, NEWS_HISTORY_SEC};
+use rvk::objects::newsfeed::NewsFeed;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
+#[derive(Default)]
+struct State {
+    items: HashMap<i64, NewsSourceModel>,
+    stats: HashMap<i64, SourceStats>,
+    // (source_id, post_id) of the counted posts with their unix time, pages of news might overlap,
+    // the ones older than the news history kept are forgotten
+    counted: HashMap<(i64, i64), i64>,
+    // unix time of the most recent counted post
+    newest: i64,
+    // stats have changed since they were saved
+    changed: bool,
+}
+
 pub struct SourcesManager {
-    pub items: Mutex<HashMap<i64, NewsSourceModel>>,
+    state: Mutex<State>,
 }
 
 impl SourcesManager {
     pub fn new() -> Self {
         SourcesManager {
-            items: Mutex::new(HashMap::new()),
+            state: Mutex::new(State::default()),
         }
     }
 
-    pub fn add_new_sources(&self, update: SourcesUpdate) -> Option<SourcesUpdate> {
-        match self.items.lock() {
-            Ok(mut items) => {
-                let mut new_items = Vec::new();
-                for item in update.items {
-                    if !items.contains_key(&item.id) {
-                        items.insert(item.id, item.clone());
-                        new_items.push(item);
-                    }
-                }
-                if !new_items.is_empty() {
-                    Some(SourcesUpdate { items: new_items })
-                } else {
-                    None
-                }
+    /// Restores the stored statistics and marks, posts of the restored news have already been counted
+    /// given as (source_id, post_id, unix time)
+    pub async fn restore_async(&self, storage: &Storage, restored: &[(i64, i64, i64)]) {
+        let stats = match storage.load_sources_async().await {
+            Ok(stats) => stats,
+            Err(e) => {
+                log::warn!("failed restoring news sources: {}", e);
+                return;
+            }
+        };
+        if let Ok(mut state) = self.state.lock() {
+            state.stats = stats.into_iter().map(|s| (s.id, s)).collect();
+            for &(source_id, post_id, date) in restored {
+                state.counted.insert((source_id, post_id), date);
+                state.newest = state.newest.max(date);
             }
+        }
+    }
+
+    /// Counts posts of the news, returns the sources met the first time and the ones whose statistics have changed
+    pub fn add_news(
+        &self,
+        newsfeed: &NewsFeed,
+        update: SourcesUpdate,
+        storage: &Storage,
+    ) -> Option<SourcesUpdate> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
             Err(e) => {
                 log::error!("failed getting access to items: {}", e);
-                None
+                return None;
+            }
+        };
+        let mut touched = HashSet::new();
+        if let Some(items) = &newsfeed.items {
+            // new photos, friends and the like are not posts
+            for item in items.iter().filter(|item| item.post_id.is_some()) {
+                let post_id = item.post_id.unwrap_or(0);
+                if state
+                    .counted
+                    .insert((item.source_id, post_id), item.date)
+                    .is_none()
+                {
+                    state
+                        .stats
+                        .entry(item.source_id)
+                        .or_insert_with(|| SourceStats::new(item.source_id))
+                        .count_post(item.date);
+                    state.changed = true;
+                    touched.insert(item.source_id);
+                }
+                state.newest = state.newest.max(item.date);
+            }
+            // pages overlap the recent posts, the ones older than the kept history are not met again
+            let horizon = state.newest - NEWS_HISTORY_SEC;
+            state.counted.retain(|_, date| *date >= horizon);
+        }
+        for item in update.items {
+            if !state.items.contains_key(&item.id) {
+                touched.insert(item.id);
+                state.items.insert(item.id, item);
+            }
+        }
+        let mut items = Vec::new();
+        for id in touched {
+            let stats = state.stats.get(&id).cloned().unwrap_or_default();
+            if let Some(item) = state.items.get_mut(&id) {
+                item.posts = stats.posts;
+                item.last_post = stats.last_post;
+                item.favourite = stats.favourite;
+                item.muted = storage.is_muted(id);
+                items.push(item.clone());
+            }
+        }
+        if !items.is_empty() {
+            Some(SourcesUpdate { items })
+        } else {
+            None
+        }
+    }
+
+    /// Marks the source as favourite or not, returns the source if it is known
+    pub fn set_favourite(&self, source_id: i64, favourite: bool) -> Option<NewsSourceModel> {
+        let mut guard = self.state.lock().ok()?;
+        let state = &mut *guard;
+        let stats = state
+            .stats
+            .entry(source_id)
+            .or_insert_with(|| SourceStats::new(source_id));
+        if stats.favourite != favourite {
+            stats.favourite = favourite;
+            state.changed = true;
+        }
+        state.items.get_mut(&source_id).map(|item| {
+            item.favourite = favourite;
+            item.clone()
+        })
+    }
+
+    /// Stores statistics and marks if they have changed
+    pub async fn save_async(&self, storage: &Storage) {
+        let stats: Vec<SourceStats> = match self.state.lock() {
+            Ok(mut state) if state.changed => {
+                state.changed = false;
+                state.stats.values().cloned().collect()
+            }
+            _ => return,
+        };
+        if let Err(e) = storage.save_sources_async(&stats).await {
+            log::warn!("failed storing news sources: {}", e);
+            if let Ok(mut state) = self.state.lock() {
+                state.changed = true;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::FixtureApi;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn count_mark_and_restore() {
+        let storage = Storage::for_test("sources");
+        let path = Path::new("resources/tests/newsfeed").join("2021-01-15_20-57-00.json");
+        let newsfeed = FixtureApi::load_newsfeed(&path).unwrap();
+        let source_id = newsfeed.items.as_ref().unwrap()[0].source_id;
+
+        let manager = SourcesManager::new();
+        let first = manager
+            .add_news(
+                &newsfeed,
+                SourcesUpdate::new_async(&newsfeed, &storage).await,
+                &storage,
+            )
+            .unwrap();
+        let posts = first
+            .items
+            .iter()
+            .find(|s| s.id == source_id)
+            .unwrap()
+            .posts;
+        assert!(posts > 0);
+        // the same page again is neither counted nor reported
+        assert!(manager
+            .add_news(
+                &newsfeed,
+                SourcesUpdate::new_async(&newsfeed, &storage).await,
+                &storage,
+            )
+            .is_none());
+        assert!(manager.set_favourite(source_id, true).unwrap().favourite);
+        manager.save_async(&storage).await;
+
+        // the stored statistics are continued
+        let restored = SourcesManager::new();
+        restored.restore_async(&storage, &[]).await;
+        let update = restored
+            .add_news(
+                &newsfeed,
+                SourcesUpdate::new_async(&newsfeed, &storage).await,
+                &storage,
+            )
+            .unwrap();
+        let source = update.items.iter().find(|s| s.id == source_id).unwrap();
+        assert_eq!(source.posts, 2 * posts);
+        assert!(source.favourite);
+    }
+
+    #[tokio::test]
+    async fn forget_posts_older_than_history() {
+        let storage = Storage::for_test("counted");
+        let path = Path::new("resources/tests/newsfeed").join("2021-01-15_20-57-00.json");
+        let newsfeed = FixtureApi::load_newsfeed(&path).unwrap();
+        let items = newsfeed.items.as_ref().unwrap();
+        let newest = items.iter().map(|item| item.date).max().unwrap();
+        let manager = SourcesManager::new();
+        let expired = (-1, 1, newest - NEWS_HISTORY_SEC - 1);
+        let kept = (-1, 2, newest - NEWS_HISTORY_SEC + 1);
+        manager.restore_async(&storage, &[expired, kept]).await;
+        manager.add_news(
+            &newsfeed,
+            SourcesUpdate::new_async(&newsfeed, &storage).await,
+            &storage,
+        );
+        let state = manager.state.lock().unwrap();
+        assert!(!state.counted.contains_key(&(-1, 1)));
+        assert!(state.counted.contains_key(&(-1, 2)));
+    }
+}
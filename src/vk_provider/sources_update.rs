@@ -42,6 +42,11 @@ impl SourcesUpdate {
                     desc: "friend".to_string(),
                     comment,
                     uri: String::new(),
+                    // statistics and marks are filled by SourcesManager
+                    posts: 0,
+                    last_post: 0,
+                    favourite: false,
+                    muted: false,
                 });
             }
         }
@@ -71,12 +76,17 @@ impl SourcesUpdate {
                     String::new()
                 };
                 items.push(NewsSourceModel {
-                    id: group.id,
+                    // news of groups have negative source_id
+                    id: -group.id,
                     name,
                     avatar,
                     desc,
                     comment,
                     uri,
+                    posts: 0,
+                    last_post: 0,
+                    favourite: false,
+                    muted: false,
                 });
             }
         }
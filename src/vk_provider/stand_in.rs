@@ -0,0 +1,63 @@
+//! Local HTTP stand-in of the servers vk.com hands out, e.g. the upload server of photos
+//! or the long poll server of chats
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// the photos written by the tests running in parallel
+static PHOTOS: AtomicUsize = AtomicUsize::new(0);
+
+/// Answers requests to the path with the JSON bodies in order and reports every whole request,
+/// the body of the request is read by its Content-Length
+pub fn stand_in(path: &str, answers: Vec<&'static str>) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let (tx_request, rx_request) = mpsc::channel();
+    thread::spawn(move || {
+        for body in answers {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let line = line.to_ascii_lowercase();
+                            line.strip_prefix("content-length:")
+                                .and_then(|v| v.trim().parse::<usize>().ok())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            let _ = tx_request.send(String::from_utf8_lossy(&request).to_string());
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    (address, rx_request)
+}
+
+/// Returns the temporary file to upload, it is not a real photo.
+/// Every file is new, so the tests running in parallel do not share them
+pub fn photo_file(name: &str) -> String {
+    let count = PHOTOS.fetch_add(1, Ordering::SeqCst);
+    let path = std::env::temp_dir().join(format!("{}-{}-{}", std::process::id(), count, name));
+    std::fs::write(&path, b"not really a jpeg").unwrap();
+    path.to_string_lossy().to_string()
+}
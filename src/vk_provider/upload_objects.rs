@@ -0,0 +1,32 @@
+//! Objects of uploading photos to the wall, there are no complete ones in rvk,
+//! see <https://vk.com/dev/upload_files?f=2.%20Uploading%20Photos%20on%20User%20or%20Community%20Wall>
+use serde::Deserialize;
+
+/// Answer to photos.getWallUploadServer
+#[derive(Deserialize, Clone)]
+pub struct UploadServer {
+    pub upload_url: String,
+}
+
+/// Answer of the upload server to pass to photos.saveWallPhoto
+#[derive(Deserialize, Clone)]
+pub struct UploadedPhoto {
+    pub server: i64,
+    // "[]" if nothing has been uploaded
+    pub photo: String,
+    pub hash: String,
+}
+
+/// The photo saved by photos.saveWallPhoto
+#[derive(Deserialize, Clone)]
+pub struct SavedPhoto {
+    pub id: i64,
+    pub owner_id: i64,
+}
+
+impl SavedPhoto {
+    /// Returns the photo as an attachment of a comment or a post, <https://vk.com/dev/wall.post>
+    pub fn attachment(&self) -> String {
+        format!("photo{}_{}", self.owner_id, self.id)
+    }
+}
@@ -1,17 +1,18 @@
+use super::VkApi;
 use crate::models;
 use crate::storage::Storage;
-use rvk::{methods::users, objects::user::User as VKUser, APIClient, Params};
+use rvk::{objects::user::User as VKUser, Params};
 use std::boxed::Box;
 use std::fmt;
 
 pub struct User(pub Box<VKUser>);
 
 impl User {
-    pub async fn query_async(api: &APIClient, user_id: &str) -> Option<Self> {
+    pub async fn query_async(api: &dyn VkApi, user_id: &str) -> Option<Self> {
         let mut params = Params::new();
         params.insert("user_id".into(), user_id.into());
         params.insert("fields".into(), "photo_50".into());
-        match users::get::<Vec<VKUser>>(api, params).await {
+        match api.users_get(params).await {
             Ok(mut users) => {
                 if users.len() > 0 {
                     Some(User(Box::new(users.pop().unwrap())))
@@ -0,0 +1,310 @@
+//! vk.com API methods used by the provider.
+//!
+//! * VkApi - the trait every API client implements, all the provider tasks share the client as SharedApi
+//! * RvkApi - the implementation which actually calls vk.com thru rvk::APIClient,
+//!   all the calls are throttled to respect the vk.com limit of requests per second,
+//!   the access token is replaced in place after re-authentication
+//! * FixtureApi (see fixture_api.rs) - in-memory implementation answering from recorded data
+use super::chat_objects::{Conversations, History, LongPollServer};
+use super::comment_objects::Comments;
+use super::community_objects::Communities;
+use super::constants::{
+    API_ERROR_AUTH_FAILED, API_ERROR_PERMISSION_DENIED, API_ERROR_TOO_MANY_REQUESTS,
+};
+use super::friend_objects::{FriendLists, Friends};
+use super::upload_objects::{SavedPhoto, UploadServer};
+use super::wall_objects;
+use async_trait::async_trait;
+use rvk::{
+    error::{Error, Result},
+    methods::{account, friends, groups, likes, messages, newsfeed, photos, users, wall},
+    objects::{account::Account, newsfeed::NewsFeed, user::User},
+    APIClient, Params,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::{sleep_until, Duration, Instant};
+
+pub type SharedApi = Arc<dyn VkApi>;
+
+// vk.com allows up to 3 requests per second for the user access token
+const MIN_REQUEST_PERIOD_MSEC: u64 = 334;
+
+#[async_trait]
+pub trait VkApi: Send + Sync {
+    /// <https://vk.com/dev/newsfeed.get>
+    async fn newsfeed_get(&self, params: Params) -> Result<NewsFeed>;
+    /// <https://vk.com/dev/account.getProfileInfo>
+    async fn account_get_profile_info(&self, params: Params) -> Result<Account>;
+    /// <https://vk.com/dev/users.get>
+    async fn users_get(&self, params: Params) -> Result<Vec<User>>;
+    /// <https://vk.com/dev/messages.getConversations>
+    async fn messages_get_conversations(&self, params: Params) -> Result<Conversations>;
+    /// <https://vk.com/dev/messages.getHistory>
+    async fn messages_get_history(&self, params: Params) -> Result<History>;
+    /// <https://vk.com/dev/messages.send>, returns id of the sent message
+    async fn messages_send(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/messages.getLongPollServer>
+    async fn messages_get_long_poll_server(&self, params: Params) -> Result<LongPollServer>;
+    /// <https://vk.com/dev/friends.get> with fields
+    async fn friends_get(&self, params: Params) -> Result<Friends>;
+    /// <https://vk.com/dev/friends.getLists>
+    async fn friends_get_lists(&self, params: Params) -> Result<FriendLists>;
+    /// <https://vk.com/dev/groups.get> with extended=1
+    async fn groups_get(&self, params: Params) -> Result<Communities>;
+    /// <https://vk.com/dev/groups.join>
+    async fn groups_join(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/groups.leave>
+    async fn groups_leave(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/wall.get> with extended=1, posts of the wall are answered as newsfeed items
+    async fn wall_get(&self, params: Params) -> Result<NewsFeed>;
+    /// <https://vk.com/dev/wall.post>, returns id of the post
+    async fn wall_post(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/wall.getComments> with extended=1
+    async fn wall_get_comments(&self, params: Params) -> Result<Comments>;
+    /// <https://vk.com/dev/wall.createComment>, returns id of the created comment
+    async fn wall_create_comment(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/photos.getWallUploadServer>
+    async fn photos_get_wall_upload_server(&self, params: Params) -> Result<UploadServer>;
+    /// <https://vk.com/dev/photos.saveWallPhoto>
+    async fn photos_save_wall_photo(&self, params: Params) -> Result<Vec<SavedPhoto>>;
+    /// <https://vk.com/dev/likes.add>, returns the count of likes of the item
+    async fn likes_add(&self, params: Params) -> Result<i64>;
+    /// <https://vk.com/dev/likes.delete>, returns the count of likes of the item
+    async fn likes_delete(&self, params: Params) -> Result<i64>;
+    /// Ends the session, the access token is not valid afterwards (auth.logOut)
+    async fn auth_log_out(&self) -> Result<()>;
+    /// Replaces access token for all the tasks sharing the client
+    async fn set_access_token(&self, access_token: &str);
+}
+
+/// Returns true if vk.com has rejected the access token, <https://vk.com/dev/errors>
+pub fn is_auth_error(error: &Error) -> bool {
+    match error {
+        Error::API(e) => e.code() == API_ERROR_AUTH_FAILED,
+        _ => false,
+    }
+}
+
+/// Returns true if the access token lacks the scope the method requires,
+/// access denied to the object itself, e.g. a closed wall, is not the case
+pub fn is_permission_error(error: &Error) -> bool {
+    match error {
+        Error::API(e) => e.code() == API_ERROR_PERMISSION_DENIED,
+        _ => false,
+    }
+}
+
+/// Returns true if vk.com has reported too many requests
+pub fn is_rate_limit(error: &Error) -> bool {
+    match error {
+        Error::API(e) => e.code() == API_ERROR_TOO_MANY_REQUESTS,
+        _ => false,
+    }
+}
+
+pub struct RvkApi {
+    client: RwLock<APIClient>,
+    limiter: RateLimiter,
+}
+
+impl RvkApi {
+    pub fn new(access_token: &str) -> Self {
+        RvkApi {
+            client: RwLock::new(APIClient::new(access_token)),
+            limiter: RateLimiter::new(Duration::from_millis(MIN_REQUEST_PERIOD_MSEC)),
+        }
+    }
+}
+
+/// Spaces requests of all the tasks sharing it at least min_period apart
+pub struct RateLimiter {
+    min_period: Duration,
+    // the earliest moment the next request is allowed at
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(min_period: Duration) -> Self {
+        RateLimiter {
+            min_period,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the request is allowed
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.min_period;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
+
+#[async_trait]
+impl VkApi for RvkApi {
+    async fn newsfeed_get(&self, params: Params) -> Result<NewsFeed> {
+        self.limiter.acquire().await;
+        newsfeed::get::<NewsFeed>(&*self.client.read().await, params).await
+    }
+
+    async fn account_get_profile_info(&self, params: Params) -> Result<Account> {
+        self.limiter.acquire().await;
+        account::get_profile_info::<Account>(&*self.client.read().await, params).await
+    }
+
+    async fn users_get(&self, params: Params) -> Result<Vec<User>> {
+        self.limiter.acquire().await;
+        users::get::<Vec<User>>(&*self.client.read().await, params).await
+    }
+
+    async fn messages_get_conversations(&self, params: Params) -> Result<Conversations> {
+        self.limiter.acquire().await;
+        messages::get_conversations::<Conversations>(&*self.client.read().await, params).await
+    }
+
+    async fn messages_get_history(&self, params: Params) -> Result<History> {
+        self.limiter.acquire().await;
+        messages::get_history::<History>(&*self.client.read().await, params).await
+    }
+
+    async fn messages_send(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        messages::send::<i64>(&*self.client.read().await, params).await
+    }
+
+    async fn messages_get_long_poll_server(&self, params: Params) -> Result<LongPollServer> {
+        self.limiter.acquire().await;
+        messages::get_long_poll_server::<LongPollServer>(&*self.client.read().await, params).await
+    }
+
+    async fn friends_get(&self, params: Params) -> Result<Friends> {
+        self.limiter.acquire().await;
+        friends::get::<Friends>(&*self.client.read().await, params).await
+    }
+
+    async fn friends_get_lists(&self, params: Params) -> Result<FriendLists> {
+        self.limiter.acquire().await;
+        friends::get_lists::<FriendLists>(&*self.client.read().await, params).await
+    }
+
+    async fn groups_get(&self, params: Params) -> Result<Communities> {
+        self.limiter.acquire().await;
+        groups::get::<Communities>(&*self.client.read().await, params).await
+    }
+
+    async fn groups_join(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        groups::join::<i64>(&*self.client.read().await, params).await
+    }
+
+    async fn groups_leave(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        groups::leave::<i64>(&*self.client.read().await, params).await
+    }
+
+    async fn wall_get(&self, params: Params) -> Result<NewsFeed> {
+        self.limiter.acquire().await;
+        let wall = wall::get::<serde_json::Value>(&*self.client.read().await, params).await?;
+        wall_objects::into_newsfeed(wall).map_err(Error::Serde)
+    }
+
+    async fn wall_post(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        let answer = wall::post::<Value>(&*self.client.read().await, params).await?;
+        Ok(answer["post_id"].as_i64().unwrap_or(0))
+    }
+
+    async fn wall_get_comments(&self, params: Params) -> Result<Comments> {
+        self.limiter.acquire().await;
+        wall::get_comments::<Comments>(&*self.client.read().await, params).await
+    }
+
+    async fn wall_create_comment(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        let answer = wall::create_comment::<Value>(&*self.client.read().await, params).await?;
+        Ok(answer["comment_id"].as_i64().unwrap_or(0))
+    }
+
+    async fn photos_get_wall_upload_server(&self, params: Params) -> Result<UploadServer> {
+        self.limiter.acquire().await;
+        photos::get_wall_upload_server::<UploadServer>(&*self.client.read().await, params).await
+    }
+
+    async fn photos_save_wall_photo(&self, params: Params) -> Result<Vec<SavedPhoto>> {
+        self.limiter.acquire().await;
+        photos::save_wall_photo::<Vec<SavedPhoto>>(&*self.client.read().await, params).await
+    }
+
+    async fn likes_add(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        let answer = likes::add::<Value>(&*self.client.read().await, params).await?;
+        Ok(answer["likes"].as_i64().unwrap_or(0))
+    }
+
+    async fn likes_delete(&self, params: Params) -> Result<i64> {
+        self.limiter.acquire().await;
+        let answer = likes::delete::<Value>(&*self.client.read().await, params).await?;
+        Ok(answer["likes"].as_i64().unwrap_or(0))
+    }
+
+    async fn auth_log_out(&self) -> Result<()> {
+        self.limiter.acquire().await;
+        // there is no wrapper of the method in rvk
+        self.client
+            .read()
+            .await
+            .call_method::<Value>("auth.logOut", Params::new())
+            .await
+            .map(|_| ())
+    }
+
+    async fn set_access_token(&self, access_token: &str) {
+        *self.client.write().await = APIClient::new(access_token);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::constants::API_ERROR_ACCESS_DENIED;
+    use super::*;
+    use rvk::error::APIError;
+
+    fn api_error(code: u64) -> Error {
+        serde_json::from_value::<APIError>(serde_json::json!({
+            "error_code": code,
+            "error_msg": "",
+            "request_params": [],
+        }))
+        .map(Error::API)
+        .unwrap()
+    }
+
+    #[test]
+    fn only_permission_error_asks_scopes() {
+        assert!(is_permission_error(&api_error(API_ERROR_PERMISSION_DENIED)));
+        assert!(!is_permission_error(&api_error(API_ERROR_ACCESS_DENIED)));
+        assert!(!is_permission_error(&api_error(API_ERROR_AUTH_FAILED)));
+    }
+
+    #[tokio::test]
+    async fn limit_request_rate() {
+        let limiter = Arc::new(RateLimiter::new(Duration::from_millis(50)));
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.is_ok());
+        }
+        // the first request goes immediately, every next one waits for its slot
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
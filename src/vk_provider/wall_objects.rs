@@ -0,0 +1,61 @@
+//! wall.get answers posts of the wall, they are turned into newsfeed items to display them like news,
+//! see <https://vk.com/dev/wall.get> and <https://vk.com/dev/objects/post>
+use super::constants::NEWS_TYPE_POST;
+use rvk::objects::newsfeed::NewsFeed;
+use serde_json::{json, Value};
+
+/// Converts the answer to wall.get with extended=1 into the newsfeed of posts,
+/// authors of the posts are in its profiles and groups the same way
+pub fn into_newsfeed(wall: Value) -> serde_json::Result<NewsFeed> {
+    let mut wall = wall;
+    let items: Vec<Value> = match wall["items"].take() {
+        Value::Array(posts) => posts.into_iter().map(into_item).collect(),
+        _ => Vec::new(),
+    };
+    serde_json::from_value::<NewsFeed>(json!({
+        "items": items,
+        "profiles": wall["profiles"].take(),
+        "groups": wall["groups"].take(),
+    }))
+}
+
+// the post of the wall is identified by owner_id and id, the newsfeed item by source_id and post_id
+fn into_item(post: Value) -> Value {
+    let mut post = post;
+    if let Some(fields) = post.as_object_mut() {
+        let post_id = fields.remove("id").unwrap_or_else(|| json!(0));
+        let source_id = fields.get("owner_id").cloned().unwrap_or_else(|| json!(0));
+        fields.insert("post_id".to_string(), post_id);
+        fields.insert("source_id".to_string(), source_id);
+        fields.insert("type".to_string(), json!(NEWS_TYPE_POST));
+    }
+    post
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn posts_into_items() {
+        let wall = json!({
+            "count": 1,
+            "items": [{
+                "id": 2194561,
+                "owner_id": -40316705,
+                "from_id": -40316705,
+                "date": 1610797140,
+                "post_type": "post",
+                "text": "text",
+            }],
+            "profiles": [],
+            "groups": [],
+        });
+        let feed = into_newsfeed(wall).unwrap();
+        let items = feed.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source_id, -40316705);
+        assert_eq!(items[0].post_id, Some(2194561));
+        assert_eq!(items[0].type_, NEWS_TYPE_POST);
+    }
+}
@@ -0,0 +1,121 @@
+//! History of a single news source, <https://vk.com/dev/wall.get>
+//!
+//! The wall of the source is paged back from its most recent post,
+//! so user reads the backlog of the source the newsfeed has never delivered
+use super::news_update::NewsUpdate;
+use super::VkApi;
+use crate::settings::Settings;
+use crate::storage::Storage;
+use rvk::{error::Result, Params};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+// wall.get returns at most 100 posts
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Keeps the position in the wall of the source paged back last time
+pub struct WallProvider {
+    // source_id and a number of posts of its wall already received
+    position: Mutex<(i64, usize)>,
+    page_size: AtomicU32,
+}
+
+impl WallProvider {
+    pub fn new(settings: &Settings) -> Self {
+        let provider = WallProvider {
+            position: Mutex::new((0, 0)),
+            page_size: AtomicU32::new(MAX_PAGE_SIZE),
+        };
+        provider.apply_settings(settings);
+        provider
+    }
+
+    /// Applies changed settings
+    pub fn apply_settings(&self, settings: &Settings) {
+        self.page_size
+            .store(settings.news_page_size.min(MAX_PAGE_SIZE), Ordering::SeqCst);
+    }
+
+    /// Returns the next page of posts of the source wall, the most recent first.
+    /// Paging starts from the most recent post if restart is requested or the source has changed,
+    /// the empty update means the wall is over
+    pub async fn next_page(
+        &self,
+        api: &dyn VkApi,
+        storage: &Storage,
+        source_id: i64,
+        restart: bool,
+    ) -> Result<NewsUpdate> {
+        let offset = match self.position.lock() {
+            Ok(mut position) => {
+                if restart || source_id != position.0 {
+                    *position = (source_id, 0);
+                }
+                position.1
+            }
+            Err(_) => 0,
+        };
+        let mut params = Params::new();
+        params.insert("owner_id".into(), source_id.to_string());
+        params.insert("offset".into(), offset.to_string());
+        params.insert(
+            "count".into(),
+            self.page_size.load(Ordering::SeqCst).to_string(),
+        );
+        params.insert("extended".into(), "1".into());
+        let wall = api.wall_get(params).await?;
+        // the posts are counted as received even if some of them are not displayed
+        let received = offset + wall.items.as_ref().map_or(0, |items| items.len());
+        if let Ok(mut position) = self.position.lock() {
+            // unless another source has been opened meanwhile
+            if position.0 == source_id {
+                position.1 = received;
+            }
+        }
+        log::debug!("got {} posts of {}", received, source_id);
+        Ok(NewsUpdate::from_wall_async(&wall, storage).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vk_provider::FixtureApi;
+
+    #[tokio::test]
+    async fn page_wall_back() {
+        let api = FixtureApi::new().with_walls_from_dir("resources/tests/wall");
+        let storage = Storage::for_test("wall");
+        let mut settings = Settings::default();
+        settings.news_page_size = 50;
+        let wall = WallProvider::new(&settings);
+        let first = wall
+            .next_page(&api, &storage, -40316705, true)
+            .await
+            .unwrap();
+        assert_eq!(first.items().len(), 50);
+        let newest = first.items()[0].timestamp;
+        assert!(first.items().iter().all(|item| item.timestamp <= newest));
+        let second = wall
+            .next_page(&api, &storage, -40316705, false)
+            .await
+            .unwrap();
+        assert_eq!(second.items().len(), 32);
+        assert!(second.items()[0].timestamp < first.items()[49].timestamp);
+        // the wall is over
+        let over = wall
+            .next_page(&api, &storage, -40316705, false)
+            .await
+            .unwrap();
+        assert!(over.is_empty());
+        // and is paged again from the most recent post
+        let again = wall
+            .next_page(&api, &storage, -40316705, true)
+            .await
+            .unwrap();
+        assert_eq!(again.items()[0].timestamp, newest);
+        // the wall of an unknown source is empty
+        let unknown = wall.next_page(&api, &storage, 1, false).await.unwrap();
+        assert!(unknown.is_empty());
+    }
+}